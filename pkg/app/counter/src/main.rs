@@ -1,5 +1,6 @@
 #![no_std]
 #![no_main]
+#![feature(thread_local)]
 
 use lib::{sync::{Semaphore, SpinLock}, utils::sleep, *};
 
@@ -8,6 +9,10 @@ extern crate lib;
 const THREAD_COUNT: usize = 8;
 static mut COUNTER: isize = 0;
 
+// every thread counts its own increments
+#[thread_local]
+static mut LOCAL_COUNTER: usize = 0;
+
 static mut SPIN_LOCK: SpinLock = SpinLock::new();
 static mut SEMAPHORE: Semaphore = Semaphore::new(0);
 
@@ -64,6 +69,28 @@ fn test_semaphore(){
 
 }
 
+fn test_thread(){
+    let handles = (0..THREAD_COUNT)
+        .map(|i| {
+            thread::spawn(move || {
+                do_counter_inc();
+                (i, unsafe { LOCAL_COUNTER })
+            })
+        })
+        .collect::<vec::Vec<_>>();
+
+    let cpid = sys_get_pid();
+    sys_stat();
+
+    for handle in handles {
+        println!("#{} joining thread #{}...", cpid, handle.tid());
+        let ret = handle.join();
+        println!("thread returned {:?}", ret);
+    }
+
+    println!("THREAD COUNTER result: {}", unsafe { COUNTER });
+}
+
 fn main() -> isize {
     //test_spin();
 
@@ -71,6 +98,10 @@ fn main() -> isize {
     
     test_semaphore();
 
+    unsafe {COUNTER = 0;}
+
+    test_thread();
+
     0
 }
 
@@ -80,6 +111,7 @@ fn do_counter_inc() {
         unsafe {SPIN_LOCK.acquire();} 
         inc_counter();
         unsafe {SPIN_LOCK.release();}
+        unsafe {LOCAL_COUNTER += 1;}
     }
 }
 
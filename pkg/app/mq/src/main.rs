@@ -1,77 +1,129 @@
 #![no_std]
 #![no_main]
 
-use lib::{*, sync::Semaphore};
+use lib::{ipc::MessageQueue, *};
 
 extern crate lib;
 
 const THREAD_COUNT: usize = 16;
-static mut MESSAGES: isize = 0;
+const MESSAGE_COUNT: usize = 10;
 
-static SEM_EMPTY: Semaphore = Semaphore::new(0);
-static SEM_FULL: Semaphore = Semaphore::new(1);
-static mut SEMAPHORE: Semaphore = Semaphore::new(2);
+/// Producers tag their messages with this type
+const MSG_DATA: i64 = 1;
 
 fn main() {
     let mut pids = [0u16; THREAD_COUNT];
-    unsafe {
-        SEMAPHORE.init(1);
-        SEM_EMPTY.init(THREAD_COUNT * 2);
-        SEM_FULL.init(0);
-    }
+    let queue = MessageQueue::private().expect("failed to create message queue");
+    // small enough to make the producers block sometimes
+    queue.set_capacity(64);
 
-    for i in 0..THREAD_COUNT{
+    for i in 0..THREAD_COUNT {
         let pid = sys_fork();
-        if pid == 0{
-            if i % 2 == 0{
-                for _ in 0..10{
-                    produce_message();
-                }
-            }
-            else{
-                for _ in 0..10{
-                    consume_message();
-                }
+        if pid == 0 {
+            if i % 2 == 0 {
+                produce(&queue);
+            } else {
+                consume(&queue);
             }
             sys_exit(0);
-        }
-        else {
+        } else {
             pids[i] = pid; // only parent knows child's pid
         }
     }
     let cpid = sys_get_pid();
     println!("process #{} holds threads: {:?}", cpid, &pids);
-    //sys_stat();
 
-    for i in 0..THREAD_COUNT {
-        //println!("#{} waiting for #{}...", cpid, pids[i]);
-        //sys_stat();
-        sys_wait_pid(pids[i]);
-        println!("{} exit, {} messages left",pids[i], unsafe{MESSAGES});
+    for pid in pids {
+        sys_wait_pid(pid);
+    }
+
+    if let Some(stat) = queue.stat() {
+        println!(
+            "sent {}, received {}, {} messages ({} bytes) left",
+            stat.sent, stat.received, stat.messages, stat.bytes
+        );
     }
+    queue.remove();
 
-    println!("finally {} message is left",unsafe {MESSAGES});
+    poll_queue_and_stdin();
 }
 
-fn produce_message(){
-    unsafe{
-        SEM_EMPTY.wait();
-        SEMAPHORE.wait();
-        MESSAGES += 1;
-        println!("produced a message, now we have {} messages",MESSAGES);
-        SEMAPHORE.signal();
-        SEM_FULL.signal();
+/// Wait on the console and a message queue at the same time
+fn poll_queue_and_stdin() {
+    let queue = MessageQueue::private().expect("failed to create message queue");
+    let qfd = queue.open_fd().expect("failed to open message queue");
+
+    let pid = sys_fork();
+    if pid == 0 {
+        for n in 1..=3 {
+            utils::sleep(1000);
+            queue.send(MSG_DATA, format!("tick {}", n).as_bytes());
+        }
+        sys_exit(0);
     }
+
+    println!("waiting for 3 messages, type something meanwhile...");
+    let mut fds = [
+        PollFd::new(0, PollEvents::IN),
+        PollFd::new(qfd, PollEvents::IN),
+    ];
+    let mut received = 0;
+    while received < 3 {
+        match sys_poll(&mut fds, 5000) {
+            Some(0) => {
+                println!("timed out");
+                break;
+            }
+            None => break,
+            Some(_) => {}
+        }
+
+        let mut buf = [0u8; 64];
+        if fds[0].revents.contains(PollEvents::IN) {
+            if let Some(len) = sys_read(0, &mut buf[..1]) {
+                println!("stdin: {:?}", core::str::from_utf8(&buf[..len]).unwrap_or("?"));
+            }
+        }
+        if fds[1].revents.contains(PollEvents::IN) {
+            if let Some(len) = sys_read(qfd, &mut buf) {
+                println!("queue: {}", core::str::from_utf8(&buf[..len]).unwrap_or("<invalid>"));
+                received += 1;
+            }
+        }
+    }
+
+    sys_wait_pid(pid);
+    sys_close(qfd);
+    queue.remove();
 }
 
-fn consume_message(){
-    unsafe{
-        SEM_FULL.wait();
-        SEMAPHORE.wait();
-        MESSAGES -= 1;
-        println!("consumed a message, now we have {} messages",MESSAGES);
-        SEMAPHORE.signal();
-        SEM_EMPTY.signal();
+fn produce(queue: &MessageQueue) {
+    let pid = sys_get_pid();
+    for n in 0..MESSAGE_COUNT {
+        let msg = format!("#{} says hello ({}/{})", pid, n + 1, MESSAGE_COUNT);
+        if !queue.send(MSG_DATA, msg.as_bytes()) {
+            errln!("#{} failed to send", pid);
+            return;
+        }
+    }
+}
+
+fn consume(queue: &MessageQueue) {
+    let pid = sys_get_pid();
+    let mut buf = [0u8; 64];
+    for _ in 0..MESSAGE_COUNT {
+        match queue.recv(0, &mut buf) {
+            Some((mtype, len)) => println!(
+                "#{} got [{}] {}",
+                pid,
+                mtype,
+                core::str::from_utf8(&buf[..len]).unwrap_or("<invalid>")
+            ),
+            None => {
+                errln!("#{} failed to receive", pid);
+                return;
+            }
+        }
     }
 }
 
@@ -2,6 +2,7 @@
 #![no_main]
 
 use lib::*;
+use lib::string::String;
 use lib::vec::Vec;
 
 extern crate lib;
@@ -10,12 +11,18 @@ const HELP_INFO: &'static str = {
     "
     Commands:
         ps              | show process info
-        lsapp           | show app info
-        exec <app_name> | execute app
+        lsapp [-l]      | show app info, -l for details
+        exec <app|path> | execute app in /APP or at path
         kill <pid>      | kill process
         clear           | clear screen
         exit            | exit shell
+        reboot          | reboot the machine
         sleep <time>    | sleep 
+        ls [path]       | list directory
+        cat <file>      | print file
+        stat <path>     | show file info
+        cd <dir>        | change directory
+        pwd             | print working directory
 "
 };
     
@@ -25,14 +32,18 @@ fn main()
     println!("                                                                         by 22331067");
     println!("                                                 Enter 'help' for a list of commands");
     loop{
-        print!("> ");
+        let mut cwd = [0u8; 128];
+        print!("{} > ", sys_getcwd(&mut cwd).unwrap_or("?"));
         let input = stdin().read_line();
         let line: Vec<&str> = input.trim().split(' ').collect();
         print!("\n");
         match line[0]{
             "help" => print!("{}",HELP_INFO),
-            "ps" => {sys_stat()}, 
-            "lsapp" => {sys_list_app()},
+            "ps" => ps(),
+            "lsapp" => match line.get(1) {
+                Some(&"-l") => lsapp_long(),
+                _ => sys_list_app(),
+            },
             "exec" => {sys_spawn(line[1]);},
             "kill" => {
                 let pid:isize = line[1].parse().expect("invalid input, this is not a pid!");
@@ -40,13 +51,132 @@ fn main()
             },
             "clear" => {print!("\x1b[1;1H\x1b[2J")},
             "exit" => sys_exit(0),
+            "reboot" => sys_reboot(),
             "sleep" => {
                 let time:i64 = line[1].parse().expect("not a number");
                 lib::utils::sleep(time)
             }
+            "ls" => ls(line.get(1).copied().unwrap_or(".")),
+            "cat" => {
+                if let Some(path) = line.get(1) {
+                    cat(path);
+                }
+            }
+            "stat" => {
+                if let Some(path) = line.get(1) {
+                    stat(path);
+                }
+            }
+            "cd" => {
+                let path = line.get(1).copied().unwrap_or("/");
+                if !sys_chdir(path) {
+                    println!("cd: {}: no such directory", path);
+                }
+            }
+            "pwd" => {
+                let mut cwd = [0u8; 128];
+                println!("{}", sys_getcwd(&mut cwd).unwrap_or("?"));
+            }
             _ => {},
         }
     }
 }
 
+fn ls(path: &str) {
+    let Some(fd) = sys_open(path, OpenFlags::READ | OpenFlags::DIRECTORY) else {
+        println!("ls: {}: cannot open directory", path);
+        return;
+    };
+
+    let mut entries = [DirEntry::default(); 16];
+    while let Some(count) = sys_getdents(fd, &mut entries) {
+        if count == 0 {
+            break;
+        }
+        for entry in &entries[..count] {
+            if entry.is_dir() {
+                println!("  {:>8}  {}/", "<DIR>", entry.name());
+            } else {
+                println!("  {:>8}  {}", entry.size, entry.name());
+            }
+        }
+    }
+
+    sys_close(fd);
+}
+
+fn ps() {
+    let mut records = vec![ProcessRecord::default(); 16];
+    loop {
+        let total = sys_list_process(&mut records);
+        if total <= records.len() {
+            records.truncate(total);
+            break;
+        }
+        records.resize(total, ProcessRecord::default());
+    }
+
+    println!("  PID | PPID | Process Name   |  Ticks  | Memory Usage | Status ");
+    for p in records.iter().filter(|p| p.is_alive()) {
+        println!(
+            " #{:-3} | #{:-3} | {:14} | {:7} | {:12} | {:?}",
+            p.pid,
+            p.ppid,
+            p.name(),
+            p.ticks,
+            p.memory,
+            p.status
+        );
+    }
+}
+
+fn lsapp_long() {
+    // the boot app list holds at most 16 apps
+    let mut records = [AppRecord::default(); 16];
+    let total = sys_list_app_info(&mut records).min(records.len());
+
+    println!("  Name             |   Size   |       Entry        | Segs |  Memory  ");
+    for app in &records[..total] {
+        println!(
+            "  {:16} | {:8} | {:#018x} | {:4} | {:8}",
+            app.name(),
+            app.size,
+            app.entry,
+            app.segments,
+            app.memory
+        );
+    }
+}
+
+fn cat(path: &str) {
+    let Some(fd) = sys_open(path, OpenFlags::READ) else {
+        println!("cat: {}: cannot open file", path);
+        return;
+    };
+
+    let mut buf = [0u8; 256];
+    while let Some(count) = sys_read(fd, &mut buf) {
+        if count == 0 {
+            break;
+        }
+        print!("{}", String::from_utf8_lossy(&buf[..count]));
+    }
+    println!();
+
+    sys_close(fd);
+}
+
+fn stat(path: &str) {
+    let Some(stat) = sys_path_stat(path) else {
+        println!("stat: {}: no such file", path);
+        return;
+    };
+
+    println!("  File: {}", path);
+    println!("  Type: {:?}", stat.kind);
+    println!("  Size: {}", stat.size);
+    println!("  Inode: {}", stat.ino);
+    println!("  Modified: {}", stat.modified);
+}
+
 entry!(main);
\ No newline at end of file
@@ -0,0 +1,75 @@
+#![no_std]
+#![no_main]
+
+use lib::{ipc::SharedMemory, *};
+
+extern crate lib;
+
+const SHM_KEY: u32 = 0x5348;
+const SHM_SIZE: usize = 4096;
+
+/// The reader writes its answer behind the greeting
+const REPLY_OFFSET: usize = 2048;
+
+fn main() {
+    let ret = match SharedMemory::get(SHM_KEY) {
+        Some(shm) => reader(shm),
+        None => writer(),
+    };
+    sys_exit(ret);
+}
+
+/// Create the segment, spawn another `shm` and wait for its reply
+fn writer() -> isize {
+    let Some(shm) = SharedMemory::create(SHM_KEY, SHM_SIZE) else {
+        errln!("failed to create shared memory");
+        return 1;
+    };
+    let mem = shm.attach().expect("failed to attach shared memory");
+    println!("#{} mapped segment #{} at {:p}", sys_get_pid(), shm.id(), mem.as_ptr());
+
+    let greeting = format!("hello from #{}", sys_get_pid());
+    write_str(mem, 0, &greeting);
+
+    // not forked, so nothing is shared but the segment
+    let pid = sys_spawn("shm");
+    sys_wait_pid(pid);
+
+    println!("#{} got reply: {}", sys_get_pid(), read_str(mem, REPLY_OFFSET));
+    if let Some(stat) = shm.stat() {
+        println!("segment #{}: {} bytes, {} attached", stat.id, stat.size, stat.attached);
+    }
+
+    shm.detach(mem);
+    shm.remove();
+    0
+}
+
+fn reader(shm: SharedMemory) -> isize {
+    let Some(mem) = shm.attach() else {
+        errln!("failed to attach shared memory");
+        return 1;
+    };
+    println!("#{} mapped segment #{} at {:p}", sys_get_pid(), shm.id(), mem.as_ptr());
+    println!("#{} read: {}", sys_get_pid(), read_str(mem, 0));
+
+    let reply = format!("hello back from #{}", sys_get_pid());
+    write_str(mem, REPLY_OFFSET, &reply);
+
+    shm.detach(mem);
+    0
+}
+
+/// Strings are stored as a length byte followed by the bytes
+fn write_str(mem: &mut [u8], offset: usize, s: &str) {
+    let len = s.len().min(255);
+    mem[offset] = len as u8;
+    mem[offset + 1..offset + 1 + len].copy_from_slice(&s.as_bytes()[..len]);
+}
+
+fn read_str(mem: &[u8], offset: usize) -> &str {
+    let len = mem[offset] as usize;
+    core::str::from_utf8(&mem[offset + 1..offset + 1 + len]).unwrap_or("<invalid>")
+}
+
+entry!(main);
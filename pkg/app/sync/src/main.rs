@@ -0,0 +1,118 @@
+#![no_std]
+#![no_main]
+
+use lib::{
+    sync::{Barrier, Condvar, Mutex, Once, RwLock},
+    *,
+};
+
+extern crate lib;
+
+const THREAD_COUNT: usize = 8;
+const ROUNDS: usize = 100;
+
+static INIT: Once = Once::new();
+static INIT_RUNS: Mutex<usize> = Mutex::new(0);
+
+static COUNTER: Mutex<usize> = Mutex::new(0);
+
+/// Writers keep all slots equal, readers check that they are
+static SLOTS: RwLock<[usize; 4]> = RwLock::new([0; 4]);
+
+static BARRIER: Barrier = Barrier::new(THREAD_COUNT);
+
+/// Items produced but not consumed yet, and if the producer is done
+static QUEUE: Mutex<(usize, bool)> = Mutex::new((0, false));
+static QUEUE_CHANGED: Condvar = Condvar::new();
+
+fn main() -> isize {
+    let handles = (0..THREAD_COUNT)
+        .map(|i| thread::spawn(move || worker(i)))
+        .collect::<vec::Vec<_>>();
+
+    let mut torn = 0;
+    for handle in handles {
+        // a thread that was killed counts as a failure
+        torn += handle.join().unwrap_or(1);
+    }
+
+    let ok = [
+        check("Once", *INIT_RUNS.lock(), 1),
+        check("Mutex", *COUNTER.lock(), THREAD_COUNT * ROUNDS),
+        check("RwLock torn reads", torn, 0),
+        check("RwLock", SLOTS.read()[0], THREAD_COUNT / 2 * ROUNDS),
+        test_condvar(),
+    ];
+
+    if ok.iter().all(|&ok| ok) {
+        0
+    } else {
+        1
+    }
+}
+
+/// Returns how many torn reads it saw
+fn worker(i: usize) -> usize {
+    INIT.call_once(|| *INIT_RUNS.lock() += 1);
+
+    for _ in 0..ROUNDS {
+        *COUNTER.lock() += 1;
+    }
+
+    // nobody starts on the rwlock before everyone is done with the mutex
+    if BARRIER.wait().is_leader() {
+        println!("#{} opened the barrier", thread::current());
+    }
+
+    let mut torn = 0;
+    for _ in 0..ROUNDS {
+        if i % 2 == 0 {
+            let mut slots = SLOTS.write();
+            for slot in slots.iter_mut() {
+                *slot += 1;
+            }
+        } else {
+            let slots = SLOTS.read();
+            if slots.iter().any(|&s| s != slots[0]) {
+                torn += 1;
+            }
+        }
+    }
+    torn
+}
+
+/// One producer, the main thread consumes
+fn test_condvar() -> bool {
+    let producer = thread::spawn(|| {
+        for _ in 0..ROUNDS {
+            QUEUE.lock().0 += 1;
+            QUEUE_CHANGED.notify_one();
+        }
+        QUEUE.lock().1 = true;
+        QUEUE_CHANGED.notify_all();
+    });
+
+    let mut consumed = 0;
+    loop {
+        let mut queue = QUEUE_CHANGED.wait_while(QUEUE.lock(), |q| q.0 == 0 && !q.1);
+        consumed += core::mem::take(&mut queue.0);
+        if queue.1 {
+            break;
+        }
+    }
+    producer.join();
+
+    check("Condvar", consumed, ROUNDS)
+}
+
+fn check(name: &str, got: usize, expected: usize) -> bool {
+    if got == expected {
+        println!("{}: {} ok", name, got);
+        true
+    } else {
+        errln!("{}: got {}, expected {}", name, got, expected);
+        false
+    }
+}
+
+entry!(main);
@@ -19,6 +19,8 @@ pub struct Config<'a> {
     pub load_apps: bool,
     /// 加分项：字符串型启动配置变量 log_level
     pub log_level: &'a str,
+    /// Timer interrupts per second, the scheduler tick
+    pub hz: u64,
 }
 
 pub const DEFAULT_CONFIG: Config = Config {
@@ -32,6 +34,7 @@ pub const DEFAULT_CONFIG: Config = Config {
     ///////////////////////
     log_level: "Error",
     ///////////////////////
+    hz: 100,
 };
 
 impl<'a> Config<'a> {
@@ -75,6 +78,7 @@ impl<'a> Config<'a> {
             //////////////////////////////////////
             "log_level" => self.log_level = value,
             //////////////////////////////////////
+            "hz" => self.hz = r10,
             _ => warn!("undefined config key: {}", key),
         }
         //info!("self.load_apps = {}",self.load_apps);
@@ -52,6 +52,12 @@ pub struct BootInfo {
 
     // Loaded apps
     pub loaded_apps: Option<AppList>,
+
+    /// Physical address of the ACPI RSDP, from the UEFI config table
+    pub rsdp_addr: Option<u64>,
+
+    /// Timer interrupts per second
+    pub hz: u64,
 }
 
 /// Get current page table from CR3
@@ -109,6 +109,9 @@ fn efi_main(image: uefi::Handle, mut system_table: SystemTable<Boot>) -> Status
 
     free_elf(bs, elf);
 
+    // ACPI tables stay in memory after boot services exit
+    let rsdp_addr = find_rsdp(&system_table);
+
     // 5. Exit boot and jump to ELF entry
     info!("Exiting boot services...");
 
@@ -122,6 +125,8 @@ fn efi_main(image: uefi::Handle, mut system_table: SystemTable<Boot>) -> Status
         physical_memory_offset: config.physical_memory_offset,
         system_table: runtime,
         loaded_apps: apps,
+        rsdp_addr,
+        hz: config.hz,
     };
 
     // align stack to 8 bytes
@@ -131,3 +136,25 @@ fn efi_main(image: uefi::Handle, mut system_table: SystemTable<Boot>) -> Status
         jump_to_entry(&bootinfo, stacktop);
     }
 }
+
+/// Find the RSDP in the UEFI config table, prefer the ACPI 2.0 one
+fn find_rsdp(system_table: &SystemTable<Boot>) -> Option<u64> {
+    use uefi::table::cfg::{ACPI2_GUID, ACPI_GUID};
+
+    let config = system_table.config_table();
+    let entry = config
+        .iter()
+        .find(|e| e.guid == ACPI2_GUID)
+        .or_else(|| config.iter().find(|e| e.guid == ACPI_GUID));
+
+    match entry {
+        Some(e) => {
+            info!("ACPI RSDP at {:p}", e.address);
+            Some(e.address as u64)
+        }
+        None => {
+            warn!("ACPI RSDP not found");
+            None
+        }
+    }
+}
@@ -87,6 +87,21 @@ pub fn map_range(
     Ok(Page::range(range_start, range_end))
 }
 
+/// Unmap a range of memory mapped by `map_range`
+///
+/// Returns the frames, they are not freed since other CPUs may still have
+/// the pages in their TLB
+pub fn unmap_range(range: PageRange, page_table: &mut impl Mapper<Size4KiB>) -> Vec<PhysFrame> {
+    let mut frames = Vec::new();
+    for page in range {
+        if let Ok((frame, flush)) = page_table.unmap(page) {
+            flush.flush();
+            frames.push(frame);
+        }
+    }
+    frames
+}
+
 /// Load & Map ELF file
 ///
 /// load segments in ELF file to new frames and set page table
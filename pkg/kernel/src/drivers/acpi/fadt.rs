@@ -0,0 +1,116 @@
+//! FADT, the Fixed ACPI Description Table
+//!
+//! Where the power management registers are, and the DSDT whose `_S5_`
+//! package holds the sleep type for soft off.
+
+use super::*;
+
+/// Reset register is supported
+const FLAG_RESET_REG_SUP: u32 = 1 << 10;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Fadt {
+    pub sci_irq: u16,
+    /// Port to write `acpi_enable` to, 0 if ACPI is always on
+    pub smi_cmd: u32,
+    pub acpi_enable: u8,
+    pub pm1a_cnt: u32,
+    pub pm1b_cnt: u32,
+    /// ACPI PM timer port, 0 if there is none
+    pub pm_timer: u32,
+    /// CMOS register of the RTC century, 0 if there is none
+    pub century: u8,
+    pub flags: u32,
+    pub reset_reg: Option<GenericAddress>,
+    pub reset_value: u8,
+    pub dsdt: u64,
+    /// SLP_TYPa and SLP_TYPb for S5
+    pub s5: Option<(u8, u8)>,
+}
+
+impl Fadt {
+    pub(super) fn parse(addr: u64) -> Self {
+        let len = header(addr).length as u64;
+        let field = |offset: u64| addr + offset;
+
+        unsafe {
+            let flags: u32 = read_phys(field(112));
+            let reset_reg = (len >= 129 && flags & FLAG_RESET_REG_SUP != 0)
+                .then(|| read_phys::<GenericAddress>(field(116)));
+
+            // X_DSDT wins if it is there
+            let x_dsdt: u64 = if len >= 148 { read_phys(field(140)) } else { 0 };
+            let dsdt = if x_dsdt != 0 { x_dsdt } else { read_phys::<u32>(field(40)) as u64 };
+
+            Self {
+                sci_irq: read_phys(field(46)),
+                smi_cmd: read_phys(field(48)),
+                acpi_enable: read_phys(field(52)),
+                pm1a_cnt: read_phys(field(64)),
+                pm1b_cnt: read_phys(field(68)),
+                pm_timer: read_phys(field(76)),
+                century: read_phys(field(108)),
+                flags,
+                reset_reg,
+                reset_value: if reset_reg.is_some() { read_phys(field(128)) } else { 0 },
+                dsdt,
+                s5: find_s5(dsdt),
+            }
+        }
+    }
+}
+
+/// Sleep types from `Name (_S5, Package () { a, b, ... })` in the DSDT
+///
+/// Not an AML interpreter, just looks for the encoding firmware uses for
+/// it in practice.
+fn find_s5(dsdt: u64) -> Option<(u8, u8)> {
+    if dsdt == 0 || &header(dsdt).signature != b"DSDT" {
+        return None;
+    }
+
+    let len = header(dsdt).length as usize;
+    let aml = unsafe {
+        core::slice::from_raw_parts(
+            physical_to_virtual(dsdt + SDT_HEADER_SIZE as u64) as *const u8,
+            len.checked_sub(SDT_HEADER_SIZE)?,
+        )
+    };
+
+    const NAME_OP: u8 = 0x08;
+    const PACKAGE_OP: u8 = 0x12;
+    const BYTE_PREFIX: u8 = 0x0A;
+
+    let pos = aml.windows(4).position(|w| w == b"_S5_")?;
+    let named = match pos {
+        0 => false,
+        1 => aml[0] == NAME_OP,
+        _ => aml[pos - 1] == NAME_OP || (aml[pos - 1] == b'\\' && aml[pos - 2] == NAME_OP),
+    };
+    if !named || *aml.get(pos + 4)? != PACKAGE_OP {
+        return None;
+    }
+
+    // PkgLength: the top two bits of the lead byte count the bytes after it
+    let mut i = pos + 5;
+    i += (*aml.get(i)? >> 6) as usize + 1;
+    // NumElements
+    i += 1;
+
+    let mut element = || -> Option<u8> {
+        let value = match *aml.get(i)? {
+            BYTE_PREFIX => {
+                i += 1;
+                *aml.get(i)?
+            }
+            // ZeroOp, OneOp and anything else taken as is
+            b => b,
+        };
+        i += 1;
+        Some(value)
+    };
+
+    let a = element()?;
+    let b = element()?;
+    Some((a, b))
+}
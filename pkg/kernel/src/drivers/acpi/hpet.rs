@@ -0,0 +1,30 @@
+//! HPET description table, where the high precision event timer is
+
+use super::*;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Hpet {
+    /// Physical address of the registers
+    pub addr: u64,
+    pub number: u8,
+    /// Smallest period in periodic mode, in main counter ticks
+    pub min_tick: u16,
+    pub comparators: u8,
+    pub counter_64bit: bool,
+}
+
+impl Hpet {
+    pub(super) fn parse(addr: u64) -> Self {
+        unsafe {
+            let block_id: u32 = read_phys(addr + 36);
+            let base: GenericAddress = read_phys(addr + 40);
+            Self {
+                addr: base.address,
+                number: read_phys(addr + 52),
+                min_tick: read_phys(addr + 53),
+                comparators: ((block_id >> 8) & 0x1F) as u8 + 1,
+                counter_64bit: block_id & (1 << 13) != 0,
+            }
+        }
+    }
+}
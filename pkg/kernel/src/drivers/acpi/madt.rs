@@ -0,0 +1,129 @@
+//! MADT, the Multiple APIC Description Table
+//!
+//! Lists the local APIC of every processor, the IO APICs and how the
+//! legacy ISA irqs are wired to them.
+
+use super::*;
+
+#[derive(Debug, Clone, Copy)]
+pub struct LocalApicInfo {
+    pub processor_id: u8,
+    pub apic_id: u8,
+    /// Usable, disabled ones can't be started
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IoApicInfo {
+    pub id: u8,
+    pub addr: u32,
+    /// First global system interrupt it handles
+    pub gsi_base: u32,
+}
+
+/// Where an ISA irq ends up, identity mapped, edge triggered and active
+/// high unless the MADT overrides it
+#[derive(Debug, Clone, Copy)]
+pub struct IrqRoute {
+    pub gsi: u32,
+    pub level: bool,
+    pub active_low: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SourceOverride {
+    irq: u8,
+    route: IrqRoute,
+}
+
+#[derive(Debug)]
+pub struct Madt {
+    pub lapic_addr: u64,
+    pub local_apics: Vec<LocalApicInfo>,
+    pub io_apics: Vec<IoApicInfo>,
+    overrides: Vec<SourceOverride>,
+}
+
+const ENTRY_LOCAL_APIC: u8 = 0;
+const ENTRY_IO_APIC: u8 = 1;
+const ENTRY_SOURCE_OVERRIDE: u8 = 2;
+const ENTRY_LAPIC_ADDR_OVERRIDE: u8 = 5;
+
+impl Madt {
+    pub(super) fn parse(addr: u64) -> Self {
+        let end = addr + header(addr).length as u64;
+
+        let body = addr + SDT_HEADER_SIZE as u64;
+        let mut madt = Madt {
+            lapic_addr: unsafe { read_phys::<u32>(body) } as u64,
+            local_apics: Vec::new(),
+            io_apics: Vec::new(),
+            overrides: Vec::new(),
+        };
+
+        // entries follow the lapic address and flags
+        let mut entry = body + 8;
+        while entry + 2 <= end {
+            let (ty, len): (u8, u8) = unsafe { (read_phys(entry), read_phys(entry + 1)) };
+            if len < 2 {
+                warn!("Broken MADT entry at {:#x}", entry);
+                break;
+            }
+
+            unsafe {
+                match ty {
+                    ENTRY_LOCAL_APIC => madt.local_apics.push(LocalApicInfo {
+                        processor_id: read_phys(entry + 2),
+                        apic_id: read_phys(entry + 3),
+                        enabled: read_phys::<u32>(entry + 4) & 1 != 0,
+                    }),
+                    ENTRY_IO_APIC => madt.io_apics.push(IoApicInfo {
+                        id: read_phys(entry + 2),
+                        addr: read_phys(entry + 4),
+                        gsi_base: read_phys(entry + 8),
+                    }),
+                    ENTRY_SOURCE_OVERRIDE => {
+                        // MPS INTI flags: polarity in bits 0..2, trigger in 2..4,
+                        // 0b11 is active low / level, anything else the ISA default
+                        let flags: u16 = read_phys(entry + 8);
+                        madt.overrides.push(SourceOverride {
+                            irq: read_phys(entry + 3),
+                            route: IrqRoute {
+                                gsi: read_phys(entry + 4),
+                                active_low: flags & 0b11 == 0b11,
+                                level: (flags >> 2) & 0b11 == 0b11,
+                            },
+                        });
+                    }
+                    ENTRY_LAPIC_ADDR_OVERRIDE => madt.lapic_addr = read_phys(entry + 4),
+                    _ => {}
+                }
+            }
+
+            entry += len as u64;
+        }
+
+        madt
+    }
+
+    /// Route of ISA irq `irq`
+    pub fn isa_irq(&self, irq: u8) -> IrqRoute {
+        self.overrides
+            .iter()
+            .find(|o| o.irq == irq)
+            .map(|o| o.route)
+            .unwrap_or(IrqRoute {
+                gsi: irq as u32,
+                level: false,
+                active_low: false,
+            })
+    }
+
+    /// The IO APIC handling `gsi`, the one with the closest lower base
+    pub fn io_apic_for(&self, gsi: u32) -> Option<&IoApicInfo> {
+        self.io_apics
+            .iter()
+            .filter(|io| io.gsi_base <= gsi)
+            .max_by_key(|io| io.gsi_base)
+    }
+}
@@ -0,0 +1,192 @@
+//! ACPI tables
+//!
+//! The bootloader takes the RSDP from the UEFI config table, the tables
+//! it points to are reached through the physical memory mapping. Only
+//! parsed as far as the kernel needs them: the MADT for processors and
+//! interrupt routing, the FADT for power off and reset, and the HPET.
+
+mod fadt;
+mod hpet;
+mod madt;
+mod power;
+
+pub use fadt::*;
+pub use hpet::*;
+pub use madt::*;
+pub use power::*;
+
+use crate::memory::physical_to_virtual;
+use alloc::string::String;
+use alloc::vec::Vec;
+use boot::BootInfo;
+
+/// Physical addresses of the tables listed in the RSDT/XSDT
+static TABLES: spin::Once<Vec<u64>> = spin::Once::new();
+
+static MADT: spin::Once<Madt> = spin::Once::new();
+static FADT: spin::Once<Fadt> = spin::Once::new();
+static HPET: spin::Once<Hpet> = spin::Once::new();
+
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+struct Rsdp {
+    signature: [u8; 8],
+    checksum: u8,
+    oem_id: [u8; 6],
+    revision: u8,
+    rsdt_addr: u32,
+    // ACPI 2.0+
+    length: u32,
+    xsdt_addr: u64,
+    ext_checksum: u8,
+    _reserved: [u8; 3],
+}
+
+/// Header shared by all system description tables
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct SdtHeader {
+    pub signature: [u8; 4],
+    pub length: u32,
+    pub revision: u8,
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: u32,
+    pub creator_revision: u32,
+}
+
+pub const SDT_HEADER_SIZE: usize = core::mem::size_of::<SdtHeader>();
+
+/// Generic Address Structure, a register in memory or io space
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct GenericAddress {
+    pub space: u8,
+    pub bit_width: u8,
+    pub bit_offset: u8,
+    pub access_size: u8,
+    pub address: u64,
+}
+
+impl GenericAddress {
+    pub const SPACE_MEMORY: u8 = 0;
+    pub const SPACE_IO: u8 = 1;
+
+    pub unsafe fn write(&self, value: u64) {
+        use x86_64::instructions::port::Port;
+
+        let addr = self.address;
+        match (self.space, self.bit_width) {
+            (Self::SPACE_IO, 16) => Port::<u16>::new(addr as u16).write(value as u16),
+            (Self::SPACE_IO, 32) => Port::<u32>::new(addr as u16).write(value as u32),
+            (Self::SPACE_IO, _) => Port::<u8>::new(addr as u16).write(value as u8),
+            (Self::SPACE_MEMORY, 16) => (physical_to_virtual(addr) as *mut u16).write_volatile(value as u16),
+            (Self::SPACE_MEMORY, 32) => (physical_to_virtual(addr) as *mut u32).write_volatile(value as u32),
+            (Self::SPACE_MEMORY, 64) => (physical_to_virtual(addr) as *mut u64).write_volatile(value),
+            (Self::SPACE_MEMORY, _) => (physical_to_virtual(addr) as *mut u8).write_volatile(value as u8),
+            (space, _) => warn!("Unsupported ACPI address space {}", space),
+        }
+    }
+}
+
+/// All bytes of a table add up to 0
+fn checksum_ok(addr: u64, len: usize) -> bool {
+    let bytes = unsafe { core::slice::from_raw_parts(physical_to_virtual(addr) as *const u8, len) };
+    bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) == 0
+}
+
+/// Read a value of a table at physical `addr`, may be unaligned
+pub(crate) unsafe fn read_phys<T: Copy>(addr: u64) -> T {
+    core::ptr::read_unaligned(physical_to_virtual(addr) as *const T)
+}
+
+pub fn header(addr: u64) -> SdtHeader {
+    unsafe { read_phys(addr) }
+}
+
+pub fn init(boot_info: &'static BootInfo) {
+    let Some(rsdp_addr) = boot_info.rsdp_addr else {
+        warn!("No ACPI RSDP, ACPI disabled.");
+        return;
+    };
+
+    let rsdp: Rsdp = unsafe { read_phys(rsdp_addr) };
+    if &rsdp.signature != b"RSD PTR " || !checksum_ok(rsdp_addr, 20) {
+        warn!("Invalid ACPI RSDP at {:#x}", rsdp_addr);
+        return;
+    }
+
+    // the XSDT holds 64 bit pointers, the old RSDT 32 bit ones
+    let (root, entry_size) = if rsdp.revision >= 2 && rsdp.xsdt_addr != 0 {
+        (rsdp.xsdt_addr, 8)
+    } else {
+        (rsdp.rsdt_addr as u64, 4)
+    };
+
+    let root_header = header(root);
+    if !checksum_ok(root, root_header.length as usize) {
+        warn!("Invalid ACPI root table at {:#x}", root);
+        return;
+    }
+
+    let count = (root_header.length as usize - SDT_HEADER_SIZE) / entry_size;
+    let tables: Vec<u64> = (0..count)
+        .map(|i| {
+            let entry = root + (SDT_HEADER_SIZE + i * entry_size) as u64;
+            unsafe {
+                if entry_size == 8 {
+                    read_phys::<u64>(entry)
+                } else {
+                    read_phys::<u32>(entry) as u64
+                }
+            }
+        })
+        .filter(|&addr| addr != 0 && checksum_ok(addr, header(addr).length as usize))
+        .collect();
+
+    let names = tables
+        .iter()
+        .map(|&addr| String::from_utf8_lossy(&header(addr).signature).into_owned())
+        .collect::<Vec<_>>();
+    info!("ACPI {} tables: {}", rsdp.revision, names.join(" "));
+
+    TABLES.call_once(|| tables);
+
+    if let Some(madt) = find_table(b"APIC").map(|addr| MADT.call_once(|| Madt::parse(addr))) {
+        info!(
+            "MADT: {} CPUs, {} IO APICs, LAPIC at {:#x}",
+            madt.local_apics.iter().filter(|l| l.enabled).count(),
+            madt.io_apics.len(),
+            madt.lapic_addr
+        );
+    }
+    if let Some(fadt) = find_table(b"FACP").map(|addr| FADT.call_once(|| Fadt::parse(addr))) {
+        info!("FADT: SCI irq {}, S5 {:?}", fadt.sci_irq, fadt.s5);
+    }
+    if let Some(hpet) = find_table(b"HPET").map(|addr| HPET.call_once(|| Hpet::parse(addr))) {
+        info!("HPET: at {:#x}, {} comparators", hpet.addr, hpet.comparators);
+    }
+}
+
+pub fn madt() -> Option<&'static Madt> {
+    MADT.get()
+}
+
+pub fn fadt() -> Option<&'static Fadt> {
+    FADT.get()
+}
+
+pub fn hpet() -> Option<&'static Hpet> {
+    HPET.get()
+}
+
+/// Physical address of the first table with `signature`
+pub fn find_table(signature: &[u8; 4]) -> Option<u64> {
+    TABLES
+        .get()?
+        .iter()
+        .copied()
+        .find(|&addr| &header(addr).signature == signature)
+}
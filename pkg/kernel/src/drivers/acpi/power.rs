@@ -0,0 +1,80 @@
+//! Soft off through S5 and reset through the FADT reset register
+
+use super::*;
+use x86_64::instructions::port::Port;
+
+/// SCI_EN in PM1 control, set once ACPI is enabled
+const SCI_EN: u16 = 1 << 0;
+const SLP_TYP_SHIFT: u16 = 10;
+const SLP_EN: u16 = 1 << 13;
+
+/// Switch from legacy to ACPI mode, if the firmware hasn't yet
+fn enable(fadt: &Fadt) {
+    let mut pm1a = Port::<u16>::new(fadt.pm1a_cnt as u16);
+    if fadt.smi_cmd == 0 || fadt.acpi_enable == 0 || unsafe { pm1a.read() } & SCI_EN != 0 {
+        return;
+    }
+
+    unsafe { Port::<u8>::new(fadt.smi_cmd as u16).write(fadt.acpi_enable) };
+    for _ in 0..300 {
+        if unsafe { pm1a.read() } & SCI_EN != 0 {
+            return;
+        }
+        crate::pit::udelay(1000);
+    }
+    warn!("ACPI was not enabled.");
+}
+
+/// Enter S5, returns only if that didn't work
+pub fn power_off() {
+    let Some(fadt) = fadt() else {
+        return;
+    };
+    let Some((typ_a, typ_b)) = fadt.s5 else {
+        warn!("No _S5_ in the DSDT, can't power off through ACPI.");
+        return;
+    };
+
+    enable(fadt);
+
+    unsafe {
+        let mut pm1a = Port::<u16>::new(fadt.pm1a_cnt as u16);
+        let value = pm1a.read() & !(0b111 << SLP_TYP_SHIFT);
+        pm1a.write(value | (typ_a as u16) << SLP_TYP_SHIFT | SLP_EN);
+
+        if fadt.pm1b_cnt != 0 {
+            let mut pm1b = Port::<u16>::new(fadt.pm1b_cnt as u16);
+            let value = pm1b.read() & !(0b111 << SLP_TYP_SHIFT);
+            pm1b.write(value | (typ_b as u16) << SLP_TYP_SHIFT | SLP_EN);
+        }
+    }
+
+    // the machine should be off by now
+    crate::pit::udelay(100_000);
+    warn!("ACPI power off failed.");
+}
+
+/// Reset through the FADT reset register, then the keyboard controller,
+/// returns only if neither worked
+pub fn reset() {
+    if let Some(reg) = fadt().and_then(|f| f.reset_reg.map(|r| (r, f.reset_value))) {
+        unsafe { reg.0.write(reg.1 as u64) };
+        crate::pit::udelay(100_000);
+        warn!("ACPI reset failed.");
+    }
+
+    // pulse the cpu reset line of the 8042
+    unsafe {
+        let mut status = Port::<u8>::new(0x64);
+        // input buffer empty, don't wait forever if there is none
+        for _ in 0..0x10000 {
+            if status.read() & 0b10 == 0 {
+                break;
+            }
+            core::hint::spin_loop();
+        }
+        status.write(0xFE);
+    }
+    crate::pit::udelay(100_000);
+    warn!("Keyboard controller reset failed.");
+}
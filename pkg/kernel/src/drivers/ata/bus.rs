@@ -0,0 +1,297 @@
+//! Register level access to one IDE channel
+//!
+//! Reference: https://wiki.osdev.org/ATA_PIO_Mode
+
+use super::*;
+use x86_64::instructions::port::{Port, PortReadOnly, PortWriteOnly};
+
+bitflags! {
+    #[derive(Debug, Clone, Copy)]
+    pub struct AtaStatus: u8 {
+        const ERR = 0x01;
+        const DRQ = 0x08;
+        const SRV = 0x10;
+        const DF = 0x20;
+        const RDY = 0x40;
+        const BSY = 0x80;
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy)]
+enum AtaCommand {
+    ReadPio = 0x20,
+    ReadPioExt = 0x24,
+    WritePio = 0x30,
+    WritePioExt = 0x34,
+    CacheFlush = 0xE7,
+    CacheFlushExt = 0xEA,
+    Identify = 0xEC,
+}
+
+/// Device control register: disable interrupts from the device
+const CTRL_NIEN: u8 = 0x02;
+/// Device control register: software reset
+const CTRL_SRST: u8 = 0x04;
+
+/// Polls before giving up on a drive
+const TIMEOUT: usize = 1_000_000;
+
+pub struct AtaBus {
+    id: u8,
+    data: Port<u16>,
+    error: PortReadOnly<u8>,
+    sector_count: PortWriteOnly<u8>,
+    lba_low: Port<u8>,
+    lba_mid: Port<u8>,
+    lba_high: Port<u8>,
+    drive: PortWriteOnly<u8>,
+    status: PortReadOnly<u8>,
+    command: PortWriteOnly<u8>,
+    alt_status: PortReadOnly<u8>,
+    control: PortWriteOnly<u8>,
+}
+
+/// What IDENTIFY DEVICE says about a drive
+#[derive(Debug, Clone)]
+pub struct AtaIdentity {
+    pub model: String,
+    pub serial: String,
+    pub sectors: usize,
+    pub lba48: bool,
+}
+
+impl AtaBus {
+    pub const fn new(id: u8, io_base: u16, ctrl_base: u16) -> Self {
+        Self {
+            id,
+            data: Port::new(io_base),
+            error: PortReadOnly::new(io_base + 1),
+            sector_count: PortWriteOnly::new(io_base + 2),
+            lba_low: Port::new(io_base + 3),
+            lba_mid: Port::new(io_base + 4),
+            lba_high: Port::new(io_base + 5),
+            drive: PortWriteOnly::new(io_base + 6),
+            status: PortReadOnly::new(io_base + 7),
+            command: PortWriteOnly::new(io_base + 7),
+            alt_status: PortReadOnly::new(ctrl_base),
+            control: PortWriteOnly::new(ctrl_base),
+        }
+    }
+
+    #[inline]
+    fn status(&mut self) -> AtaStatus {
+        AtaStatus::from_bits_retain(unsafe { self.status.read() })
+    }
+
+    #[inline]
+    fn alt_status(&mut self) -> AtaStatus {
+        AtaStatus::from_bits_retain(unsafe { self.alt_status.read() })
+    }
+
+    /// Reading the alternate status four times gives the drive the
+    /// 400ns it needs after a drive select or a command
+    fn delay(&mut self) {
+        for _ in 0..4 {
+            self.alt_status();
+        }
+    }
+
+    /// Nothing answers on a floating bus, all lines read high
+    pub fn is_floating(&mut self) -> bool {
+        unsafe { self.status.read() == 0xFF }
+    }
+
+    pub fn reset(&mut self) {
+        unsafe {
+            self.control.write(CTRL_SRST | CTRL_NIEN);
+            self.delay();
+            self.control.write(CTRL_NIEN);
+        }
+        let _ = self.wait_not_busy();
+    }
+
+    fn select(&mut self, drive: u8, head: u8) {
+        unsafe { self.drive.write(0xE0 | (drive << 4) | (head & 0x0F)) };
+        self.delay();
+    }
+
+    fn wait_not_busy(&mut self) -> FsResult<AtaStatus> {
+        for _ in 0..TIMEOUT {
+            let status = self.alt_status();
+            if !status.contains(AtaStatus::BSY) {
+                return Ok(status);
+            }
+            core::hint::spin_loop();
+        }
+        warn!("ATA{}: timeout waiting for drive", self.id);
+        Err(FsError::IoError)
+    }
+
+    /// Wait until the drive has data for us (or wants data from us)
+    ///
+    /// PIO polling: the bus lock is held and syscalls run with interrupts
+    /// off, so there is no sleeping on the drive irq, it stays disabled.
+    fn wait_ready(&mut self) -> FsResult {
+        self.delay();
+
+        let status = self.wait_not_busy()?;
+        if status.intersects(AtaStatus::ERR | AtaStatus::DF) {
+            let error = unsafe { self.error.read() };
+            warn!("ATA{}: drive error {:?}, {:#04x}", self.id, status, error);
+            return Err(FsError::IoError);
+        }
+        if !status.contains(AtaStatus::DRQ) {
+            return Err(FsError::IoError);
+        }
+        Ok(())
+    }
+
+    /// Send IDENTIFY DEVICE, `None` if there is no ATA drive
+    pub fn identify(&mut self, drive: u8) -> Option<AtaIdentity> {
+        self.select(drive, 0);
+        unsafe {
+            self.control.write(CTRL_NIEN);
+            self.sector_count.write(0);
+            self.lba_low.write(0);
+            self.lba_mid.write(0);
+            self.lba_high.write(0);
+            self.command.write(AtaCommand::Identify as u8);
+        }
+
+        if self.status().bits() == 0 {
+            return None;
+        }
+        self.wait_not_busy().ok()?;
+
+        // ATAPI and SATA devices put their signature here and abort
+        let (mid, high) = unsafe { (self.lba_mid.read(), self.lba_high.read()) };
+        if mid != 0 || high != 0 {
+            debug!("ATA{}.{}: non-ATA device {:#04x}{:02x}", self.id, drive, high, mid);
+            return None;
+        }
+
+        self.wait_ready().ok()?;
+
+        let mut words = [0u16; 256];
+        for word in words.iter_mut() {
+            *word = unsafe { self.data.read() };
+        }
+
+        let lba48 = words[83] & (1 << 10) != 0;
+        let sectors = if lba48 {
+            (words[100] as u64
+                | (words[101] as u64) << 16
+                | (words[102] as u64) << 32
+                | (words[103] as u64) << 48) as usize
+        } else {
+            (words[60] as u32 | (words[61] as u32) << 16) as usize
+        };
+
+        Some(AtaIdentity {
+            model: ata_string(&words[27..47]),
+            serial: ata_string(&words[10..20]),
+            sectors,
+            lba48,
+        })
+    }
+
+    /// Program the task file for a transfer of `count` sectors at `lba`
+    fn setup(&mut self, drive: u8, lba: usize, count: usize, lba48: bool) {
+        unsafe {
+            self.control.write(CTRL_NIEN);
+        }
+
+        if lba48 {
+            self.select(drive, 0);
+            unsafe {
+                self.sector_count.write((count >> 8) as u8);
+                self.lba_low.write((lba >> 24) as u8);
+                self.lba_mid.write((lba >> 32) as u8);
+                self.lba_high.write((lba >> 40) as u8);
+                self.sector_count.write(count as u8);
+                self.lba_low.write(lba as u8);
+                self.lba_mid.write((lba >> 8) as u8);
+                self.lba_high.write((lba >> 16) as u8);
+            }
+        } else {
+            self.select(drive, (lba >> 24) as u8);
+            unsafe {
+                self.sector_count.write(count as u8);
+                self.lba_low.write(lba as u8);
+                self.lba_mid.write((lba >> 8) as u8);
+                self.lba_high.write((lba >> 16) as u8);
+            }
+        }
+    }
+
+    /// Read `buf.len() / 512` sectors, at most 256 (65536 with lba48)
+    pub fn read(&mut self, drive: u8, lba: usize, buf: &mut [u8], lba48: bool) -> FsResult {
+        let count = buf.len() / BLOCK_SIZE;
+
+        self.setup(drive, lba, count, lba48);
+        let command = if lba48 {
+            AtaCommand::ReadPioExt
+        } else {
+            AtaCommand::ReadPio
+        };
+        unsafe { self.command.write(command as u8) };
+
+        for sector in buf.chunks_exact_mut(BLOCK_SIZE) {
+            self.wait_ready()?;
+            for pair in sector.chunks_exact_mut(2) {
+                let word = unsafe { self.data.read() };
+                pair.copy_from_slice(&word.to_le_bytes());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write `buf.len() / 512` sectors and flush the drive cache
+    pub fn write(&mut self, drive: u8, lba: usize, buf: &[u8], lba48: bool) -> FsResult {
+        let count = buf.len() / BLOCK_SIZE;
+
+        self.setup(drive, lba, count, lba48);
+        let command = if lba48 {
+            AtaCommand::WritePioExt
+        } else {
+            AtaCommand::WritePio
+        };
+        unsafe { self.command.write(command as u8) };
+
+        for sector in buf.chunks_exact(BLOCK_SIZE) {
+            self.wait_ready()?;
+            for pair in sector.chunks_exact(2) {
+                let word = u16::from_le_bytes([pair[0], pair[1]]);
+                unsafe { self.data.write(word) };
+            }
+        }
+
+        let flush = if lba48 {
+            AtaCommand::CacheFlushExt
+        } else {
+            AtaCommand::CacheFlush
+        };
+        unsafe { self.command.write(flush as u8) };
+        self.delay();
+        let status = self.wait_not_busy()?;
+
+        if status.intersects(AtaStatus::ERR | AtaStatus::DF) {
+            warn!("ATA{}: cache flush failed {:?}", self.id, status);
+            return Err(FsError::IoError);
+        }
+
+        Ok(())
+    }
+}
+
+/// Strings in IDENTIFY data are space padded with the bytes of each
+/// word swapped
+fn ata_string(words: &[u16]) -> String {
+    let bytes = words
+        .iter()
+        .flat_map(|w| w.to_be_bytes())
+        .collect::<Vec<u8>>();
+    String::from_utf8_lossy(&bytes).trim().into()
+}
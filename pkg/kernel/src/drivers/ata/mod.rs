@@ -0,0 +1,172 @@
+//! ATA PIO driver for the legacy IDE controller
+//!
+//! Probes the master and slave drive on both channels. Transfers are
+//! PIO with the drive irqs disabled, the status register is polled.
+
+mod bus;
+
+pub use bus::AtaIdentity;
+
+use self::bus::AtaBus;
+use super::block::*;
+use crate::fs::{FsError, FsResult};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+/// Most sectors moved by one lba28 command
+const MAX_SECTORS_LBA28: usize = 256;
+/// Keep lba48 commands reasonably short as well
+const MAX_SECTORS_LBA48: usize = 256;
+
+/// (io base, control base) of the primary and secondary channel
+const CHANNELS: [(u16, u16); 2] = [(0x1F0, 0x3F6), (0x170, 0x376)];
+
+static BUSES: [Mutex<AtaBus>; 2] = [
+    Mutex::new(AtaBus::new(0, CHANNELS[0].0, CHANNELS[0].1)),
+    Mutex::new(AtaBus::new(1, CHANNELS[1].0, CHANNELS[1].1)),
+];
+
+static DRIVES: spin::Once<Vec<Arc<AtaDrive>>> = spin::Once::new();
+
+/// A drive on one of the IDE channels
+pub struct AtaDrive {
+    bus: u8,
+    drive: u8,
+    identity: AtaIdentity,
+}
+
+impl AtaDrive {
+    /// 0: primary master, 1: primary slave, 2: secondary master...
+    pub fn index(&self) -> usize {
+        (self.bus * 2 + self.drive) as usize
+    }
+
+    pub fn identity(&self) -> &AtaIdentity {
+        &self.identity
+    }
+
+    #[inline]
+    fn max_sectors(&self) -> usize {
+        if self.identity.lba48 {
+            MAX_SECTORS_LBA48
+        } else {
+            MAX_SECTORS_LBA28
+        }
+    }
+
+    fn check(&self, id: usize, count: usize) -> FsResult {
+        if id + count <= self.identity.sectors {
+            Ok(())
+        } else {
+            Err(FsError::IoError)
+        }
+    }
+
+    /// Use lba48 commands only when the address needs them
+    #[inline]
+    fn need_lba48(&self, id: usize, count: usize) -> bool {
+        self.identity.lba48 && (id + count >= 1 << 28)
+    }
+}
+
+impl BlockDevice for AtaDrive {
+    fn block_count(&self) -> usize {
+        self.identity.sectors
+    }
+
+    fn read_block(&self, id: usize, buf: &mut Block) -> FsResult {
+        self.read_blocks(id, buf)
+    }
+
+    fn write_block(&self, id: usize, buf: &Block) -> FsResult {
+        self.write_blocks(id, buf)
+    }
+
+    fn read_blocks(&self, id: usize, buf: &mut [u8]) -> FsResult {
+        self.check(id, buf.len() / BLOCK_SIZE)?;
+
+        let mut lba = id;
+        for chunk in buf.chunks_mut(self.max_sectors() * BLOCK_SIZE) {
+            let count = chunk.len() / BLOCK_SIZE;
+            let lba48 = self.need_lba48(lba, count);
+            BUSES[self.bus as usize]
+                .lock()
+                .read(self.drive, lba, chunk, lba48)?;
+            lba += count;
+        }
+
+        Ok(())
+    }
+
+    fn write_blocks(&self, id: usize, buf: &[u8]) -> FsResult {
+        self.check(id, buf.len() / BLOCK_SIZE)?;
+
+        let mut lba = id;
+        for chunk in buf.chunks(self.max_sectors() * BLOCK_SIZE) {
+            let count = chunk.len() / BLOCK_SIZE;
+            let lba48 = self.need_lba48(lba, count);
+            BUSES[self.bus as usize]
+                .lock()
+                .write(self.drive, lba, chunk, lba48)?;
+            lba += count;
+        }
+
+        Ok(())
+    }
+}
+
+impl core::fmt::Debug for AtaDrive {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("AtaDrive")
+            .field("bus", &self.bus)
+            .field("drive", &self.drive)
+            .field("model", &self.identity.model)
+            .field("sectors", &self.identity.sectors)
+            .field("lba48", &self.identity.lba48)
+            .finish()
+    }
+}
+
+/// Probe both IDE channels for ATA drives
+pub fn init() {
+    DRIVES.call_once(|| {
+        let mut drives = Vec::new();
+
+        for (bus_id, bus) in BUSES.iter().enumerate() {
+            let mut bus = bus.lock();
+            if bus.is_floating() {
+                continue;
+            }
+            bus.reset();
+
+            for drive in 0..2 {
+                if let Some(identity) = bus.identify(drive) {
+                    info!(
+                        "ATA{}.{}: {} ({} sectors, {} MiB)",
+                        bus_id,
+                        drive,
+                        identity.model,
+                        identity.sectors,
+                        identity.sectors * BLOCK_SIZE / 1024 / 1024
+                    );
+                    drives.push(Arc::new(AtaDrive {
+                        bus: bus_id as u8,
+                        drive,
+                        identity,
+                    }));
+                }
+            }
+        }
+
+        drives
+    });
+
+    info!("ATA Initialized, {} drive(s).", drives().len());
+}
+
+/// Drives found by [`init`], in the order of [`AtaDrive::index`]
+pub fn drives() -> &'static [Arc<AtaDrive>] {
+    DRIVES.get().map(|d| d.as_slice()).unwrap_or(&[])
+}
@@ -0,0 +1,134 @@
+//! Block devices and MBR partitions
+//!
+//! Filesystem drivers only see [`BlockDevice`], so the same driver runs
+//! on a disk, a partition of a disk or a ramdisk.
+
+use crate::fs::{FsError, FsResult};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+pub const BLOCK_SIZE: usize = 512;
+
+pub type Block = [u8; BLOCK_SIZE];
+
+/// A device addressed in fixed-size blocks of [`BLOCK_SIZE`] bytes
+pub trait BlockDevice: Send + Sync {
+    /// Number of blocks on the device
+    fn block_count(&self) -> usize;
+
+    fn read_block(&self, id: usize, buf: &mut Block) -> FsResult;
+
+    fn write_block(&self, id: usize, buf: &Block) -> FsResult;
+
+    /// Read consecutive blocks starting at `id`, `buf` must be block aligned
+    fn read_blocks(&self, id: usize, buf: &mut [u8]) -> FsResult {
+        for (i, chunk) in buf.chunks_exact_mut(BLOCK_SIZE).enumerate() {
+            self.read_block(id + i, chunk.try_into().unwrap())?;
+        }
+        Ok(())
+    }
+
+    /// Write consecutive blocks starting at `id`, `buf` must be block aligned
+    fn write_blocks(&self, id: usize, buf: &[u8]) -> FsResult {
+        for (i, chunk) in buf.chunks_exact(BLOCK_SIZE).enumerate() {
+            self.write_block(id + i, chunk.try_into().unwrap())?;
+        }
+        Ok(())
+    }
+}
+
+/// A contiguous range of blocks on another device
+pub struct Partition {
+    device: Arc<dyn BlockDevice>,
+    start: usize,
+    count: usize,
+    kind: u8,
+}
+
+impl Partition {
+    pub fn new(device: Arc<dyn BlockDevice>, start: usize, count: usize, kind: u8) -> Self {
+        Self {
+            device,
+            start,
+            count,
+            kind,
+        }
+    }
+
+    /// The partition type byte from the partition table
+    pub fn kind(&self) -> u8 {
+        self.kind
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// If the partition type says this is a FAT volume (or an ESP)
+    pub fn is_fat(&self) -> bool {
+        matches!(self.kind, 0x01 | 0x04 | 0x06 | 0x0B | 0x0C | 0x0E | 0xEF)
+    }
+
+    #[inline]
+    fn check(&self, id: usize) -> FsResult {
+        if id < self.count {
+            Ok(())
+        } else {
+            Err(FsError::IoError)
+        }
+    }
+}
+
+impl BlockDevice for Partition {
+    fn block_count(&self) -> usize {
+        self.count
+    }
+
+    fn read_block(&self, id: usize, buf: &mut Block) -> FsResult {
+        self.check(id)?;
+        self.device.read_block(self.start + id, buf)
+    }
+
+    fn write_block(&self, id: usize, buf: &Block) -> FsResult {
+        self.check(id)?;
+        self.device.write_block(self.start + id, buf)
+    }
+}
+
+impl core::fmt::Debug for Partition {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Partition")
+            .field("kind", &format_args!("{:#04x}", self.kind))
+            .field("start", &self.start)
+            .field("count", &self.count)
+            .finish()
+    }
+}
+
+/// Read the MBR partition table of a device
+///
+/// Returns an empty list if block 0 has no MBR signature.
+pub fn mbr_partitions(device: &Arc<dyn BlockDevice>) -> FsResult<Vec<Partition>> {
+    let mut mbr = [0u8; BLOCK_SIZE];
+    device.read_block(0, &mut mbr)?;
+
+    if mbr[510] != 0x55 || mbr[511] != 0xAA {
+        return Ok(Vec::new());
+    }
+
+    let mut partitions = Vec::new();
+    for i in 0..4 {
+        let entry = &mbr[446 + i * 16..446 + (i + 1) * 16];
+        let kind = entry[4];
+        let start = u32::from_le_bytes(entry[8..12].try_into().unwrap()) as usize;
+        let count = u32::from_le_bytes(entry[12..16].try_into().unwrap()) as usize;
+
+        if kind == 0 || count == 0 || start + count > device.block_count() {
+            continue;
+        }
+
+        partitions.push(Partition::new(device.clone(), start, count, kind));
+    }
+
+    Ok(partitions)
+}
@@ -0,0 +1,21 @@
+use super::*;
+
+/// The kernel log; writes are logged as well
+pub struct Kmsg;
+
+impl CharDevice for Kmsg {
+    fn read(&self, offset: u64, buf: &mut [u8]) -> FsResult<usize> {
+        Ok(crate::logger::read_kmsg(offset, buf).0)
+    }
+
+    /// A reader that fell behind continues after what was overwritten
+    fn read_from(&self, offset: u64, buf: &mut [u8]) -> FsResult<(usize, u64)> {
+        Ok(crate::logger::read_kmsg(offset, buf))
+    }
+
+    fn write(&self, buf: &[u8]) -> FsResult<usize> {
+        let msg = String::from_utf8_lossy(buf);
+        info!("{}", msg.trim_end());
+        Ok(buf.len())
+    }
+}
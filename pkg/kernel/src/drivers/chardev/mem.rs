@@ -0,0 +1,89 @@
+use super::*;
+use spin::Mutex;
+
+/// Discards writes, reads nothing
+pub struct Null;
+
+impl CharDevice for Null {
+    fn read(&self, _offset: u64, _buf: &mut [u8]) -> FsResult<usize> {
+        Ok(0)
+    }
+
+    fn write(&self, buf: &[u8]) -> FsResult<usize> {
+        Ok(buf.len())
+    }
+}
+
+/// Discards writes, reads zeros
+pub struct Zero;
+
+impl CharDevice for Zero {
+    fn read(&self, _offset: u64, buf: &mut [u8]) -> FsResult<usize> {
+        buf.fill(0);
+        Ok(buf.len())
+    }
+
+    fn write(&self, buf: &[u8]) -> FsResult<usize> {
+        Ok(buf.len())
+    }
+}
+
+/// xorshift64* seeded from RDRAND, or from the TSC if there is no RDRAND
+///
+/// Not cryptographically secure. Writes are mixed into the state.
+pub struct Random {
+    state: Mutex<u64>,
+}
+
+impl Random {
+    pub fn new() -> Self {
+        let seed = x86_64::instructions::random::RdRand::new()
+            .and_then(|rd| rd.get_u64())
+            .unwrap_or_else(|| unsafe { core::arch::x86_64::_rdtsc() });
+
+        Self {
+            // xorshift gets stuck on 0
+            state: Mutex::new(seed | 1),
+        }
+    }
+
+    fn next(state: &mut u64) -> u64 {
+        let mut x = *state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        *state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CharDevice for Random {
+    fn read(&self, _offset: u64, buf: &mut [u8]) -> FsResult<usize> {
+        let mut state = self.state.lock();
+        for chunk in buf.chunks_mut(8) {
+            let bytes = Self::next(&mut state).to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        Ok(buf.len())
+    }
+
+    fn write(&self, buf: &[u8]) -> FsResult<usize> {
+        let mut state = self.state.lock();
+        for chunk in buf.chunks(8) {
+            let mut bytes = [0u8; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            *state ^= u64::from_le_bytes(bytes);
+            if *state == 0 {
+                *state = 1;
+            }
+            Self::next(&mut state);
+        }
+        Ok(buf.len())
+    }
+}
@@ -0,0 +1,93 @@
+//! Character devices
+//!
+//! Drivers implement [`CharDevice`] and [`register`] themselves under a
+//! name; devfs shows every registered device as `/dev/<name>`.
+
+mod kmsg;
+mod mem;
+mod tty;
+
+pub use kmsg::Kmsg;
+pub use mem::{Null, Random, Zero};
+pub use tty::SerialTty;
+
+use crate::fs::{FsError, FsResult, PollEvents};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::RwLock;
+
+/// A device read and written as a stream of bytes
+pub trait CharDevice: Send + Sync {
+    /// Read into `buf`, returns 0 if there is nothing to read right now
+    ///
+    /// `offset` is the position of the reader in the stream; devices
+    /// without a position (most of them) ignore it.
+    fn read(&self, _offset: u64, _buf: &mut [u8]) -> FsResult<usize> {
+        Err(FsError::NotSupported)
+    }
+
+    /// `read`, also returning the position of the next read
+    fn read_from(&self, offset: u64, buf: &mut [u8]) -> FsResult<(usize, u64)> {
+        let count = self.read(offset, buf)?;
+        Ok((count, offset + count as u64))
+    }
+
+    fn write(&self, _buf: &[u8]) -> FsResult<usize> {
+        Err(FsError::NotSupported)
+    }
+
+    /// Whether `read` has data and `write` has room, for `Poll`
+    fn poll(&self) -> PollEvents {
+        PollEvents::IN | PollEvents::OUT
+    }
+}
+
+lazy_static! {
+    static ref DEVICES: RwLock<BTreeMap<String, Arc<dyn CharDevice>>> =
+        RwLock::new(BTreeMap::new());
+}
+
+/// Make `device` available as `/dev/<name>`
+pub fn register(name: &str, device: Arc<dyn CharDevice>) -> FsResult {
+    let mut devices = DEVICES.write();
+    if devices.contains_key(name) {
+        return Err(FsError::AlreadyExists);
+    }
+    devices.insert(name.into(), device);
+    Ok(())
+}
+
+pub fn unregister(name: &str) -> FsResult {
+    DEVICES.write().remove(name).map(|_| ()).ok_or(FsError::NotFound)
+}
+
+pub fn get(name: &str) -> Option<Arc<dyn CharDevice>> {
+    DEVICES.read().get(name).cloned()
+}
+
+/// Names of all registered devices, sorted
+pub fn names() -> Vec<String> {
+    DEVICES.read().keys().cloned().collect()
+}
+
+/// Register the built-in devices
+pub fn init() {
+    let devices: [(&str, Arc<dyn CharDevice>); 4] = [
+        ("null", Arc::new(Null)),
+        ("zero", Arc::new(Zero)),
+        ("random", Arc::new(Random::new())),
+        ("kmsg", Arc::new(Kmsg)),
+    ];
+    for (name, device) in devices {
+        register(name, device).unwrap();
+    }
+
+    register("ttyS0", Arc::new(SerialTty::Console)).unwrap();
+    if let Some(tty) = SerialTty::probe(tty::COM2) {
+        register("ttyS1", Arc::new(tty)).unwrap();
+    }
+
+    info!("Character Devices Initialized: {}", names().join(", "));
+}
@@ -0,0 +1,73 @@
+use super::*;
+use crate::drivers::input::{has_key, try_pop_key};
+use crate::drivers::serial::get_serial_for_sure;
+use crate::drivers::uart16550::SerialPort;
+use spin::Mutex;
+
+pub const COM2: u16 = 0x2F8;
+
+pub enum SerialTty {
+    /// COM1, shared with the kernel console; input arrives through the
+    /// serial irq into the input buffer
+    Console,
+    /// Any other port, polled
+    Port(Mutex<SerialPort>),
+}
+
+impl SerialTty {
+    /// Initialize the UART at `port`, `None` if it fails the loopback test
+    pub fn probe(port: u16) -> Option<Self> {
+        let serial = SerialPort::new(port);
+        if serial.init() != 0 {
+            return None;
+        }
+        Some(Self::Port(Mutex::new(serial)))
+    }
+}
+
+impl CharDevice for SerialTty {
+    fn read(&self, _offset: u64, buf: &mut [u8]) -> FsResult<usize> {
+        let mut count = 0;
+        match self {
+            SerialTty::Console => {
+                while count < buf.len() {
+                    let Some(key) = try_pop_key() else { break };
+                    buf[count] = key;
+                    count += 1;
+                }
+            }
+            SerialTty::Port(port) => {
+                let mut port = port.lock();
+                while count < buf.len() {
+                    let Some(byte) = port.receive() else { break };
+                    buf[count] = byte;
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    fn write(&self, buf: &[u8]) -> FsResult<usize> {
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            let send = |port: &mut SerialPort| buf.iter().for_each(|&b| port.send(b));
+            match self {
+                SerialTty::Console => send(&mut get_serial_for_sure()),
+                SerialTty::Port(port) => send(&mut port.lock()),
+            }
+        });
+        Ok(buf.len())
+    }
+
+    fn poll(&self) -> PollEvents {
+        let readable = match self {
+            SerialTty::Console => has_key(),
+            SerialTty::Port(port) => port.try_lock().map_or(false, |p| p.has_data()),
+        };
+        if readable {
+            PollEvents::IN | PollEvents::OUT
+        } else {
+            PollEvents::OUT
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! HPET main counter as a clock source
+//!
+//! The comparators are not used, the counter just runs.
+
+use crate::memory::physical_to_virtual;
+
+const REG_CAPABILITIES: u64 = 0x00;
+const REG_CONFIG: u64 = 0x10;
+const REG_COUNTER: u64 = 0xF0;
+
+const CONFIG_ENABLE: u64 = 1 << 0;
+
+static HPET: spin::Once<Hpet> = spin::Once::new();
+
+pub struct Hpet {
+    base: u64,
+    /// Length of a counter tick in femtoseconds
+    period_fs: u64,
+}
+
+impl Hpet {
+    unsafe fn read(&self, reg: u64) -> u64 {
+        ((self.base + reg) as *const u64).read_volatile()
+    }
+
+    unsafe fn write(&self, reg: u64, value: u64) {
+        ((self.base + reg) as *mut u64).write_volatile(value)
+    }
+
+    #[inline]
+    pub fn counter(&self) -> u64 {
+        unsafe { self.read(REG_COUNTER) }
+    }
+
+    /// Counter ticks per second
+    pub fn frequency(&self) -> u64 {
+        1_000_000_000_000_000 / self.period_fs
+    }
+}
+
+/// Start the main counter of the HPET in the ACPI tables
+pub fn init() -> Option<&'static Hpet> {
+    let table = crate::acpi::hpet()?;
+
+    let hpet = Hpet {
+        base: physical_to_virtual(table.addr),
+        period_fs: 0,
+    };
+    let period_fs = unsafe { hpet.read(REG_CAPABILITIES) } >> 32;
+    // the spec caps the period at 100 ns
+    if period_fs == 0 || period_fs > 100_000_000 {
+        warn!("HPET reports a bad period: {} fs", period_fs);
+        return None;
+    }
+
+    let hpet = HPET.call_once(|| Hpet { period_fs, ..hpet });
+    unsafe {
+        let config = hpet.read(REG_CONFIG);
+        hpet.write(REG_CONFIG, config | CONFIG_ENABLE);
+    }
+    info!("HPET             : {} kHz", hpet.frequency() / 1000);
+    Some(hpet)
+}
+
+pub fn get() -> Option<&'static Hpet> {
+    HPET.get()
+}
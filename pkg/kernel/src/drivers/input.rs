@@ -38,6 +38,11 @@ pub fn try_pop_key() -> Option<Key> {
     INPUT_BUF.pop()
 }
 
+#[inline]
+pub fn has_key() -> bool {
+    !INPUT_BUF.is_empty()
+}
+
 //简单版本，只能处理英文和数字输入
 #[inline]
 pub fn get_line() -> String {
@@ -0,0 +1,139 @@
+//! i8042 PS/2 controller, only the first port (the keyboard) is used
+
+use x86_64::instructions::port::Port;
+
+const DATA: u16 = 0x60;
+/// Status on read, command on write
+const STATUS: u16 = 0x64;
+
+/// Output buffer full, a byte can be read from `DATA`
+const STATUS_OUTPUT_FULL: u8 = 1 << 0;
+/// Input buffer full, the controller hasn't taken the last byte yet
+const STATUS_INPUT_FULL: u8 = 1 << 1;
+
+const CMD_READ_CONFIG: u8 = 0x20;
+const CMD_WRITE_CONFIG: u8 = 0x60;
+const CMD_DISABLE_PORT2: u8 = 0xA7;
+const CMD_SELF_TEST: u8 = 0xAA;
+const CMD_DISABLE_PORT1: u8 = 0xAD;
+const CMD_ENABLE_PORT1: u8 = 0xAE;
+
+const SELF_TEST_OK: u8 = 0x55;
+
+pub const CONFIG_IRQ1: u8 = 1 << 0;
+pub const CONFIG_IRQ2: u8 = 1 << 1;
+/// The controller turns scancode set 2 into set 1
+pub const CONFIG_TRANSLATE: u8 = 1 << 6;
+
+/// Keyboard replies
+pub const ACK: u8 = 0xFA;
+pub const RESEND: u8 = 0xFE;
+
+/// Keyboard commands
+pub const KBD_SET_LEDS: u8 = 0xED;
+pub const KBD_SCANCODE_SET: u8 = 0xF0;
+pub const KBD_ENABLE_SCANNING: u8 = 0xF4;
+
+/// Status polls before giving up, a missing controller reads 0xFF
+const TIMEOUT: usize = 100_000;
+
+#[inline]
+fn status() -> u8 {
+    unsafe { Port::<u8>::new(STATUS).read() }
+}
+
+fn wait_input_empty() -> bool {
+    (0..TIMEOUT).any(|_| status() & STATUS_INPUT_FULL == 0)
+}
+
+fn wait_output_full() -> bool {
+    (0..TIMEOUT).any(|_| status() & STATUS_OUTPUT_FULL != 0)
+}
+
+/// A byte from the keyboard or the controller, if one is waiting
+#[inline]
+pub fn try_read() -> Option<u8> {
+    if status() & STATUS_OUTPUT_FULL != 0 {
+        Some(unsafe { Port::<u8>::new(DATA).read() })
+    } else {
+        None
+    }
+}
+
+/// Wait for a byte, `None` after the timeout
+pub fn read() -> Option<u8> {
+    wait_output_full().then(|| unsafe { Port::<u8>::new(DATA).read() })
+}
+
+/// Send a byte to the keyboard
+pub fn write(byte: u8) -> bool {
+    if !wait_input_empty() {
+        return false;
+    }
+    unsafe { Port::<u8>::new(DATA).write(byte) };
+    true
+}
+
+fn command(cmd: u8) -> bool {
+    if !wait_input_empty() {
+        return false;
+    }
+    unsafe { Port::<u8>::new(STATUS).write(cmd) };
+    true
+}
+
+/// Send a byte to the keyboard and wait for its ACK, with interrupts off
+pub fn write_acked(byte: u8) -> bool {
+    for _ in 0..3 {
+        if !write(byte) {
+            return false;
+        }
+        match read() {
+            Some(ACK) => return true,
+            Some(RESEND) => continue,
+            _ => return false,
+        }
+    }
+    false
+}
+
+/// Throw away whatever is waiting in the output buffer
+pub fn flush() {
+    for _ in 0..16 {
+        if try_read().is_none() {
+            break;
+        }
+    }
+}
+
+/// Reset the controller with both ports and their irqs off, returns the
+/// configuration byte the firmware left, `None` if there is no i8042
+pub fn init() -> Option<u8> {
+    if !command(CMD_DISABLE_PORT1) || !command(CMD_DISABLE_PORT2) {
+        return None;
+    }
+    flush();
+
+    if !command(CMD_READ_CONFIG) {
+        return None;
+    }
+    let config = read()?;
+    set_config(config & !(CONFIG_IRQ1 | CONFIG_IRQ2));
+
+    if !command(CMD_SELF_TEST) || read()? != SELF_TEST_OK {
+        return None;
+    }
+    // the self test may have reset the configuration
+    set_config(config & !(CONFIG_IRQ1 | CONFIG_IRQ2));
+    Some(config)
+}
+
+pub fn set_config(config: u8) {
+    if command(CMD_WRITE_CONFIG) {
+        write(config);
+    }
+}
+
+pub fn enable_port1() -> bool {
+    command(CMD_ENABLE_PORT1)
+}
@@ -0,0 +1,99 @@
+//! Keymaps, indexed by set 1 make code
+//!
+//! Set 2 codes are turned into set 1 first with the table the i8042 uses
+//! for translation, so the rest of the driver only knows set 1.
+
+/// Set 2 make code to set 1 make code, as the i8042 translates them
+#[rustfmt::skip]
+const SET2_TO_SET1: [u8; 0x85] = [
+    0xff, 0x43, 0x41, 0x3f, 0x3d, 0x3b, 0x3c, 0x58,
+    0x64, 0x44, 0x42, 0x40, 0x3e, 0x0f, 0x29, 0x59,
+    0x65, 0x38, 0x2a, 0x70, 0x1d, 0x10, 0x02, 0x5a,
+    0x66, 0x71, 0x2c, 0x1f, 0x1e, 0x11, 0x03, 0x5b,
+    0x67, 0x2e, 0x2d, 0x20, 0x12, 0x05, 0x04, 0x5c,
+    0x68, 0x39, 0x2f, 0x21, 0x14, 0x13, 0x06, 0x5d,
+    0x69, 0x31, 0x30, 0x23, 0x22, 0x15, 0x07, 0x5e,
+    0x6a, 0x72, 0x32, 0x24, 0x16, 0x08, 0x09, 0x5f,
+    0x6b, 0x33, 0x25, 0x17, 0x18, 0x0b, 0x0a, 0x60,
+    0x6c, 0x34, 0x35, 0x26, 0x27, 0x19, 0x0c, 0x61,
+    0x6d, 0x73, 0x28, 0x74, 0x1a, 0x0d, 0x62, 0x6e,
+    0x3a, 0x36, 0x1c, 0x1b, 0x75, 0x2b, 0x63, 0x76,
+    0x55, 0x56, 0x77, 0x78, 0x79, 0x7a, 0x0e, 0x7b,
+    0x7c, 0x4f, 0x7d, 0x4b, 0x47, 0x7e, 0x7f, 0x6f,
+    0x52, 0x53, 0x50, 0x4c, 0x4d, 0x48, 0x01, 0x45,
+    0x57, 0x4e, 0x51, 0x4a, 0x37, 0x49, 0x46, 0x54,
+    0x80, 0x81, 0x82, 0x41, 0x54,
+];
+
+/// Set 1 make code of set 2 make code `code`
+pub fn set2_to_set1(code: u8) -> Option<u8> {
+    SET2_TO_SET1.get(code as usize).copied().filter(|&c| c < 0x80)
+}
+
+/// Keys of the main block, set 1 codes 0x00 to 0x39
+const MAIN_KEYS: usize = 0x3A;
+
+/// The extra key next to left shift on ISO keyboards
+pub const KEY_ISO: u8 = 0x56;
+
+pub struct Keymap {
+    pub name: &'static str,
+    /// Unshifted and shifted byte of each key, 0 if it has none
+    keys: [[u8; 2]; MAIN_KEYS],
+    iso: [u8; 2],
+}
+
+impl Keymap {
+    /// Byte of key `code`, 0 for keys without one
+    pub fn get(&self, code: u8, shift: bool) -> u8 {
+        let pair = match code {
+            KEY_ISO => self.iso,
+            c if (c as usize) < MAIN_KEYS => self.keys[c as usize],
+            _ => return 0,
+        };
+        pair[shift as usize]
+    }
+}
+
+pub static US: Keymap = Keymap {
+    name: "us",
+    keys: [
+        [0, 0], [0x1b, 0x1b],
+        [b'1', b'!'], [b'2', b'@'], [b'3', b'#'], [b'4', b'$'], [b'5', b'%'],
+        [b'6', b'^'], [b'7', b'&'], [b'8', b'*'], [b'9', b'('], [b'0', b')'],
+        [b'-', b'_'], [b'=', b'+'], [0x7f, 0x7f], [b'\t', b'\t'],
+        [b'q', b'Q'], [b'w', b'W'], [b'e', b'E'], [b'r', b'R'], [b't', b'T'],
+        [b'y', b'Y'], [b'u', b'U'], [b'i', b'I'], [b'o', b'O'], [b'p', b'P'],
+        [b'[', b'{'], [b']', b'}'], [b'\r', b'\r'], [0, 0],
+        [b'a', b'A'], [b's', b'S'], [b'd', b'D'], [b'f', b'F'], [b'g', b'G'],
+        [b'h', b'H'], [b'j', b'J'], [b'k', b'K'], [b'l', b'L'],
+        [b';', b':'], [b'\'', b'"'], [b'`', b'~'], [0, 0], [b'\\', b'|'],
+        [b'z', b'Z'], [b'x', b'X'], [b'c', b'C'], [b'v', b'V'], [b'b', b'B'],
+        [b'n', b'N'], [b'm', b'M'], [b',', b'<'], [b'.', b'>'], [b'/', b'?'],
+        [0, 0], [b'*', b'*'], [0, 0], [b' ', b' '],
+    ],
+    iso: [b'\\', b'|'],
+};
+
+pub static DVORAK: Keymap = Keymap {
+    name: "dvorak",
+    keys: [
+        [0, 0], [0x1b, 0x1b],
+        [b'1', b'!'], [b'2', b'@'], [b'3', b'#'], [b'4', b'$'], [b'5', b'%'],
+        [b'6', b'^'], [b'7', b'&'], [b'8', b'*'], [b'9', b'('], [b'0', b')'],
+        [b'[', b'{'], [b']', b'}'], [0x7f, 0x7f], [b'\t', b'\t'],
+        [b'\'', b'"'], [b',', b'<'], [b'.', b'>'], [b'p', b'P'], [b'y', b'Y'],
+        [b'f', b'F'], [b'g', b'G'], [b'c', b'C'], [b'r', b'R'], [b'l', b'L'],
+        [b'/', b'?'], [b'=', b'+'], [b'\r', b'\r'], [0, 0],
+        [b'a', b'A'], [b'o', b'O'], [b'e', b'E'], [b'u', b'U'], [b'i', b'I'],
+        [b'd', b'D'], [b'h', b'H'], [b't', b'T'], [b'n', b'N'],
+        [b's', b'S'], [b'-', b'_'], [b'`', b'~'], [0, 0], [b'\\', b'|'],
+        [b';', b':'], [b'q', b'Q'], [b'j', b'J'], [b'k', b'K'], [b'x', b'X'],
+        [b'b', b'B'], [b'm', b'M'], [b'w', b'W'], [b'v', b'V'], [b'z', b'Z'],
+        [0, 0], [b'*', b'*'], [0, 0], [b' ', b' '],
+    ],
+    iso: [b'\\', b'|'],
+};
+
+/// All keymaps, the first is the default
+pub static KEYMAPS: [&Keymap; 2] = [&US, &DVORAK];
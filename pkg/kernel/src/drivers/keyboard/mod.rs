@@ -0,0 +1,348 @@
+//! PS/2 keyboard on the i8042
+//!
+//! Scancodes come in set 1 when the controller translates, in set 2
+//! otherwise; both end up as set 1 make codes. Keys are turned into the
+//! bytes a serial terminal would send (`\r` for enter, DEL for backspace,
+//! ANSI sequences for the arrows) and pushed into the same input buffer
+//! as the serial port, so readers can't tell the two apart.
+//!
+//! `/dev/keymap` reads as the name of the keymap in use, writing the name
+//! of another one (`us`, `dvorak`) switches to it.
+
+mod i8042;
+pub mod keymap;
+
+use self::keymap::{Keymap, KEYMAPS};
+use super::input::push_key;
+use crate::drivers::chardev::{self, CharDevice};
+use crate::fs::{FsError, FsResult};
+use alloc::format;
+use alloc::sync::Arc;
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+/// Prefix of the extended keys
+const EXTENDED: u8 = 0xE0;
+/// Prefix of pause, the only key with no break code
+const PAUSE: u8 = 0xE1;
+/// Set 2 prefix of a break code
+const SET2_BREAK: u8 = 0xF0;
+/// Set 1 break codes are the make code with this bit set
+const SET1_BREAK: u8 = 0x80;
+
+const KEY_LCTRL: u8 = 0x1D;
+const KEY_LSHIFT: u8 = 0x2A;
+const KEY_RSHIFT: u8 = 0x36;
+const KEY_LALT: u8 = 0x38;
+const KEY_CAPS_LOCK: u8 = 0x3A;
+const KEY_NUM_LOCK: u8 = 0x45;
+const KEY_SCROLL_LOCK: u8 = 0x46;
+/// Keypad 7 to keypad ., set 1 codes 0x47 to 0x53
+const KEY_KEYPAD_FIRST: u8 = 0x47;
+const KEY_KEYPAD_LAST: u8 = 0x53;
+const KEYPAD_NUM: &[u8; 13] = b"789-456+1230.";
+const KEY_KEYPAD_ENTER: u8 = 0x1C;
+const KEY_KEYPAD_SLASH: u8 = 0x35;
+
+/// LED bits of `KBD_SET_LEDS`
+const LED_SCROLL: u8 = 1 << 0;
+const LED_NUM: u8 = 1 << 1;
+const LED_CAPS: u8 = 1 << 2;
+
+static KEYBOARD: Mutex<Option<Keyboard>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScancodeSet {
+    Set1,
+    Set2,
+}
+
+struct Keyboard {
+    set: ScancodeSet,
+    keymap: &'static Keymap,
+    /// Seen `EXTENDED`, the next code is an extended key
+    extended: bool,
+    /// Seen `SET2_BREAK`, the next code is a release
+    release: bool,
+    /// Bytes of the pause sequence still to skip
+    skip: u8,
+    lshift: bool,
+    rshift: bool,
+    ctrl: bool,
+    alt: bool,
+    caps_lock: bool,
+    num_lock: bool,
+    scroll_lock: bool,
+    /// LED byte to send once the keyboard has acknowledged `KBD_SET_LEDS`
+    pending_leds: Option<u8>,
+}
+
+impl Keyboard {
+    fn new(set: ScancodeSet) -> Self {
+        Self {
+            set,
+            keymap: KEYMAPS[0],
+            extended: false,
+            release: false,
+            skip: 0,
+            lshift: false,
+            rshift: false,
+            ctrl: false,
+            alt: false,
+            caps_lock: false,
+            num_lock: false,
+            scroll_lock: false,
+            pending_leds: None,
+        }
+    }
+
+    /// Feed one byte from the keyboard
+    fn receive(&mut self, byte: u8) {
+        match byte {
+            i8042::ACK => {
+                if let Some(leds) = self.pending_leds.take() {
+                    i8042::write(leds);
+                }
+                return;
+            }
+            i8042::RESEND => return,
+            _ => {}
+        }
+
+        if self.skip > 0 {
+            self.skip -= 1;
+            return;
+        }
+
+        match (self.set, byte) {
+            (_, EXTENDED) => self.extended = true,
+            (ScancodeSet::Set1, PAUSE) => self.skip = 5,
+            (ScancodeSet::Set2, PAUSE) => self.skip = 7,
+            (ScancodeSet::Set2, SET2_BREAK) => self.release = true,
+            (ScancodeSet::Set1, code) => {
+                self.key(code & !SET1_BREAK, code & SET1_BREAK != 0);
+            }
+            (ScancodeSet::Set2, code) => {
+                let release = core::mem::take(&mut self.release);
+                match keymap::set2_to_set1(code) {
+                    Some(code) => self.key(code, release),
+                    None => self.extended = false,
+                }
+            }
+        }
+    }
+
+    /// Key `code` (set 1) was pressed or released
+    fn key(&mut self, code: u8, release: bool) {
+        let extended = core::mem::take(&mut self.extended);
+        let pressed = !release;
+
+        match (extended, code) {
+            // fake shifts around print screen and the keypad keys
+            (true, KEY_LSHIFT | KEY_RSHIFT) => {}
+            (false, KEY_LSHIFT) => self.lshift = pressed,
+            (false, KEY_RSHIFT) => self.rshift = pressed,
+            (_, KEY_LCTRL) => self.ctrl = pressed,
+            (_, KEY_LALT) => self.alt = pressed,
+            (false, KEY_CAPS_LOCK) if pressed => {
+                self.caps_lock = !self.caps_lock;
+                self.update_leds();
+            }
+            (false, KEY_NUM_LOCK) if pressed => {
+                self.num_lock = !self.num_lock;
+                self.update_leds();
+            }
+            (false, KEY_SCROLL_LOCK) if pressed => {
+                self.scroll_lock = !self.scroll_lock;
+                self.update_leds();
+            }
+            _ if release => {}
+            (true, code) => self.extended_key(code),
+            (false, KEY_KEYPAD_FIRST..=KEY_KEYPAD_LAST) => self.keypad_key(code),
+            (false, code) => self.main_key(code),
+        }
+    }
+
+    fn main_key(&mut self, code: u8) {
+        let shift = self.lshift || self.rshift;
+        let mut byte = self.keymap.get(code, shift);
+        if byte == 0 {
+            return;
+        }
+
+        // caps lock only shifts letters
+        if self.caps_lock && byte.is_ascii_alphabetic() {
+            byte = self.keymap.get(code, !shift);
+        }
+        if self.ctrl {
+            match byte {
+                b'@'..=b'_' | b'a'..=b'z' => byte &= 0x1F,
+                _ => {}
+            }
+        }
+        // alt sends ESC first, like a terminal with meta
+        if self.alt {
+            push_key(0x1B);
+        }
+        push_key(byte);
+    }
+
+    fn keypad_key(&mut self, code: u8) {
+        let num = KEYPAD_NUM[(code - KEY_KEYPAD_FIRST) as usize];
+        // the keypad has digits when num lock and shift disagree
+        if self.num_lock != (self.lshift || self.rshift) || matches!(num, b'-' | b'+') {
+            push_key(num);
+            return;
+        }
+        if let Some(seq) = navigation(code) {
+            push_str(seq);
+        }
+    }
+
+    fn extended_key(&mut self, code: u8) {
+        match code {
+            KEY_KEYPAD_ENTER => push_key(b'\r'),
+            KEY_KEYPAD_SLASH => push_key(b'/'),
+            code => {
+                if let Some(seq) = navigation(code) {
+                    push_str(seq);
+                }
+            }
+        }
+    }
+
+    /// Tell the keyboard the lock states, the LED byte follows the ACK
+    fn update_leds(&mut self) {
+        let mut leds = 0;
+        if self.scroll_lock {
+            leds |= LED_SCROLL;
+        }
+        if self.num_lock {
+            leds |= LED_NUM;
+        }
+        if self.caps_lock {
+            leds |= LED_CAPS;
+        }
+        if i8042::write(i8042::KBD_SET_LEDS) {
+            self.pending_leds = Some(leds);
+        }
+    }
+}
+
+/// ANSI sequence of the arrows and the keys above them, which share their
+/// set 1 codes with the keypad
+fn navigation(code: u8) -> Option<&'static str> {
+    Some(match code {
+        0x47 => "\x1b[H",
+        0x48 => "\x1b[A",
+        0x49 => "\x1b[5~",
+        0x4B => "\x1b[D",
+        0x4D => "\x1b[C",
+        0x4F => "\x1b[F",
+        0x50 => "\x1b[B",
+        0x51 => "\x1b[6~",
+        0x52 => "\x1b[2~",
+        0x53 => "\x1b[3~",
+        _ => return None,
+    })
+}
+
+fn push_str(s: &str) {
+    for &byte in s.as_bytes() {
+        push_key(byte);
+    }
+}
+
+/// Scancode set the keyboard sends, asked with interrupts off
+fn query_set() -> Option<ScancodeSet> {
+    if !i8042::write_acked(i8042::KBD_SCANCODE_SET) || !i8042::write_acked(0) {
+        return None;
+    }
+    match i8042::read()? {
+        1 => Some(ScancodeSet::Set1),
+        2 => Some(ScancodeSet::Set2),
+        _ => None,
+    }
+}
+
+/// Set up the controller and the keyboard, nothing to do without one
+pub fn init() {
+    let set = interrupts::without_interrupts(|| {
+        let config = i8042::init()?;
+        if !i8042::enable_port1() {
+            return None;
+        }
+
+        let set = if config & i8042::CONFIG_TRANSLATE != 0 {
+            ScancodeSet::Set1
+        } else {
+            query_set().unwrap_or(ScancodeSet::Set2)
+        };
+
+        if !i8042::write_acked(i8042::KBD_ENABLE_SCANNING) {
+            return None;
+        }
+        i8042::flush();
+        i8042::set_config(config | i8042::CONFIG_IRQ1);
+        Some(set)
+    });
+
+    match set {
+        Some(set) => {
+            *KEYBOARD.lock() = Some(Keyboard::new(set));
+            chardev::register("keymap", Arc::new(KeymapDevice)).unwrap();
+            info!("PS/2 Keyboard Initialized: scancode {:?}.", set);
+        }
+        None => warn!("No PS/2 keyboard found."),
+    }
+}
+
+/// Use the keymap called `name`, false if there is none
+pub fn set_keymap(name: &str) -> bool {
+    let Some(&keymap) = KEYMAPS.iter().find(|k| k.name == name) else {
+        return false;
+    };
+    interrupts::without_interrupts(|| {
+        if let Some(keyboard) = KEYBOARD.lock().as_mut() {
+            keyboard.keymap = keymap;
+        }
+    });
+    true
+}
+
+/// Name of the keymap in use, `None` without a keyboard
+pub fn keymap_name() -> Option<&'static str> {
+    interrupts::without_interrupts(|| KEYBOARD.lock().as_ref().map(|k| k.keymap.name))
+}
+
+struct KeymapDevice;
+
+impl CharDevice for KeymapDevice {
+    fn read(&self, offset: u64, buf: &mut [u8]) -> FsResult<usize> {
+        let text = format!("{}\n", keymap_name().ok_or(FsError::IoError)?);
+        let bytes = text.as_bytes();
+
+        let start = (offset as usize).min(bytes.len());
+        let count = buf.len().min(bytes.len() - start);
+        buf[..count].copy_from_slice(&bytes[start..start + count]);
+        Ok(count)
+    }
+
+    fn write(&self, buf: &[u8]) -> FsResult<usize> {
+        let name = core::str::from_utf8(buf).map_err(|_| FsError::InvalidArgument)?;
+        if !set_keymap(name.trim()) {
+            return Err(FsError::InvalidArgument);
+        }
+        Ok(buf.len())
+    }
+}
+
+/// Called on irq 1
+pub fn handle_irq() {
+    let Some(byte) = i8042::try_read() else {
+        return;
+    };
+    if let Some(keyboard) = KEYBOARD.lock().as_mut() {
+        keyboard.receive(byte);
+    }
+}
@@ -1,3 +1,12 @@
 pub mod uart16550;
 pub mod serial;
-pub mod input;
\ No newline at end of file
+pub mod input;
+pub mod block;
+pub mod ata;
+pub mod ramdisk;
+pub mod chardev;
+pub mod acpi;
+pub mod pit;
+pub mod rtc;
+pub mod hpet;
+pub mod keyboard;
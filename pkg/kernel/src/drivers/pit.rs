@@ -0,0 +1,58 @@
+//! 8254 PIT, only used as a reference clock for short busy waits
+//!
+//! Channel 2 is the speaker channel: its gate is controlled through port
+//! 0x61 and its output can be read back there, so the other channels and
+//! the PIT irq are left alone.
+
+use x86_64::instructions::port::Port;
+
+/// Input clock of the PIT in Hz
+pub const PIT_FREQUENCY: u64 = 1_193_182;
+
+const CHANNEL2: u16 = 0x42;
+const COMMAND: u16 = 0x43;
+const SPEAKER: u16 = 0x61;
+
+/// Channel 2, lobyte/hibyte, mode 0 (interrupt on terminal count)
+const CMD_CHANNEL2_ONESHOT: u8 = 0b1011_0000;
+
+const GATE: u8 = 1 << 0;
+const SPEAKER_ON: u8 = 1 << 1;
+const OUT2: u8 = 1 << 5;
+
+/// Count down `count` PIT ticks and wait for it, `count` <= 0xFFFF
+fn wait_ticks(count: u16) {
+    let mut speaker = Port::<u8>::new(SPEAKER);
+    let mut command = Port::<u8>::new(COMMAND);
+    let mut channel = Port::<u8>::new(CHANNEL2);
+
+    unsafe {
+        // gate off while programming, speaker stays quiet
+        let ctrl = speaker.read() & !(GATE | SPEAKER_ON);
+        speaker.write(ctrl);
+
+        command.write(CMD_CHANNEL2_ONESHOT);
+        channel.write(count as u8);
+        channel.write((count >> 8) as u8);
+
+        // rising gate starts the count
+        speaker.write(ctrl | GATE);
+        while speaker.read() & OUT2 == 0 {
+            core::hint::spin_loop();
+        }
+
+        speaker.write(ctrl);
+    }
+}
+
+/// Busy wait for at least `us` microseconds
+///
+/// Works without interrupts, e.g. while starting other processors.
+pub fn udelay(us: u64) {
+    let mut ticks = (us * PIT_FREQUENCY).div_ceil(1_000_000);
+    while ticks > 0 {
+        let n = ticks.min(0xFFFF);
+        wait_ticks(n as u16);
+        ticks -= n;
+    }
+}
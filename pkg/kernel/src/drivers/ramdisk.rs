@@ -0,0 +1,62 @@
+//! Block device backed by kernel heap memory
+
+use super::block::*;
+use crate::fs::{FsError, FsResult};
+use alloc::vec::Vec;
+use spin::RwLock;
+
+pub struct RamDisk {
+    data: RwLock<Vec<u8>>,
+}
+
+impl RamDisk {
+    /// A zero filled disk of `blocks` blocks
+    pub fn new(blocks: usize) -> Self {
+        Self {
+            data: RwLock::new(alloc::vec![0u8; blocks * BLOCK_SIZE]),
+        }
+    }
+
+    #[inline]
+    fn range(&self, id: usize, len: usize) -> FsResult<core::ops::Range<usize>> {
+        let start = id.checked_mul(BLOCK_SIZE).ok_or(FsError::IoError)?;
+        match start.checked_add(len) {
+            Some(end) if end <= self.data.read().len() => Ok(start..end),
+            _ => Err(FsError::IoError),
+        }
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn block_count(&self) -> usize {
+        self.data.read().len() / BLOCK_SIZE
+    }
+
+    fn read_block(&self, id: usize, buf: &mut Block) -> FsResult {
+        self.read_blocks(id, buf)
+    }
+
+    fn write_block(&self, id: usize, buf: &Block) -> FsResult {
+        self.write_blocks(id, buf)
+    }
+
+    fn read_blocks(&self, id: usize, buf: &mut [u8]) -> FsResult {
+        let range = self.range(id, buf.len())?;
+        buf.copy_from_slice(&self.data.read()[range]);
+        Ok(())
+    }
+
+    fn write_blocks(&self, id: usize, buf: &[u8]) -> FsResult {
+        let range = self.range(id, buf.len())?;
+        self.data.write()[range].copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+impl core::fmt::Debug for RamDisk {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("RamDisk")
+            .field("blocks", &self.block_count())
+            .finish()
+    }
+}
@@ -0,0 +1,349 @@
+//! CMOS real time clock
+//!
+//! Read once at boot for the wall clock time, the monotonic clock keeps
+//! time after that. It also raises irq 8 periodically (2 Hz to 8 kHz)
+//! and once a day at an alarm time; the irq must read status register C
+//! or the RTC never raises it again.
+//!
+//! `/dev/rtc` reads as the date (`YYYY-MM-DD HH:MM:SS`, UTC) and the
+//! periodic interrupts seen so far. Writing to it takes one of
+//!
+//! ```text
+//! YYYY-MM-DD HH:MM:SS     set the date
+//! alarm HH:MM:SS | off    set or clear the daily alarm
+//! periodic <hz> | off     start or stop the periodic interrupt
+//! ```
+
+use crate::drivers::chardev::{self, CharDevice};
+use crate::fs::{FsError, FsResult};
+use alloc::format;
+use alloc::sync::Arc;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+
+const CMOS_ADDR: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+
+const REG_SECONDS: u8 = 0x00;
+const REG_SECONDS_ALARM: u8 = 0x01;
+const REG_MINUTES: u8 = 0x02;
+const REG_MINUTES_ALARM: u8 = 0x03;
+const REG_HOURS: u8 = 0x04;
+const REG_HOURS_ALARM: u8 = 0x05;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_STATUS_A: u8 = 0x0A;
+const REG_STATUS_B: u8 = 0x0B;
+const REG_STATUS_C: u8 = 0x0C;
+
+/// Update in progress, the time registers may be inconsistent
+const STATUS_A_UIP: u8 = 1 << 7;
+/// Low bits of status A, the periodic interrupt rate
+const STATUS_A_RATE: u8 = 0x0F;
+/// Stop updates while the time is written
+const STATUS_B_SET: u8 = 1 << 7;
+const STATUS_B_PERIODIC: u8 = 1 << 6;
+const STATUS_B_ALARM: u8 = 1 << 5;
+const STATUS_B_24H: u8 = 1 << 1;
+const STATUS_B_BINARY: u8 = 1 << 2;
+const STATUS_C_PERIODIC: u8 = 1 << 6;
+const STATUS_C_ALARM: u8 = 1 << 5;
+/// Set in the hour register for PM in 12 hour mode
+const HOUR_PM: u8 = 1 << 7;
+
+/// Base frequency of the periodic interrupt, divided by `1 << (rate - 1)`
+const PERIODIC_BASE: u32 = 32768;
+/// Rates 1 and 2 are broken on real hardware, 15 is the slowest
+const RATE_MIN: u8 = 3;
+const RATE_MAX: u8 = 15;
+
+/// Periodic interrupts seen since boot
+static PERIODIC_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Register select and data port are one access, so every CPU and the
+/// irq handler go through this lock; take it with interrupts off
+static CMOS: Mutex<Cmos> = Mutex::new(Cmos);
+
+struct Cmos;
+
+impl Cmos {
+    /// Read CMOS register `reg`, NMIs stay enabled
+    fn read(&mut self, reg: u8) -> u8 {
+        unsafe {
+            Port::<u8>::new(CMOS_ADDR).write(reg & 0x7F);
+            Port::<u8>::new(CMOS_DATA).read()
+        }
+    }
+
+    fn write(&mut self, reg: u8, value: u8) {
+        unsafe {
+            Port::<u8>::new(CMOS_ADDR).write(reg & 0x7F);
+            Port::<u8>::new(CMOS_DATA).write(value);
+        }
+    }
+
+    /// Set and clear bits of `reg`
+    fn update(&mut self, reg: u8, set: u8, clear: u8) {
+        let value = self.read(reg);
+        self.write(reg, (value & !clear) | set);
+    }
+
+    fn wait_update(&mut self) {
+        // an update takes under 2 ms, don't hang if there is no RTC
+        for _ in 0..100_000 {
+            if self.read(REG_STATUS_A) & STATUS_A_UIP == 0 {
+                break;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Raw time registers, century register last if there is one
+    fn read_raw(&mut self, century_reg: Option<u8>) -> [u8; 7] {
+        self.wait_update();
+        [
+            self.read(REG_SECONDS),
+            self.read(REG_MINUTES),
+            self.read(REG_HOURS),
+            self.read(REG_DAY),
+            self.read(REG_MONTH),
+            self.read(REG_YEAR),
+            century_reg.map_or(0, |reg| self.read(reg)),
+        ]
+    }
+}
+
+/// Run `f` with the CMOS locked
+fn with_cmos<T>(f: impl FnOnce(&mut Cmos) -> T) -> T {
+    interrupts::without_interrupts(|| f(&mut CMOS.lock()))
+}
+
+fn bcd_to_binary(v: u8) -> u8 {
+    (v & 0x0F) + (v >> 4) * 10
+}
+
+fn binary_to_bcd(v: u8) -> u8 {
+    ((v / 10) << 4) | (v % 10)
+}
+
+/// Register value of `v` in the format status B asks for
+fn encode(v: u8, status_b: u8) -> u8 {
+    if status_b & STATUS_B_BINARY != 0 {
+        v
+    } else {
+        binary_to_bcd(v)
+    }
+}
+
+/// Hour register value, with the PM bit in 12 hour mode
+fn encode_hour(hour: u8, status_b: u8) -> u8 {
+    if status_b & STATUS_B_24H != 0 {
+        return encode(hour, status_b);
+    }
+    // 0 is 12 AM, 12 is 12 PM
+    let pm = if hour >= 12 { HOUR_PM } else { 0 };
+    let hour = match hour % 12 {
+        0 => 12,
+        h => h,
+    };
+    encode(hour, status_b) | pm
+}
+
+/// Register holding the century, if the FADT names one
+fn century_reg() -> Option<u8> {
+    crate::acpi::fadt()
+        .map(|f| f.century)
+        .filter(|&reg| reg != 0)
+}
+
+/// Current date and time, taken as UTC
+pub fn now() -> Option<NaiveDateTime> {
+    let century_reg = century_reg();
+
+    let (raw, status_b) = with_cmos(|cmos| {
+        // read until two reads agree, an update may happen in between
+        let mut raw = cmos.read_raw(century_reg);
+        loop {
+            let again = cmos.read_raw(century_reg);
+            if again == raw {
+                break;
+            }
+            raw = again;
+        }
+        (raw, cmos.read(REG_STATUS_B))
+    });
+    let [mut sec, mut min, hour_raw, mut day, mut month, mut year, mut century] = raw;
+    let pm = hour_raw & HOUR_PM != 0;
+    let mut hour = hour_raw & !HOUR_PM;
+
+    if status_b & STATUS_B_BINARY == 0 {
+        sec = bcd_to_binary(sec);
+        min = bcd_to_binary(min);
+        hour = bcd_to_binary(hour);
+        day = bcd_to_binary(day);
+        month = bcd_to_binary(month);
+        year = bcd_to_binary(year);
+        century = bcd_to_binary(century);
+    }
+
+    if status_b & STATUS_B_24H == 0 {
+        // 12 AM is 0, 12 PM is 12
+        hour %= 12;
+        if pm {
+            hour += 12;
+        }
+    }
+
+    let century = if century != 0 { century as i32 } else { 20 };
+    NaiveDate::from_ymd_opt(century * 100 + year as i32, month as u32, day as u32)?
+        .and_hms_opt(hour as u32, min as u32, sec as u32)
+}
+
+/// Write `time` to the RTC, false if it can't hold the year
+///
+/// Without a century register only 2000 to 2099 can be stored.
+pub fn set(time: NaiveDateTime) -> bool {
+    let century_reg = century_reg();
+    let year = time.year();
+    if !(0..10000).contains(&year) || (century_reg.is_none() && year / 100 != 20) {
+        return false;
+    }
+
+    with_cmos(|cmos| {
+        let status_b = cmos.read(REG_STATUS_B);
+        // no updates while the registers are half written
+        cmos.write(REG_STATUS_B, status_b | STATUS_B_SET);
+
+        cmos.write(REG_SECONDS, encode(time.second() as u8, status_b));
+        cmos.write(REG_MINUTES, encode(time.minute() as u8, status_b));
+        cmos.write(REG_HOURS, encode_hour(time.hour() as u8, status_b));
+        cmos.write(REG_DAY, encode(time.day() as u8, status_b));
+        cmos.write(REG_MONTH, encode(time.month() as u8, status_b));
+        cmos.write(REG_YEAR, encode((year % 100) as u8, status_b));
+        if let Some(reg) = century_reg {
+            cmos.write(reg, encode((year / 100) as u8, status_b));
+        }
+
+        cmos.write(REG_STATUS_B, status_b & !STATUS_B_SET);
+    });
+    true
+}
+
+/// Raise irq 8 about `hz` times a second, rounded up to a power of two
+/// between 2 and 8192; returns the rate actually set
+pub fn enable_periodic(hz: u32) -> u32 {
+    // frequency is PERIODIC_BASE >> (rate - 1)
+    let shift = (PERIODIC_BASE / hz.max(1)).max(1).ilog2() as u8;
+    let rate = (shift + 1).clamp(RATE_MIN, RATE_MAX);
+
+    with_cmos(|cmos| {
+        cmos.update(REG_STATUS_A, rate, STATUS_A_RATE);
+        cmos.update(REG_STATUS_B, STATUS_B_PERIODIC, 0);
+    });
+    PERIODIC_BASE >> (rate - 1)
+}
+
+pub fn disable_periodic() {
+    with_cmos(|cmos| cmos.update(REG_STATUS_B, 0, STATUS_B_PERIODIC));
+}
+
+/// Raise irq 8 every day at `time`
+pub fn set_alarm(time: NaiveTime) {
+    with_cmos(|cmos| {
+        let status_b = cmos.read(REG_STATUS_B);
+        cmos.write(REG_SECONDS_ALARM, encode(time.second() as u8, status_b));
+        cmos.write(REG_MINUTES_ALARM, encode(time.minute() as u8, status_b));
+        cmos.write(REG_HOURS_ALARM, encode_hour(time.hour() as u8, status_b));
+        cmos.write(REG_STATUS_B, status_b | STATUS_B_ALARM);
+    });
+}
+
+pub fn clear_alarm() {
+    with_cmos(|cmos| cmos.update(REG_STATUS_B, 0, STATUS_B_ALARM));
+}
+
+/// Periodic interrupts seen since boot
+pub fn periodic_count() -> u64 {
+    PERIODIC_COUNT.load(Ordering::Relaxed)
+}
+
+/// Called on irq 8, interrupts are off
+pub fn handle_irq() {
+    let mut cmos = CMOS.lock();
+    // reading C acknowledges the interrupt
+    let status_c = cmos.read(REG_STATUS_C);
+
+    if status_c & STATUS_C_PERIODIC != 0 {
+        PERIODIC_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+
+    if status_c & STATUS_C_ALARM != 0 {
+        // raw registers, decoding them is too much for an irq handler
+        info!(
+            "RTC alarm at {:02x}:{:02x}:{:02x} (status B {:#04x})",
+            cmos.read(REG_HOURS),
+            cmos.read(REG_MINUTES),
+            cmos.read(REG_SECONDS),
+            cmos.read(REG_STATUS_B)
+        );
+    }
+}
+
+/// `/dev/rtc`
+struct RtcDevice;
+
+impl CharDevice for RtcDevice {
+    fn read(&self, offset: u64, buf: &mut [u8]) -> FsResult<usize> {
+        let time = now().ok_or(FsError::IoError)?;
+        let text = format!("{}\nperiodic {}\n", time, periodic_count());
+        let bytes = text.as_bytes();
+
+        // the whole text on every read from the start, then end of file
+        let start = (offset as usize).min(bytes.len());
+        let count = buf.len().min(bytes.len() - start);
+        buf[..count].copy_from_slice(&bytes[start..start + count]);
+        Ok(count)
+    }
+
+    fn write(&self, buf: &[u8]) -> FsResult<usize> {
+        let text = core::str::from_utf8(buf).map_err(|_| FsError::InvalidArgument)?;
+        let text = text.trim();
+
+        if let Some(arg) = text.strip_prefix("alarm ") {
+            match arg.trim() {
+                "off" => clear_alarm(),
+                arg => set_alarm(
+                    NaiveTime::parse_from_str(arg, "%H:%M:%S")
+                        .map_err(|_| FsError::InvalidArgument)?,
+                ),
+            }
+        } else if let Some(arg) = text.strip_prefix("periodic ") {
+            match arg.trim() {
+                "off" => disable_periodic(),
+                arg => {
+                    let hz = arg.parse().map_err(|_| FsError::InvalidArgument)?;
+                    let hz = enable_periodic(hz);
+                    debug!("RTC periodic interrupt at {} Hz", hz);
+                }
+            }
+        } else {
+            let time = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S")
+                .map_err(|_| FsError::InvalidArgument)?;
+            if !crate::clock::set_now(time) {
+                return Err(FsError::IoError);
+            }
+        }
+        Ok(buf.len())
+    }
+}
+
+/// Drop anything pending from before boot and add `/dev/rtc`
+pub fn init() {
+    with_cmos(|cmos| cmos.read(REG_STATUS_C));
+    chardev::register("rtc", Arc::new(RtcDevice)).unwrap();
+    info!("RTC Initialized.");
+}
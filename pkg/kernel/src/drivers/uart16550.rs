@@ -80,6 +80,11 @@ impl SerialPort {
             return PortReadOnly::<u8>::new(self.PORT + 5).read() & 0x1;
         }
     }
+    /// Whether `receive` would return a byte
+    pub fn has_data(&self) -> bool {
+        self.serial_received() != 0
+    }
+
     /// Receives a byte on the serial port no wait.
     pub fn receive(&mut self) -> Option<u8> {
         // FIXME: Receive a byte on the serial port no wait
@@ -0,0 +1,77 @@
+//! Device filesystem
+//!
+//! Lists the registered character devices, `/dev/<name>` opens like any
+//! other file and reads/writes go straight to the driver.
+
+use super::*;
+use crate::drivers::chardev::{self, CharDevice};
+
+pub struct DevFs;
+
+impl FileSystem for DevFs {
+    fn name(&self) -> &str {
+        "devfs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(DevRoot)
+    }
+}
+
+struct DevRoot;
+
+impl Inode for DevRoot {
+    fn metadata(&self) -> FsResult<Metadata> {
+        Ok(Metadata::new(1, FileType::Directory, 0))
+    }
+
+    fn lookup(&self, name: &str) -> FsResult<Arc<dyn Inode>> {
+        let device = chardev::get(name).ok_or(FsError::NotFound)?;
+        let ino = chardev::names()
+            .iter()
+            .position(|n| n == name)
+            .unwrap_or_default() as u64
+            + 2;
+
+        Ok(Arc::new(DevInode { ino, device }))
+    }
+
+    fn read_dir(&self) -> FsResult<Vec<DirEntry>> {
+        Ok(chardev::names()
+            .iter()
+            .map(|name| DirEntry::new(name, FileType::CharDevice, 0))
+            .collect())
+    }
+}
+
+struct DevInode {
+    ino: u64,
+    device: Arc<dyn CharDevice>,
+}
+
+impl Inode for DevInode {
+    fn metadata(&self) -> FsResult<Metadata> {
+        Ok(Metadata::new(self.ino, FileType::CharDevice, 0))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> FsResult<usize> {
+        self.device.read(offset, buf)
+    }
+
+    fn read_from(&self, offset: u64, buf: &mut [u8]) -> FsResult<(usize, u64)> {
+        self.device.read_from(offset, buf)
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> FsResult<usize> {
+        self.device.write(buf)
+    }
+
+    /// Opening with `TRUNCATE` is harmless on a device
+    fn truncate(&self, _size: u64) -> FsResult {
+        Ok(())
+    }
+
+    fn poll(&self) -> PollEvents {
+        self.device.poll()
+    }
+}
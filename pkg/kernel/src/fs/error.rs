@@ -0,0 +1,55 @@
+/// Errors returned by the filesystem layer
+///
+/// Each variant maps to a negative errno-like code when passed back
+/// to user space through a syscall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    NotFound,
+    AlreadyExists,
+    NotADirectory,
+    IsADirectory,
+    DirectoryNotEmpty,
+    InvalidPath,
+    /// Bad argument that is not a path, e.g. a malformed device command
+    InvalidArgument,
+    InvalidSeek,
+    BadDescriptor,
+    TooManyOpenFiles,
+    PermissionDenied,
+    NoSpace,
+    BufferTooSmall,
+    NotSupported,
+    IoError,
+    Corrupted,
+}
+
+pub type FsResult<T = ()> = Result<T, FsError>;
+
+impl FsError {
+    /// Negative code returned in `rax` for a failed syscall
+    pub fn as_code(&self) -> isize {
+        -(match self {
+            FsError::NotFound => 2,
+            FsError::IoError => 5,
+            FsError::BadDescriptor => 9,
+            FsError::PermissionDenied => 13,
+            FsError::AlreadyExists => 17,
+            FsError::NotADirectory => 20,
+            FsError::IsADirectory => 21,
+            FsError::InvalidPath | FsError::InvalidArgument => 22,
+            FsError::TooManyOpenFiles => 24,
+            FsError::NoSpace => 28,
+            FsError::InvalidSeek => 29,
+            FsError::BufferTooSmall => 34,
+            FsError::NotSupported => 38,
+            FsError::DirectoryNotEmpty => 39,
+            FsError::Corrupted => 117,
+        })
+    }
+}
+
+impl core::fmt::Display for FsError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
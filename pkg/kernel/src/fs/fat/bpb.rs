@@ -0,0 +1,218 @@
+//! BIOS Parameter Block, the first sector of a FAT volume
+//!
+//! Reference: <https://wiki.osdev.org/FAT#BPB_(BIOS_Parameter_Block)>
+
+use crate::drivers::block::BLOCK_SIZE;
+use crate::fs::{FsError, FsResult};
+
+/// Most clusters a FAT12 volume can have
+const FAT12_MAX_CLUSTERS: u32 = 4084;
+/// Fixed size of the root directory `fat12` gives a volume
+const FAT12_ROOT_ENTRIES: u16 = 224;
+/// Media descriptor of a fixed disk, also the low byte of FAT entry 0
+pub const MEDIA_FIXED: u8 = 0xF8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+#[derive(Debug, Clone)]
+pub struct BiosParameterBlock {
+    pub bytes_per_sector: u16,
+    pub sectors_per_cluster: u8,
+    pub reserved_sectors: u16,
+    pub fat_count: u8,
+    pub root_entries: u16,
+    pub total_sectors: u32,
+    pub sectors_per_fat: u32,
+    /// First cluster of the root directory, FAT32 only
+    pub root_cluster: u32,
+    pub volume_label: [u8; 11],
+}
+
+#[inline]
+fn u16_at(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+#[inline]
+fn u32_at(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+impl BiosParameterBlock {
+    pub fn parse(sector: &[u8; BLOCK_SIZE]) -> FsResult<Self> {
+        if sector[510] != 0x55 || sector[511] != 0xAA {
+            return Err(FsError::Corrupted);
+        }
+
+        // a boot sector starts with a short or near jump
+        if sector[0] != 0xEB && sector[0] != 0xE9 {
+            return Err(FsError::Corrupted);
+        }
+
+        let sectors_per_fat_16 = u16_at(sector, 0x16) as u32;
+        let total_sectors_16 = u16_at(sector, 0x13) as u32;
+
+        let fat32 = sectors_per_fat_16 == 0;
+
+        let mut volume_label = [0u8; 11];
+        let label_offset = if fat32 { 0x47 } else { 0x2B };
+        volume_label.copy_from_slice(&sector[label_offset..label_offset + 11]);
+
+        let bpb = Self {
+            bytes_per_sector: u16_at(sector, 0x0B),
+            sectors_per_cluster: sector[0x0D],
+            reserved_sectors: u16_at(sector, 0x0E),
+            fat_count: sector[0x10],
+            root_entries: u16_at(sector, 0x11),
+            total_sectors: if total_sectors_16 != 0 {
+                total_sectors_16
+            } else {
+                u32_at(sector, 0x20)
+            },
+            sectors_per_fat: if fat32 {
+                u32_at(sector, 0x24)
+            } else {
+                sectors_per_fat_16
+            },
+            root_cluster: if fat32 { u32_at(sector, 0x2C) } else { 0 },
+            volume_label,
+        };
+
+        if bpb.bytes_per_sector as usize != BLOCK_SIZE {
+            warn!("FAT: unsupported sector size {}", bpb.bytes_per_sector);
+            return Err(FsError::NotSupported);
+        }
+
+        if !bpb.sectors_per_cluster.is_power_of_two()
+            || bpb.fat_count == 0
+            || bpb.reserved_sectors == 0
+            || bpb.sectors_per_fat == 0
+        {
+            return Err(FsError::Corrupted);
+        }
+
+        // no room for data, `cluster_count` would underflow; summed in u64
+        // so `first_data_sector` can't overflow either once this passes
+        let data_start = bpb.reserved_sectors as u64
+            + bpb.fat_count as u64 * bpb.sectors_per_fat as u64
+            + bpb.root_dir_sectors() as u64;
+        if bpb.total_sectors as u64 <= data_start {
+            return Err(FsError::Corrupted);
+        }
+
+        Ok(bpb)
+    }
+
+    /// Layout of a new FAT12 volume of `total_sectors`
+    ///
+    /// Clusters grow until their count fits in FAT12, each FAT has room
+    /// for as many clusters as there are sectors.
+    pub fn fat12(total_sectors: u32, label: &str) -> FsResult<Self> {
+        let mut sectors_per_cluster = 1u32;
+        while total_sectors / sectors_per_cluster > FAT12_MAX_CLUSTERS {
+            sectors_per_cluster *= 2;
+        }
+        if sectors_per_cluster > 128 {
+            return Err(FsError::NotSupported);
+        }
+
+        // 12 bits per entry, plus the two reserved ones
+        let entries = total_sectors / sectors_per_cluster + 2;
+        let mut bpb = Self {
+            bytes_per_sector: BLOCK_SIZE as u16,
+            sectors_per_cluster: sectors_per_cluster as u8,
+            reserved_sectors: 1,
+            fat_count: 2,
+            root_entries: FAT12_ROOT_ENTRIES,
+            total_sectors,
+            sectors_per_fat: (entries * 3).div_ceil(2).div_ceil(BLOCK_SIZE as u32),
+            root_cluster: 0,
+            volume_label: [b' '; 11],
+        };
+
+        if total_sectors <= bpb.first_data_sector() {
+            return Err(FsError::NoSpace);
+        }
+
+        let len = label.len().min(11);
+        bpb.volume_label[..len].copy_from_slice(&label.as_bytes()[..len]);
+        Ok(bpb)
+    }
+
+    /// Boot sector of a FAT12/16 volume with this BPB, no boot code
+    pub fn to_sector(&self) -> [u8; BLOCK_SIZE] {
+        let mut sector = [0u8; BLOCK_SIZE];
+        sector[0..3].copy_from_slice(&[0xEB, 0x3C, 0x90]);
+        sector[3..11].copy_from_slice(b"YSOS    ");
+
+        sector[0x0B..0x0D].copy_from_slice(&self.bytes_per_sector.to_le_bytes());
+        sector[0x0D] = self.sectors_per_cluster;
+        sector[0x0E..0x10].copy_from_slice(&self.reserved_sectors.to_le_bytes());
+        sector[0x10] = self.fat_count;
+        sector[0x11..0x13].copy_from_slice(&self.root_entries.to_le_bytes());
+        match u16::try_from(self.total_sectors) {
+            Ok(total) => sector[0x13..0x15].copy_from_slice(&total.to_le_bytes()),
+            Err(_) => sector[0x20..0x24].copy_from_slice(&self.total_sectors.to_le_bytes()),
+        }
+        sector[0x15] = MEDIA_FIXED;
+        sector[0x16..0x18].copy_from_slice(&(self.sectors_per_fat as u16).to_le_bytes());
+
+        // extended boot record
+        sector[0x26] = 0x29;
+        sector[0x2B..0x36].copy_from_slice(&self.volume_label);
+        let name: &[u8; 8] = match self.fat_type() {
+            FatType::Fat12 => b"FAT12   ",
+            _ => b"FAT16   ",
+        };
+        sector[0x36..0x3E].copy_from_slice(name);
+
+        sector[510] = 0x55;
+        sector[511] = 0xAA;
+        sector
+    }
+
+    /// Sectors taken by the fixed root directory of FAT12/16
+    pub fn root_dir_sectors(&self) -> u32 {
+        (self.root_entries as u32 * 32).div_ceil(self.bytes_per_sector as u32)
+    }
+
+    pub fn first_fat_sector(&self) -> u32 {
+        self.reserved_sectors as u32
+    }
+
+    pub fn first_root_dir_sector(&self) -> u32 {
+        self.first_fat_sector() + self.fat_count as u32 * self.sectors_per_fat
+    }
+
+    pub fn first_data_sector(&self) -> u32 {
+        self.first_root_dir_sector() + self.root_dir_sectors()
+    }
+
+    pub fn cluster_count(&self) -> u32 {
+        (self.total_sectors - self.first_data_sector()) / self.sectors_per_cluster as u32
+    }
+
+    pub fn cluster_size(&self) -> usize {
+        self.sectors_per_cluster as usize * self.bytes_per_sector as usize
+    }
+
+    /// The FAT type is determined by the number of clusters only
+    pub fn fat_type(&self) -> FatType {
+        match self.cluster_count() {
+            0..=4084 => FatType::Fat12,
+            4085..=65524 => FatType::Fat16,
+            _ => FatType::Fat32,
+        }
+    }
+
+    pub fn volume_label(&self) -> &str {
+        core::str::from_utf8(&self.volume_label)
+            .unwrap_or_default()
+            .trim_end()
+    }
+}
@@ -0,0 +1,523 @@
+//! Directory entries and long file names
+//!
+//! Reference: <https://wiki.osdev.org/FAT#Directories_on_FAT12.2F16.2F32>
+
+use super::volume::FatVolume;
+use crate::fs::{FsError, FsResult};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+
+pub const DIR_ENTRY_SIZE: usize = 32;
+
+/// First name byte of a deleted entry
+const DELETED: u8 = 0xE5;
+/// Last LFN entry of a name (the first one on disk)
+const LFN_LAST: u8 = 0x40;
+/// Characters stored in one LFN entry
+const LFN_CHARS: usize = 13;
+/// Offsets of the UCS-2 characters inside an LFN entry
+const LFN_OFFSETS: [usize; LFN_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Attributes: u8 {
+        const READ_ONLY = 0x01;
+        const HIDDEN    = 0x02;
+        const SYSTEM    = 0x04;
+        const VOLUME_ID = 0x08;
+        const DIRECTORY = 0x10;
+        const ARCHIVE   = 0x20;
+        const LFN       = 0x0F;
+    }
+}
+
+/// Where the entries of a directory are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirLocation {
+    /// The fixed-size root directory of FAT12/16
+    FixedRoot,
+    /// A cluster chain, for every other directory
+    Chain(u32),
+}
+
+/// An 8.3 directory entry
+#[derive(Debug, Clone)]
+pub struct ShortEntry {
+    pub name: [u8; 11],
+    pub attr: Attributes,
+    /// NT case flags: 0x08 lowercase base, 0x10 lowercase extension
+    pub case: u8,
+    pub first_cluster: u32,
+    pub size: u32,
+    pub ctime: u16,
+    pub cdate: u16,
+    pub mtime: u16,
+    pub mdate: u16,
+}
+
+impl ShortEntry {
+    pub fn new(name: [u8; 11], attr: Attributes) -> Self {
+        let (date, time) = encode_datetime(crate::utils::clock::now());
+        Self {
+            name,
+            attr,
+            case: 0,
+            first_cluster: 0,
+            size: 0,
+            ctime: time,
+            cdate: date,
+            mtime: time,
+            mdate: date,
+        }
+    }
+
+    pub fn parse(raw: &[u8; DIR_ENTRY_SIZE]) -> Self {
+        let u16_at = |offset: usize| u16::from_le_bytes([raw[offset], raw[offset + 1]]);
+
+        let mut name = [0u8; 11];
+        name.copy_from_slice(&raw[0..11]);
+        if name[0] == 0x05 {
+            // 0x05 stands in for a real leading 0xE5
+            name[0] = DELETED;
+        }
+
+        Self {
+            name,
+            attr: Attributes::from_bits_retain(raw[11]),
+            case: raw[12],
+            ctime: u16_at(14),
+            cdate: u16_at(16),
+            first_cluster: (u16_at(20) as u32) << 16 | u16_at(26) as u32,
+            mtime: u16_at(22),
+            mdate: u16_at(24),
+            size: u32::from_le_bytes(raw[28..32].try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; DIR_ENTRY_SIZE] {
+        let mut raw = [0u8; DIR_ENTRY_SIZE];
+        raw[0..11].copy_from_slice(&self.name);
+        if raw[0] == DELETED {
+            raw[0] = 0x05;
+        }
+        raw[11] = self.attr.bits();
+        raw[12] = self.case;
+        raw[14..16].copy_from_slice(&self.ctime.to_le_bytes());
+        raw[16..18].copy_from_slice(&self.cdate.to_le_bytes());
+        raw[18..20].copy_from_slice(&self.mdate.to_le_bytes());
+        raw[20..22].copy_from_slice(&((self.first_cluster >> 16) as u16).to_le_bytes());
+        raw[22..24].copy_from_slice(&self.mtime.to_le_bytes());
+        raw[24..26].copy_from_slice(&self.mdate.to_le_bytes());
+        raw[26..28].copy_from_slice(&(self.first_cluster as u16).to_le_bytes());
+        raw[28..32].copy_from_slice(&self.size.to_le_bytes());
+        raw
+    }
+
+    #[inline]
+    pub fn is_dir(&self) -> bool {
+        self.attr.contains(Attributes::DIRECTORY)
+    }
+
+    /// `.` or `..`
+    #[inline]
+    pub fn is_dot(&self) -> bool {
+        self.name[0] == b'.'
+    }
+
+    /// The 8.3 name as `BASE.EXT`, honoring the NT lowercase flags
+    pub fn display_name(&self) -> String {
+        let part = |bytes: &[u8], lower: bool| {
+            let s: String = bytes
+                .iter()
+                .map(|&b| b as char)
+                .collect::<String>()
+                .trim_end()
+                .into();
+            if lower {
+                s.to_ascii_lowercase()
+            } else {
+                s
+            }
+        };
+
+        let base = part(&self.name[0..8], self.case & 0x08 != 0);
+        let ext = part(&self.name[8..11], self.case & 0x10 != 0);
+
+        if ext.is_empty() {
+            base
+        } else {
+            format!("{}.{}", base, ext)
+        }
+    }
+
+    /// Checksum of the 8.3 name stored in each of its LFN entries
+    pub fn checksum(&self) -> u8 {
+        self.name
+            .iter()
+            .fold(0u8, |sum, &b| (sum >> 1 | sum << 7).wrapping_add(b))
+    }
+
+    pub fn modified(&self) -> Option<NaiveDateTime> {
+        decode_datetime(self.mdate, self.mtime)
+    }
+
+    pub fn touch(&mut self) {
+        (self.mdate, self.mtime) = encode_datetime(crate::utils::clock::now());
+    }
+}
+
+fn decode_datetime(date: u16, time: u16) -> Option<NaiveDateTime> {
+    NaiveDate::from_ymd_opt(
+        1980 + (date >> 9) as i32,
+        (date >> 5 & 0xF) as u32,
+        (date & 0x1F) as u32,
+    )?
+    .and_hms_opt(
+        (time >> 11) as u32,
+        (time >> 5 & 0x3F) as u32,
+        (time & 0x1F) as u32 * 2,
+    )
+}
+
+fn encode_datetime(time: Option<NaiveDateTime>) -> (u16, u16) {
+    match time {
+        Some(t) if t.year() >= 1980 => (
+            ((t.year() - 1980) as u16) << 9 | (t.month() as u16) << 5 | t.day() as u16,
+            (t.hour() as u16) << 11 | (t.minute() as u16) << 5 | (t.second() / 2) as u16,
+        ),
+        _ => (0, 0),
+    }
+}
+
+/// A parsed directory entry with the slots it occupies
+#[derive(Debug, Clone)]
+pub struct DirItem {
+    /// Long name if present and valid, otherwise the 8.3 name
+    pub name: String,
+    pub entry: ShortEntry,
+    /// Byte offset of the 8.3 entry on the volume
+    pub pos: u64,
+    /// Byte offsets of the LFN entries belonging to this item
+    pub lfn_slots: Vec<u64>,
+}
+
+/// Collects LFN entries until the 8.3 entry they belong to shows up
+#[derive(Default)]
+struct LfnBuilder {
+    parts: Vec<[u16; LFN_CHARS]>,
+    slots: Vec<u64>,
+    checksum: u8,
+    expected: u8,
+}
+
+impl LfnBuilder {
+    fn push(&mut self, pos: u64, raw: &[u8; DIR_ENTRY_SIZE]) {
+        let order = raw[0] & 0x3F;
+
+        if raw[0] & LFN_LAST != 0 {
+            self.reset();
+            self.expected = order;
+            self.checksum = raw[13];
+        } else if order != self.expected || raw[13] != self.checksum {
+            // out of sequence, drop what we have
+            self.reset();
+            return;
+        }
+
+        if order == 0 {
+            self.reset();
+            return;
+        }
+
+        let mut part = [0u16; LFN_CHARS];
+        for (i, &offset) in LFN_OFFSETS.iter().enumerate() {
+            part[i] = u16::from_le_bytes([raw[offset], raw[offset + 1]]);
+        }
+
+        self.parts.push(part);
+        self.slots.push(pos);
+        self.expected = order - 1;
+    }
+
+    /// The long name if a complete, matching sequence precedes `entry`
+    fn finish(&mut self, entry: &ShortEntry) -> Option<String> {
+        let complete = !self.parts.is_empty() && self.expected == 0;
+        let valid = complete && self.checksum == entry.checksum();
+
+        let name = if valid {
+            let units = self
+                .parts
+                .iter()
+                .rev()
+                .flat_map(|p| p.iter().copied())
+                .take_while(|&c| c != 0x0000 && c != 0xFFFF);
+            char::decode_utf16(units)
+                .collect::<Result<String, _>>()
+                .ok()
+        } else {
+            None
+        };
+
+        if name.is_none() {
+            self.slots.clear();
+        }
+        self.parts.clear();
+        name
+    }
+
+    fn reset(&mut self) {
+        self.parts.clear();
+        self.slots.clear();
+        self.expected = 0;
+    }
+}
+
+impl FatVolume {
+    /// All 32-byte slots of a directory with their byte offsets
+    fn read_slots(&self, dir: DirLocation) -> FsResult<Vec<(u64, [u8; DIR_ENTRY_SIZE])>> {
+        let regions: Vec<(u64, usize)> = match dir {
+            DirLocation::FixedRoot => alloc::vec![(self.root_dir_offset(), self.root_dir_size())],
+            DirLocation::Chain(first) => self
+                .chain(first)?
+                .into_iter()
+                .map(|c| (self.cluster_offset(c), self.cluster_size()))
+                .collect(),
+        };
+
+        let mut slots = Vec::new();
+        let mut buf = alloc::vec![0u8; regions.iter().map(|r| r.1).max().unwrap_or(0)];
+
+        for (offset, size) in regions {
+            self.read_bytes(offset, &mut buf[..size])?;
+            for (i, raw) in buf[..size].chunks_exact(DIR_ENTRY_SIZE).enumerate() {
+                slots.push((
+                    offset + (i * DIR_ENTRY_SIZE) as u64,
+                    raw.try_into().unwrap(),
+                ));
+            }
+        }
+
+        Ok(slots)
+    }
+
+    /// Parse the entries of a directory, including `.` and `..`
+    pub fn dir_items(&self, dir: DirLocation) -> FsResult<Vec<DirItem>> {
+        let mut items = Vec::new();
+        let mut lfn = LfnBuilder::default();
+
+        for (pos, raw) in self.read_slots(dir)? {
+            match raw[0] {
+                0x00 => break,
+                DELETED => {
+                    lfn.reset();
+                    continue;
+                }
+                _ => {}
+            }
+
+            if raw[11] & Attributes::LFN.bits() == Attributes::LFN.bits() {
+                lfn.push(pos, &raw);
+                continue;
+            }
+
+            let entry = ShortEntry::parse(&raw);
+
+            if entry.attr.contains(Attributes::VOLUME_ID) {
+                lfn.reset();
+                continue;
+            }
+
+            let name = lfn.finish(&entry).unwrap_or_else(|| entry.display_name());
+            items.push(DirItem {
+                name,
+                entry,
+                pos,
+                lfn_slots: core::mem::take(&mut lfn.slots),
+            });
+        }
+
+        Ok(items)
+    }
+
+    pub fn read_entry(&self, pos: u64) -> FsResult<ShortEntry> {
+        let mut raw = [0u8; DIR_ENTRY_SIZE];
+        self.read_bytes(pos, &mut raw)?;
+        Ok(ShortEntry::parse(&raw))
+    }
+
+    pub fn write_entry(&self, pos: u64, entry: &ShortEntry) -> FsResult {
+        self.write_bytes(pos, &entry.to_bytes())
+    }
+
+    /// Mark the slots of an item as deleted
+    pub fn remove_item(&self, item: &DirItem) -> FsResult {
+        for &pos in item.lfn_slots.iter().chain(core::iter::once(&item.pos)) {
+            self.write_bytes(pos, &[DELETED])?;
+        }
+        Ok(())
+    }
+
+    /// Find `count` consecutive free slots, growing the directory if needed
+    fn alloc_slots(&self, dir: DirLocation, count: usize) -> FsResult<Vec<u64>> {
+        let mut run = Vec::new();
+        let mut ended = false;
+
+        for (pos, raw) in self.read_slots(dir)? {
+            // everything after the end marker is free as well
+            ended |= raw[0] == 0x00;
+            if ended || raw[0] == DELETED {
+                run.push(pos);
+                if run.len() == count {
+                    return Ok(run);
+                }
+            } else {
+                run.clear();
+            }
+        }
+
+        let DirLocation::Chain(first) = dir else {
+            return Err(FsError::NoSpace);
+        };
+
+        // the new clusters are zeroed, so all of their slots are free
+        let mut last = *self.chain(first)?.last().ok_or(FsError::Corrupted)?;
+        while run.len() < count {
+            last = self.alloc_cluster(Some(last))?;
+            let offset = self.cluster_offset(last);
+            for i in 0..self.cluster_size() / DIR_ENTRY_SIZE {
+                run.push(offset + (i * DIR_ENTRY_SIZE) as u64);
+            }
+        }
+        run.truncate(count);
+
+        Ok(run)
+    }
+
+    /// Add an entry named `name` to a directory
+    ///
+    /// An 8.3 alias is generated, and LFN entries are written whenever
+    /// the alias does not spell the name exactly. Returns the byte offset
+    /// of the 8.3 entry.
+    pub fn insert_item(&self, dir: DirLocation, name: &str, mut entry: ShortEntry) -> FsResult<u64> {
+        let items = self.dir_items(dir)?;
+
+        let (short, exact) = short_name(name, |candidate| {
+            items.iter().any(|item| &item.entry.name == candidate)
+        })
+        .ok_or(FsError::AlreadyExists)?;
+        entry.name = short;
+
+        let units: Vec<u16> = name.encode_utf16().collect();
+        let lfn_count = if exact { 0 } else { units.len().div_ceil(LFN_CHARS) };
+
+        let slots = self.alloc_slots(dir, lfn_count + 1)?;
+        let checksum = entry.checksum();
+
+        for (i, &pos) in slots[..lfn_count].iter().enumerate() {
+            let order = (lfn_count - i) as u8;
+            let mut raw = [0u8; DIR_ENTRY_SIZE];
+            raw[0] = if i == 0 { order | LFN_LAST } else { order };
+            raw[11] = Attributes::LFN.bits();
+            raw[13] = checksum;
+
+            for (j, &offset) in LFN_OFFSETS.iter().enumerate() {
+                let idx = (order as usize - 1) * LFN_CHARS + j;
+                let c = match idx.cmp(&units.len()) {
+                    core::cmp::Ordering::Less => units[idx],
+                    core::cmp::Ordering::Equal => 0x0000,
+                    core::cmp::Ordering::Greater => 0xFFFF,
+                };
+                raw[offset..offset + 2].copy_from_slice(&c.to_le_bytes());
+            }
+
+            self.write_bytes(pos, &raw)?;
+        }
+
+        let pos = slots[lfn_count];
+        self.write_entry(pos, &entry)?;
+
+        Ok(pos)
+    }
+}
+
+/// Characters allowed in an 8.3 name besides letters and digits
+const SHORT_NAME_SPECIAL: &[u8] = b"!#$%&'()-@^_`{}~";
+
+/// If a name may be stored in a directory at all
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name.encode_utf16().count() <= 255
+        && !name
+            .chars()
+            .any(|c| c.is_control() || "\"*/:<>?\\|".contains(c))
+}
+
+/// Generate the 8.3 alias of `name`
+///
+/// Returns the alias and whether it represents the name exactly
+/// (so no LFN entries are needed), or `None` if no free alias exists.
+fn short_name(name: &str, exists: impl Fn(&[u8; 11]) -> bool) -> Option<([u8; 11], bool)> {
+    let convert = |s: &str| -> Vec<u8> {
+        s.chars()
+            .filter(|&c| c != ' ' && c != '.')
+            .map(|c| {
+                let c = c.to_ascii_uppercase();
+                if c.is_ascii_alphanumeric() || (c.is_ascii() && SHORT_NAME_SPECIAL.contains(&(c as u8))) {
+                    c as u8
+                } else {
+                    b'_'
+                }
+            })
+            .collect()
+    };
+
+    let trimmed = name.trim_start_matches('.');
+    let (base, ext) = match trimmed.rfind('.') {
+        Some(idx) => (&trimmed[..idx], &trimmed[idx + 1..]),
+        None => (trimmed, ""),
+    };
+
+    let base_bytes = convert(base);
+    let ext_bytes = convert(ext);
+
+    let mut short = [b' '; 11];
+    let ext_len = ext_bytes.len().min(3);
+    short[8..8 + ext_len].copy_from_slice(&ext_bytes[..ext_len]);
+
+    let fits = !base_bytes.is_empty() && base_bytes.len() <= 8 && ext_bytes.len() <= 3;
+    if fits {
+        short[..base_bytes.len()].copy_from_slice(&base_bytes);
+        let base_str = core::str::from_utf8(&base_bytes).unwrap_or_default();
+        let ext_str = core::str::from_utf8(&ext_bytes).unwrap_or_default();
+        let exact = if ext_str.is_empty() {
+            name == base_str
+        } else {
+            name == format!("{}.{}", base_str, ext_str)
+        };
+
+        if !exists(&short) {
+            return Some((short, exact));
+        }
+    }
+
+    // numeric tail: BASE~N
+    for n in 1..=999_999u32 {
+        let tail = format!("~{}", n);
+        let keep = (8 - tail.len()).min(base_bytes.len());
+
+        let mut candidate = [b' '; 11];
+        candidate[8..].copy_from_slice(&short[8..]);
+        candidate[..keep].copy_from_slice(&base_bytes[..keep]);
+        candidate[keep..keep + tail.len()].copy_from_slice(tail.as_bytes());
+
+        if !exists(&candidate) {
+            return Some((candidate, false));
+        }
+    }
+
+    None
+}
@@ -0,0 +1,391 @@
+//! FAT12/16/32 filesystem
+//!
+//! Every inode remembers where its 8.3 entry lives on the volume and
+//! reads it back on each operation, so several inodes for the same file
+//! never disagree about its size or first cluster. An inode whose entry
+//! was unlinked, or reused by another file, gets `NotFound`.
+
+mod bpb;
+mod dir;
+mod volume;
+
+pub use bpb::FatType;
+
+use self::bpb::{BiosParameterBlock, MEDIA_FIXED};
+use self::dir::*;
+use self::volume::FatVolume;
+use super::*;
+use crate::drivers::block::{BlockDevice, BLOCK_SIZE};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+pub struct FatFs {
+    volume: Arc<FatVolume>,
+}
+
+impl FatFs {
+    /// Open the FAT volume on `device`
+    pub fn new(device: Arc<dyn BlockDevice>) -> FsResult<Self> {
+        let volume = FatVolume::new(device)?;
+        info!("FAT: {:?}", volume);
+
+        Ok(Self {
+            volume: Arc::new(volume),
+        })
+    }
+
+    /// Make an empty FAT12 volume on `device`, whatever was on it is lost
+    pub fn format(device: &dyn BlockDevice, label: &str) -> FsResult {
+        let total = u32::try_from(device.block_count()).map_err(|_| FsError::NotSupported)?;
+        let bpb = BiosParameterBlock::fat12(total, label)?;
+        device.write_block(0, &bpb.to_sector())?;
+
+        // empty FATs and root directory
+        let zero = [0u8; BLOCK_SIZE];
+        for id in bpb.first_fat_sector()..bpb.first_data_sector() {
+            device.write_block(id as usize, &zero)?;
+        }
+
+        // entries 0 and 1 are reserved, the first holds the media byte
+        let mut first = [0u8; BLOCK_SIZE];
+        first[..3].copy_from_slice(&[MEDIA_FIXED, 0xFF, 0xFF]);
+        for fat in 0..bpb.fat_count as u32 {
+            let id = bpb.first_fat_sector() + fat * bpb.sectors_per_fat;
+            device.write_block(id as usize, &first)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn fat_type(&self) -> FatType {
+        self.volume.fat_type
+    }
+}
+
+impl FileSystem for FatFs {
+    fn name(&self) -> &str {
+        match self.volume.fat_type {
+            FatType::Fat12 => "fat12",
+            FatType::Fat16 => "fat16",
+            FatType::Fat32 => "fat32",
+        }
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(FatInode {
+            volume: self.volume.clone(),
+            pos: None,
+        })
+    }
+}
+
+/// Tells a file apart from a later one reusing its 8.3 entry
+///
+/// Not the first cluster, that changes when an empty file grows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct EntryId {
+    name: [u8; 11],
+    cdate: u16,
+    ctime: u16,
+}
+
+impl EntryId {
+    fn of(entry: &ShortEntry) -> Self {
+        Self {
+            name: entry.name,
+            cdate: entry.cdate,
+            ctime: entry.ctime,
+        }
+    }
+}
+
+pub struct FatInode {
+    volume: Arc<FatVolume>,
+    /// Byte offset of the 8.3 entry on the volume and the file it held
+    /// when looked up, `None` for the root
+    pos: Option<(u64, EntryId)>,
+}
+
+impl FatInode {
+    /// The entry at `pos`, `NotFound` if it is not our file any more
+    fn read_entry(&self, pos: u64, id: EntryId) -> FsResult<ShortEntry> {
+        let entry = self.volume.read_entry(pos)?;
+        if EntryId::of(&entry) != id {
+            return Err(FsError::NotFound);
+        }
+        Ok(entry)
+    }
+
+    fn entry(&self) -> FsResult<Option<ShortEntry>> {
+        self.pos.map(|(pos, id)| self.read_entry(pos, id)).transpose()
+    }
+
+    fn is_dir(&self) -> FsResult<bool> {
+        Ok(self.entry()?.map(|e| e.is_dir()).unwrap_or(true))
+    }
+
+    /// Where this directory keeps its entries
+    fn location(&self) -> FsResult<DirLocation> {
+        match self.entry()? {
+            Some(entry) if entry.is_dir() => Ok(DirLocation::Chain(entry.first_cluster)),
+            Some(_) => Err(FsError::NotADirectory),
+            None => Ok(match self.volume.fat_type {
+                FatType::Fat32 => DirLocation::Chain(self.volume.bpb.root_cluster),
+                _ => DirLocation::FixedRoot,
+            }),
+        }
+    }
+
+    /// Children of this directory, without `.` and `..`
+    fn items(&self) -> FsResult<Vec<DirItem>> {
+        let mut items = self.volume.dir_items(self.location()?)?;
+        items.retain(|item| !item.entry.is_dot());
+        Ok(items)
+    }
+
+    fn find(&self, name: &str) -> FsResult<DirItem> {
+        self.items()?
+            .into_iter()
+            .find(|item| item.name.eq_ignore_ascii_case(name))
+            .ok_or(FsError::NotFound)
+    }
+
+    fn child(&self, pos: u64, entry: &ShortEntry) -> Arc<dyn Inode> {
+        Arc::new(FatInode {
+            volume: self.volume.clone(),
+            pos: Some((pos, EntryId::of(entry))),
+        })
+    }
+
+    /// The regular file entry, or an error for directories
+    fn file_entry(&self) -> FsResult<(u64, ShortEntry)> {
+        let (pos, id) = self.pos.ok_or(FsError::IsADirectory)?;
+        let entry = self.read_entry(pos, id)?;
+        if entry.is_dir() {
+            return Err(FsError::IsADirectory);
+        }
+        Ok((pos, entry))
+    }
+
+    /// Entry of a new directory in `cluster` under this one, with its
+    /// `.` and `..` written
+    fn new_dir(&self, cluster: u32, location: DirLocation) -> FsResult<ShortEntry> {
+        let mut dot = ShortEntry::new(*b".          ", Attributes::DIRECTORY);
+        dot.first_cluster = cluster;
+
+        // `..` of a directory in the root points to cluster 0
+        let mut dotdot = ShortEntry::new(*b"..         ", Attributes::DIRECTORY);
+        dotdot.first_cluster = match location {
+            DirLocation::Chain(c) if self.pos.is_some() => c,
+            _ => 0,
+        };
+
+        let offset = self.volume.cluster_offset(cluster);
+        self.volume.write_entry(offset, &dot)?;
+        self.volume.write_entry(offset + DIR_ENTRY_SIZE as u64, &dotdot)?;
+
+        let mut entry = ShortEntry::new([b' '; 11], Attributes::DIRECTORY);
+        entry.first_cluster = cluster;
+        Ok(entry)
+    }
+
+    /// Make sure the chain of `entry` covers `size` bytes, returns the
+    /// chain and how many clusters it had before
+    fn grow_chain(&self, entry: &mut ShortEntry, size: u64) -> FsResult<(Vec<u32>, usize)> {
+        let mut chain = self.volume.chain(entry.first_cluster)?;
+        let old_len = chain.len();
+        let needed = size.div_ceil(self.volume.cluster_size() as u64) as usize;
+
+        while chain.len() < needed {
+            let cluster = self.volume.alloc_cluster(chain.last().copied())?;
+            chain.push(cluster);
+        }
+
+        if entry.first_cluster == 0 {
+            entry.first_cluster = chain.first().copied().unwrap_or(0);
+        }
+
+        Ok((chain, old_len))
+    }
+
+    /// Grow the file of `entry` to hold `size` bytes, everything after the
+    /// old end of file reads as zeros; the volume must be locked
+    fn grow_to(&self, entry: &mut ShortEntry, size: u64) -> FsResult<Vec<u32>> {
+        let old_size = entry.size as u64;
+        let (chain, old_len) = self.grow_chain(entry, size)?;
+
+        // new clusters come zeroed, only the tail of the old ones may
+        // still hold data from before a truncate
+        let end = size.min(old_len as u64 * self.volume.cluster_size() as u64);
+        if end > old_size {
+            self.volume.zero_chain(&chain, old_size, end - old_size)?;
+        }
+
+        Ok(chain)
+    }
+}
+
+impl Inode for FatInode {
+    fn metadata(&self) -> FsResult<Metadata> {
+        Ok(match (self.pos, self.entry()?) {
+            (Some((pos, _)), Some(entry)) => {
+                let kind = if entry.is_dir() {
+                    FileType::Directory
+                } else {
+                    FileType::File
+                };
+                let mut meta = Metadata::new(pos / DIR_ENTRY_SIZE as u64 + 2, kind, entry.size as u64);
+                meta.modified = entry.modified();
+                meta
+            }
+            _ => Metadata::new(1, FileType::Directory, 0),
+        })
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> FsResult<usize> {
+        let (_, entry) = self.file_entry()?;
+        let size = entry.size as u64;
+
+        if offset >= size {
+            return Ok(0);
+        }
+
+        let len = ((size - offset) as usize).min(buf.len());
+        let chain = self.volume.chain(entry.first_cluster)?;
+        self.volume.read_chain(&chain, offset, &mut buf[..len])?;
+
+        Ok(len)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> FsResult<usize> {
+        let _guard = self.volume.lock.lock();
+        let (pos, mut entry) = self.file_entry()?;
+
+        let end = offset + buf.len() as u64;
+        if end > u32::MAX as u64 {
+            return Err(FsError::NoSpace);
+        }
+
+        // a hole between the old end of file and `offset` reads as zeros
+        let new_size = end.max(entry.size as u64);
+        let chain = self.grow_to(&mut entry, new_size)?;
+
+        self.volume.write_chain(&chain, offset, buf)?;
+
+        entry.size = entry.size.max(end as u32);
+        entry.touch();
+        self.volume.write_entry(pos, &entry)?;
+
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, size: u64) -> FsResult {
+        let _guard = self.volume.lock.lock();
+        let (pos, mut entry) = self.file_entry()?;
+
+        if size > u32::MAX as u64 {
+            return Err(FsError::NoSpace);
+        }
+
+        if size > entry.size as u64 {
+            self.grow_to(&mut entry, size)?;
+        } else {
+            let chain = self.volume.chain(entry.first_cluster)?;
+            let keep = size.div_ceil(self.volume.cluster_size() as u64) as usize;
+
+            if keep == 0 {
+                if entry.first_cluster != 0 {
+                    self.volume.free_chain(entry.first_cluster)?;
+                }
+                entry.first_cluster = 0;
+            } else if keep < chain.len() {
+                self.volume.truncate_chain(chain[keep - 1])?;
+            }
+        }
+
+        entry.size = size as u32;
+        entry.touch();
+        self.volume.write_entry(pos, &entry)
+    }
+
+    fn lookup(&self, name: &str) -> FsResult<Arc<dyn Inode>> {
+        let item = self.find(name)?;
+        Ok(self.child(item.pos, &item.entry))
+    }
+
+    fn read_dir(&self) -> FsResult<Vec<DirEntry>> {
+        Ok(self
+            .items()?
+            .into_iter()
+            .map(|item| {
+                let kind = if item.entry.is_dir() {
+                    FileType::Directory
+                } else {
+                    FileType::File
+                };
+                DirEntry::new(&item.name, kind, item.entry.size as u64)
+            })
+            .collect())
+    }
+
+    fn create(&self, name: &str, kind: FileType) -> FsResult<Arc<dyn Inode>> {
+        let _guard = self.volume.lock.lock();
+
+        if !is_valid_name(name) {
+            return Err(FsError::InvalidPath);
+        }
+
+        let location = self.location()?;
+
+        match self.find(name) {
+            Ok(_) => return Err(FsError::AlreadyExists),
+            Err(FsError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+
+        let (entry, cluster) = match kind {
+            FileType::File => (Ok(ShortEntry::new([b' '; 11], Attributes::ARCHIVE)), None),
+            FileType::Directory => {
+                let cluster = self.volume.alloc_cluster(None)?;
+                (self.new_dir(cluster, location), Some(cluster))
+            }
+            _ => return Err(FsError::NotSupported),
+        };
+
+        // a directory that can't be linked in gives its cluster back
+        let pos = entry
+            .and_then(|entry| self.volume.insert_item(location, name, entry))
+            .inspect_err(|_| {
+                if let Some(cluster) = cluster {
+                    let _ = self.volume.free_chain(cluster);
+                }
+            })?;
+
+        // with the name it got
+        let entry = self.volume.read_entry(pos)?;
+        Ok(self.child(pos, &entry))
+    }
+
+    fn unlink(&self, name: &str) -> FsResult {
+        let _guard = self.volume.lock.lock();
+
+        let item = self.find(name)?;
+
+        if item.entry.is_dir() {
+            let children = self
+                .volume
+                .dir_items(DirLocation::Chain(item.entry.first_cluster))?;
+            if children.iter().any(|c| !c.entry.is_dot()) {
+                return Err(FsError::DirectoryNotEmpty);
+            }
+        }
+
+        self.volume.remove_item(&item)?;
+
+        if item.entry.first_cluster != 0 {
+            self.volume.free_chain(item.entry.first_cluster)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,358 @@
+//! Raw access to a FAT volume: sectors, the allocation table and clusters
+
+use super::bpb::*;
+use crate::drivers::block::{BlockDevice, BLOCK_SIZE};
+use crate::fs::{FsError, FsResult};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+pub struct FatVolume {
+    device: Arc<dyn BlockDevice>,
+    pub bpb: BiosParameterBlock,
+    pub fat_type: FatType,
+    cluster_size: usize,
+    /// Where to start looking for a free cluster
+    alloc_hint: Mutex<u32>,
+    /// Serializes operations that modify the volume
+    pub(super) lock: Mutex<()>,
+}
+
+impl FatVolume {
+    pub fn new(device: Arc<dyn BlockDevice>) -> FsResult<Self> {
+        let mut sector = [0u8; BLOCK_SIZE];
+        device.read_block(0, &mut sector)?;
+
+        let bpb = BiosParameterBlock::parse(&sector)?;
+
+        if bpb.total_sectors as usize > device.block_count() {
+            warn!(
+                "FAT: volume has {} sectors but device only {}",
+                bpb.total_sectors,
+                device.block_count()
+            );
+            return Err(FsError::Corrupted);
+        }
+
+        Ok(Self {
+            device,
+            fat_type: bpb.fat_type(),
+            cluster_size: bpb.cluster_size(),
+            alloc_hint: Mutex::new(2),
+            lock: Mutex::new(()),
+            bpb,
+        })
+    }
+
+    #[inline]
+    pub fn cluster_size(&self) -> usize {
+        self.cluster_size
+    }
+
+    /// Read bytes at an absolute offset on the volume
+    pub fn read_bytes(&self, offset: u64, buf: &mut [u8]) -> FsResult {
+        let mut block = [0u8; BLOCK_SIZE];
+        let mut done = 0;
+
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let id = (pos / BLOCK_SIZE as u64) as usize;
+            let inner = (pos % BLOCK_SIZE as u64) as usize;
+            let len = (BLOCK_SIZE - inner).min(buf.len() - done);
+
+            self.device.read_block(id, &mut block)?;
+            buf[done..done + len].copy_from_slice(&block[inner..inner + len]);
+
+            done += len;
+        }
+
+        Ok(())
+    }
+
+    /// Write bytes at an absolute offset on the volume
+    ///
+    /// Partially covered sectors are read back and merged first.
+    pub fn write_bytes(&self, offset: u64, buf: &[u8]) -> FsResult {
+        let mut block = [0u8; BLOCK_SIZE];
+        let mut done = 0;
+
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let id = (pos / BLOCK_SIZE as u64) as usize;
+            let inner = (pos % BLOCK_SIZE as u64) as usize;
+            let len = (BLOCK_SIZE - inner).min(buf.len() - done);
+
+            if len != BLOCK_SIZE {
+                self.device.read_block(id, &mut block)?;
+            }
+            block[inner..inner + len].copy_from_slice(&buf[done..done + len]);
+            self.device.write_block(id, &block)?;
+
+            done += len;
+        }
+
+        Ok(())
+    }
+
+    /// Byte offset of the fixed root directory of FAT12/16
+    pub fn root_dir_offset(&self) -> u64 {
+        self.bpb.first_root_dir_sector() as u64 * BLOCK_SIZE as u64
+    }
+
+    pub fn root_dir_size(&self) -> usize {
+        self.bpb.root_entries as usize * 32
+    }
+
+    /// Byte offset of the first byte of a data cluster
+    pub fn cluster_offset(&self, cluster: u32) -> u64 {
+        let sector = self.bpb.first_data_sector() as u64
+            + (cluster as u64 - 2) * self.bpb.sectors_per_cluster as u64;
+        sector * BLOCK_SIZE as u64
+    }
+
+    #[inline]
+    fn is_valid_cluster(&self, cluster: u32) -> bool {
+        cluster >= 2 && cluster < self.bpb.cluster_count() + 2
+    }
+
+    /// Byte offset of a cluster's entry in the `index`-th FAT
+    fn fat_entry_offset(&self, cluster: u32, index: u32) -> u64 {
+        let fat_start =
+            (self.bpb.first_fat_sector() + index * self.bpb.sectors_per_fat) as u64 * BLOCK_SIZE as u64;
+
+        fat_start
+            + match self.fat_type {
+                FatType::Fat12 => cluster as u64 + cluster as u64 / 2,
+                FatType::Fat16 => cluster as u64 * 2,
+                FatType::Fat32 => cluster as u64 * 4,
+            }
+    }
+
+    /// The end-of-chain marker written by this driver
+    fn end_of_chain(&self) -> u32 {
+        match self.fat_type {
+            FatType::Fat12 => 0xFFF,
+            FatType::Fat16 => 0xFFFF,
+            FatType::Fat32 => 0x0FFF_FFFF,
+        }
+    }
+
+    /// If a FAT entry marks the end of a chain
+    fn is_end_of_chain(&self, value: u32) -> bool {
+        match self.fat_type {
+            FatType::Fat12 => value >= 0xFF8,
+            FatType::Fat16 => value >= 0xFFF8,
+            FatType::Fat32 => value >= 0x0FFF_FFF8,
+        }
+    }
+
+    pub fn fat_entry(&self, cluster: u32) -> FsResult<u32> {
+        let offset = self.fat_entry_offset(cluster, 0);
+
+        Ok(match self.fat_type {
+            FatType::Fat12 => {
+                let mut buf = [0u8; 2];
+                self.read_bytes(offset, &mut buf)?;
+                let value = u16::from_le_bytes(buf) as u32;
+                if cluster & 1 == 1 {
+                    value >> 4
+                } else {
+                    value & 0xFFF
+                }
+            }
+            FatType::Fat16 => {
+                let mut buf = [0u8; 2];
+                self.read_bytes(offset, &mut buf)?;
+                u16::from_le_bytes(buf) as u32
+            }
+            FatType::Fat32 => {
+                let mut buf = [0u8; 4];
+                self.read_bytes(offset, &mut buf)?;
+                u32::from_le_bytes(buf) & 0x0FFF_FFFF
+            }
+        })
+    }
+
+    /// Update a cluster's entry in every copy of the FAT
+    pub fn set_fat_entry(&self, cluster: u32, value: u32) -> FsResult {
+        for index in 0..self.bpb.fat_count as u32 {
+            let offset = self.fat_entry_offset(cluster, index);
+
+            match self.fat_type {
+                FatType::Fat12 => {
+                    let mut buf = [0u8; 2];
+                    self.read_bytes(offset, &mut buf)?;
+                    let old = u16::from_le_bytes(buf);
+                    let new = if cluster & 1 == 1 {
+                        (old & 0x000F) | ((value as u16) << 4)
+                    } else {
+                        (old & 0xF000) | (value as u16 & 0x0FFF)
+                    };
+                    self.write_bytes(offset, &new.to_le_bytes())?;
+                }
+                FatType::Fat16 => {
+                    self.write_bytes(offset, &(value as u16).to_le_bytes())?;
+                }
+                FatType::Fat32 => {
+                    // the high 4 bits are reserved and must be preserved
+                    let mut buf = [0u8; 4];
+                    self.read_bytes(offset, &mut buf)?;
+                    let old = u32::from_le_bytes(buf);
+                    let new = (old & 0xF000_0000) | (value & 0x0FFF_FFFF);
+                    self.write_bytes(offset, &new.to_le_bytes())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Follow the chain one step, `None` at the end of the chain
+    pub fn next_cluster(&self, cluster: u32) -> FsResult<Option<u32>> {
+        let value = self.fat_entry(cluster)?;
+
+        if self.is_end_of_chain(value) {
+            Ok(None)
+        } else if self.is_valid_cluster(value) {
+            Ok(Some(value))
+        } else {
+            warn!("FAT: broken chain at cluster {:#x} -> {:#x}", cluster, value);
+            Err(FsError::Corrupted)
+        }
+    }
+
+    /// Collect the whole cluster chain starting at `first`
+    pub fn chain(&self, first: u32) -> FsResult<Vec<u32>> {
+        let mut chain = Vec::new();
+
+        if first == 0 {
+            return Ok(chain);
+        }
+
+        if !self.is_valid_cluster(first) {
+            return Err(FsError::Corrupted);
+        }
+
+        let mut current = Some(first);
+        while let Some(cluster) = current {
+            // a chain longer than the volume must contain a loop
+            if chain.len() > self.bpb.cluster_count() as usize {
+                return Err(FsError::Corrupted);
+            }
+            chain.push(cluster);
+            current = self.next_cluster(cluster)?;
+        }
+
+        Ok(chain)
+    }
+
+    /// Allocate a zeroed cluster and append it to the chain ending at `prev`
+    pub fn alloc_cluster(&self, prev: Option<u32>) -> FsResult<u32> {
+        let mut hint = self.alloc_hint.lock();
+        let count = self.bpb.cluster_count();
+
+        let mut found = None;
+        for i in 0..count {
+            let cluster = (*hint - 2 + i) % count + 2;
+            if self.fat_entry(cluster)? == 0 {
+                found = Some(cluster);
+                break;
+            }
+        }
+
+        let cluster = found.ok_or(FsError::NoSpace)?;
+        *hint = cluster;
+
+        self.set_fat_entry(cluster, self.end_of_chain())?;
+        if let Some(prev) = prev {
+            self.set_fat_entry(prev, cluster)?;
+        }
+
+        self.write_bytes(self.cluster_offset(cluster), &vec![0u8; self.cluster_size])?;
+
+        Ok(cluster)
+    }
+
+    /// Release every cluster of the chain starting at `first`
+    pub fn free_chain(&self, first: u32) -> FsResult {
+        for cluster in self.chain(first)? {
+            self.set_fat_entry(cluster, 0)?;
+        }
+        Ok(())
+    }
+
+    /// Cut the chain after `last`, freeing the rest
+    pub fn truncate_chain(&self, last: u32) -> FsResult {
+        if let Some(next) = self.next_cluster(last)? {
+            self.free_chain(next)?;
+        }
+        self.set_fat_entry(last, self.end_of_chain())
+    }
+
+    /// Read from a file whose data lives in `chain`, starting at `offset`
+    pub fn read_chain(&self, chain: &[u32], offset: u64, buf: &mut [u8]) -> FsResult {
+        let cluster_size = self.cluster_size as u64;
+        let mut done = 0;
+
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let cluster = *chain
+                .get((pos / cluster_size) as usize)
+                .ok_or(FsError::Corrupted)?;
+            let inner = pos % cluster_size;
+            let len = ((cluster_size - inner) as usize).min(buf.len() - done);
+
+            self.read_bytes(self.cluster_offset(cluster) + inner, &mut buf[done..done + len])?;
+            done += len;
+        }
+
+        Ok(())
+    }
+
+    /// Write `len` zero bytes to the file of `chain` at `offset`, one
+    /// cluster at a time
+    pub fn zero_chain(&self, chain: &[u32], offset: u64, len: u64) -> FsResult {
+        let zeros = vec![0u8; (len as usize).min(self.cluster_size)];
+        let mut done = 0;
+
+        while done < len {
+            let count = (len - done).min(zeros.len() as u64) as usize;
+            self.write_chain(chain, offset + done, &zeros[..count])?;
+            done += count as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Write to a file whose data lives in `chain`, starting at `offset`
+    pub fn write_chain(&self, chain: &[u32], offset: u64, buf: &[u8]) -> FsResult {
+        let cluster_size = self.cluster_size as u64;
+        let mut done = 0;
+
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let cluster = *chain
+                .get((pos / cluster_size) as usize)
+                .ok_or(FsError::Corrupted)?;
+            let inner = pos % cluster_size;
+            let len = ((cluster_size - inner) as usize).min(buf.len() - done);
+
+            self.write_bytes(self.cluster_offset(cluster) + inner, &buf[done..done + len])?;
+            done += len;
+        }
+
+        Ok(())
+    }
+}
+
+impl core::fmt::Debug for FatVolume {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("FatVolume")
+            .field("type", &self.fat_type)
+            .field("label", &self.bpb.volume_label())
+            .field("clusters", &self.bpb.cluster_count())
+            .field("cluster_size", &self.cluster_size)
+            .finish()
+    }
+}
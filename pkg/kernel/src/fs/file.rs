@@ -0,0 +1,142 @@
+use super::*;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// An open file description held in a process's `ResourceSet`
+pub trait File: Send + Sync {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize>;
+
+    fn write(&mut self, buf: &[u8]) -> FsResult<usize>;
+
+    /// Move the file cursor, returns the new position
+    fn seek(&mut self, _offset: i64, _whence: Whence) -> FsResult<u64> {
+        Err(FsError::InvalidSeek)
+    }
+
+    fn stat(&self) -> FsResult<Metadata>;
+
+    /// The path this file was opened with, if it has one
+    fn path(&self) -> Option<&str> {
+        None
+    }
+
+    /// Return up to `max` directory entries following the previous call
+    fn read_dir(&mut self, _max: usize) -> FsResult<Vec<DirEntry>> {
+        Err(FsError::NotADirectory)
+    }
+
+    /// What `read` and `write` can do right now without waiting
+    fn poll(&self) -> PollEvents {
+        PollEvents::IN | PollEvents::OUT
+    }
+}
+
+impl core::fmt::Debug for dyn File {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self.stat() {
+            Ok(meta) => write!(f, "File({:?}, ino={})", meta.kind, meta.ino),
+            Err(_) => write!(f, "File(?)"),
+        }
+    }
+}
+
+/// A `File` backed by an inode and a cursor
+///
+/// For directories the cursor counts entries instead of bytes.
+pub struct InodeFile {
+    path: String,
+    inode: Arc<dyn Inode>,
+    flags: OpenFlags,
+    offset: u64,
+}
+
+impl InodeFile {
+    pub fn new(path: String, inode: Arc<dyn Inode>, flags: OpenFlags) -> Self {
+        Self {
+            path,
+            inode,
+            flags,
+            offset: 0,
+        }
+    }
+
+    pub fn inode(&self) -> &Arc<dyn Inode> {
+        &self.inode
+    }
+}
+
+impl File for InodeFile {
+    fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
+        if !self.flags.contains(OpenFlags::READ) {
+            return Err(FsError::PermissionDenied);
+        }
+
+        let (count, next) = self.inode.read_from(self.offset, buf)?;
+        self.offset = next;
+        Ok(count)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> FsResult<usize> {
+        if !self.flags.contains(OpenFlags::WRITE) {
+            return Err(FsError::PermissionDenied);
+        }
+
+        if self.flags.contains(OpenFlags::APPEND) {
+            self.offset = self.inode.metadata()?.size;
+        }
+
+        let count = self.inode.write_at(self.offset, buf)?;
+        self.offset += count as u64;
+        Ok(count)
+    }
+
+    fn seek(&mut self, offset: i64, whence: Whence) -> FsResult<u64> {
+        let base = match whence {
+            Whence::Set => 0,
+            Whence::Current => self.offset as i64,
+            Whence::End => self.inode.metadata()?.size as i64,
+        };
+
+        let pos = base.checked_add(offset).ok_or(FsError::InvalidSeek)?;
+        if pos < 0 {
+            return Err(FsError::InvalidSeek);
+        }
+
+        self.offset = pos as u64;
+        Ok(self.offset)
+    }
+
+    fn stat(&self) -> FsResult<Metadata> {
+        self.inode.metadata()
+    }
+
+    fn path(&self) -> Option<&str> {
+        Some(&self.path)
+    }
+
+    fn read_dir(&mut self, max: usize) -> FsResult<Vec<DirEntry>> {
+        let mut entries = self.inode.read_dir()?;
+        add_mount_points(&self.path, &mut entries);
+
+        let ret: Vec<DirEntry> = entries
+            .into_iter()
+            .skip(self.offset as usize)
+            .take(max)
+            .collect();
+
+        self.offset += ret.len() as u64;
+        Ok(ret)
+    }
+
+    fn poll(&self) -> PollEvents {
+        let mut events = self.inode.poll();
+        if !self.flags.contains(OpenFlags::READ) {
+            events.remove(PollEvents::IN);
+        }
+        if !self.flags.contains(OpenFlags::WRITE) {
+            events.remove(PollEvents::OUT);
+        }
+        events
+    }
+}
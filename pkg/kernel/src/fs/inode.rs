@@ -0,0 +1,137 @@
+use super::*;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use chrono::NaiveDateTime;
+
+/// Metadata of an inode
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    /// Filesystem specific inode number
+    pub ino: u64,
+    pub kind: FileType,
+    pub size: u64,
+    pub modified: Option<NaiveDateTime>,
+}
+
+impl Metadata {
+    pub fn new(ino: u64, kind: FileType, size: u64) -> Self {
+        Self {
+            ino,
+            kind,
+            size,
+            modified: None,
+        }
+    }
+
+    #[inline]
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileType::Directory
+    }
+
+    /// Convert to the structure returned by `FStat`
+    pub fn to_stat(&self) -> FileStat {
+        FileStat {
+            ino: self.ino,
+            size: self.size,
+            modified: self
+                .modified
+                .map(|t| t.and_utc().timestamp())
+                .unwrap_or_default(),
+            kind: self.kind,
+        }
+    }
+}
+
+/// An entry of a directory listing
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    pub name: String,
+    pub kind: FileType,
+    pub size: u64,
+}
+
+impl DirEntry {
+    pub fn new(name: &str, kind: FileType, size: u64) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            size,
+        }
+    }
+
+    /// Convert to the record written by `Getdents`
+    pub fn to_record(&self) -> syscall_def::fs::DirEntry {
+        syscall_def::fs::DirEntry::new(&self.name, self.kind, self.size)
+    }
+}
+
+/// A node in a filesystem: a regular file, a directory or a device
+///
+/// Methods take `&self` so that inodes can be shared through `Arc`;
+/// implementations use interior mutability where they need it.
+/// Everything a node does not support falls back to an error.
+pub trait Inode: Send + Sync {
+    fn metadata(&self) -> FsResult<Metadata>;
+
+    /// Read from `offset` into `buf`, returns the number of bytes read
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> FsResult<usize> {
+        Err(FsError::NotSupported)
+    }
+
+    /// `read_at`, also returning where the next read starts
+    ///
+    /// Streams that drop old data (`/dev/kmsg`) may skip ahead of `offset`.
+    fn read_from(&self, offset: u64, buf: &mut [u8]) -> FsResult<(usize, u64)> {
+        let count = self.read_at(offset, buf)?;
+        Ok((count, offset + count as u64))
+    }
+
+    /// Write `buf` at `offset`, growing the file if needed
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> FsResult<usize> {
+        Err(FsError::NotSupported)
+    }
+
+    /// Change the size of a regular file
+    fn truncate(&self, _size: u64) -> FsResult {
+        Err(FsError::NotSupported)
+    }
+
+    /// Find the child `name` of a directory
+    fn lookup(&self, _name: &str) -> FsResult<Arc<dyn Inode>> {
+        Err(FsError::NotADirectory)
+    }
+
+    /// List all children of a directory, without `.` and `..`
+    fn read_dir(&self) -> FsResult<Vec<DirEntry>> {
+        Err(FsError::NotADirectory)
+    }
+
+    /// Create a child `name` of the given type in a directory
+    fn create(&self, _name: &str, _kind: FileType) -> FsResult<Arc<dyn Inode>> {
+        Err(FsError::NotSupported)
+    }
+
+    /// Remove the child `name` from a directory
+    fn unlink(&self, _name: &str) -> FsResult {
+        Err(FsError::NotSupported)
+    }
+
+    /// Readiness for `Poll`, files on disk never make anyone wait
+    fn poll(&self) -> PollEvents {
+        PollEvents::IN | PollEvents::OUT
+    }
+}
+
+/// A mountable filesystem
+pub trait FileSystem: Send + Sync {
+    /// Short name of the filesystem type, e.g. `fat`
+    fn name(&self) -> &str;
+
+    fn root(&self) -> Arc<dyn Inode>;
+
+    /// Write back any cached state
+    fn sync(&self) -> FsResult {
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+//! Virtual filesystem
+//!
+//! Filesystems implement [`FileSystem`] and [`Inode`] and are attached to
+//! the global mount table. Paths are resolved against the caller's working
+//! directory, routed to the deepest mount point, then walked inode by inode.
+//! Opened paths become [`File`]s that live in a process's `ResourceSet`.
+
+pub mod devfs;
+mod error;
+pub mod fat;
+mod file;
+mod inode;
+mod mount;
+pub mod path;
+pub mod procfs;
+pub mod tmpfs;
+
+pub use error::*;
+pub use file::*;
+pub use inode::*;
+pub use mount::*;
+
+pub use syscall_def::fs::{FileStat, FileType, OpenFlags, PollEvents, Whence};
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// Size of the ramdisk used as "/" when there is no disk, 512 KiB
+const RAMDISK_BLOCKS: usize = 1024;
+
+pub fn init() {
+    if let Err(e) = mount_root() {
+        warn!("No root filesystem: {:?}, using a ramdisk", e);
+        if let Err(e) = mount_ramdisk_root() {
+            warn!("Failed to mount a ramdisk at /: {:?}", e);
+        }
+    }
+
+    if let Err(e) = mount("/tmp", Arc::new(tmpfs::TmpFs::new())) {
+        warn!("Failed to mount tmpfs at /tmp: {:?}", e);
+    }
+
+    if let Err(e) = mount("/proc", Arc::new(procfs::ProcFs)) {
+        warn!("Failed to mount procfs at /proc: {:?}", e);
+    }
+
+    if let Err(e) = mount("/dev", Arc::new(devfs::DevFs)) {
+        warn!("Failed to mount devfs at /dev: {:?}", e);
+    }
+
+    info!("VFS Initialized.");
+}
+
+/// Mount the first FAT volume found on the ATA drives at "/"
+///
+/// Each drive is checked for a FAT partition in its MBR, then as a
+/// whole-disk (partitionless) volume.
+fn mount_root() -> FsResult {
+    use crate::drivers::ata;
+    use crate::drivers::block::{mbr_partitions, BlockDevice};
+
+    for drive in ata::drives() {
+        let disk: Arc<dyn BlockDevice> = drive.clone();
+
+        let partitions = match mbr_partitions(&disk) {
+            Ok(partitions) => partitions,
+            Err(e) => {
+                warn!("Failed to read the MBR of ata{}: {:?}", drive.index(), e);
+                continue;
+            }
+        };
+
+        let mut candidates: Vec<Arc<dyn BlockDevice>> = partitions
+            .into_iter()
+            .filter(|p| p.is_fat())
+            .map(|p| Arc::new(p) as Arc<dyn BlockDevice>)
+            .collect();
+        candidates.push(disk);
+
+        for device in candidates {
+            if let Ok(fs) = fat::FatFs::new(device) {
+                info!("Root filesystem found on ata{}", drive.index());
+                return mount("/", Arc::new(fs));
+            }
+        }
+    }
+
+    Err(FsError::NotFound)
+}
+
+/// Mount an empty FAT volume in memory at "/", nothing on it survives
+/// a reboot
+fn mount_ramdisk_root() -> FsResult {
+    use crate::drivers::ramdisk::RamDisk;
+
+    let disk = Arc::new(RamDisk::new(RAMDISK_BLOCKS));
+    fat::FatFs::format(&*disk, "RAMDISK")?;
+    mount("/", Arc::new(fat::FatFs::new(disk)?))
+}
+
+/// Look up the inode at a normalized absolute path
+pub fn lookup(path: &str) -> FsResult<Arc<dyn Inode>> {
+    let (fs, rest) = get_mount_table()
+        .read()
+        .resolve(path)
+        .ok_or(FsError::NotFound)?;
+
+    let mut inode = fs.root();
+    for name in path::components(rest) {
+        inode = inode.lookup(name)?;
+    }
+
+    Ok(inode)
+}
+
+/// Open `path` relative to `cwd`
+pub fn open(cwd: &str, path: &str, flags: OpenFlags) -> FsResult<Box<dyn File>> {
+    let path = path::normalize(cwd, path);
+
+    let inode = match lookup(&path) {
+        Ok(inode) => inode,
+        Err(FsError::NotFound) if flags.contains(OpenFlags::CREATE) => {
+            let (parent, name) = path::split_parent(&path);
+            if name.is_empty() {
+                return Err(FsError::InvalidPath);
+            }
+            lookup(parent)?.create(name, FileType::File)?
+        }
+        Err(e) => return Err(e),
+    };
+
+    let meta = inode.metadata()?;
+
+    if meta.is_dir() && flags.contains(OpenFlags::WRITE) {
+        return Err(FsError::IsADirectory);
+    }
+
+    if !meta.is_dir() && flags.contains(OpenFlags::DIRECTORY) {
+        return Err(FsError::NotADirectory);
+    }
+
+    if flags.contains(OpenFlags::TRUNCATE) && flags.contains(OpenFlags::WRITE) {
+        inode.truncate(0)?;
+    }
+
+    trace!("Open {} with {:?}", path, flags);
+
+    Ok(Box::new(InodeFile::new(path, inode, flags)))
+}
+
+/// Metadata of the file at `path` relative to `cwd`
+pub fn stat(cwd: &str, path: &str) -> FsResult<Metadata> {
+    lookup(&path::normalize(cwd, path))?.metadata()
+}
+
+/// List a directory, including filesystems mounted directly below it
+pub fn read_dir(cwd: &str, path: &str) -> FsResult<Vec<DirEntry>> {
+    let path = path::normalize(cwd, path);
+    let mut entries = lookup(&path)?.read_dir()?;
+    add_mount_points(&path, &mut entries);
+    Ok(entries)
+}
+
+/// Show mount points below `path` as directories, even if the
+/// underlying filesystem has no such entry
+fn add_mount_points(path: &str, entries: &mut Vec<DirEntry>) {
+    for name in get_mount_table().read().children_of(path) {
+        if !entries.iter().any(|e| e.name == name) {
+            entries.push(DirEntry::new(&name, FileType::Directory, 0));
+        }
+    }
+}
+
+/// Resolve `path` against `cwd`, making sure it names a directory
+pub fn canonical_dir(cwd: &str, path: &str) -> FsResult<String> {
+    let path = path::normalize(cwd, path);
+    if lookup(&path)?.metadata()?.is_dir() {
+        Ok(path)
+    } else {
+        Err(FsError::NotADirectory)
+    }
+}
+
+/// Read a whole file into memory
+pub fn read_all(path: &str) -> FsResult<Vec<u8>> {
+    let inode = lookup(&path::normalize("/", path))?;
+    let size = inode.metadata()?.size as usize;
+
+    let mut buf = alloc::vec![0u8; size];
+    let mut read = 0;
+    while read < size {
+        let count = inode.read_at(read as u64, &mut buf[read..])?;
+        if count == 0 {
+            break;
+        }
+        read += count;
+    }
+    buf.truncate(read);
+
+    Ok(buf)
+}
@@ -0,0 +1,77 @@
+use super::*;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::RwLock;
+
+lazy_static! {
+    static ref MOUNT_TABLE: RwLock<MountTable> = RwLock::new(MountTable::default());
+}
+
+/// Filesystems keyed by the absolute path they are mounted on
+#[derive(Default)]
+pub struct MountTable {
+    mounts: BTreeMap<String, Arc<dyn FileSystem>>,
+}
+
+impl MountTable {
+    pub fn mount(&mut self, path: &str, fs: Arc<dyn FileSystem>) -> FsResult {
+        let path = path::normalize("/", path);
+        if self.mounts.contains_key(&path) {
+            return Err(FsError::AlreadyExists);
+        }
+
+        info!("Mount {} at {}", fs.name(), path);
+        self.mounts.insert(path, fs);
+        Ok(())
+    }
+
+    pub fn umount(&mut self, path: &str) -> FsResult<Arc<dyn FileSystem>> {
+        let path = path::normalize("/", path);
+        let fs = self.mounts.remove(&path).ok_or(FsError::NotFound)?;
+        fs.sync()?;
+        Ok(fs)
+    }
+
+    /// Find the filesystem a normalized path lives on
+    ///
+    /// Returns the filesystem and the path relative to its root.
+    /// The deepest mount point wins, so `/dev/tty` goes to the
+    /// filesystem on `/dev` rather than the one on `/`.
+    pub fn resolve<'a>(&self, path: &'a str) -> Option<(Arc<dyn FileSystem>, &'a str)> {
+        self.mounts
+            .iter()
+            .filter_map(|(mount_point, fs)| {
+                path::strip_mount_point(path, mount_point).map(|rest| (mount_point, fs, rest))
+            })
+            .max_by_key(|(mount_point, _, _)| mount_point.len())
+            .map(|(_, fs, rest)| (fs.clone(), rest))
+    }
+
+    /// Mount points directly below the directory `path`
+    pub fn children_of(&self, path: &str) -> Vec<String> {
+        self.mounts
+            .keys()
+            .filter(|mount_point| mount_point.as_str() != "/")
+            .filter(|mount_point| path::split_parent(mount_point).0 == path)
+            .map(|mount_point| String::from(path::split_parent(mount_point).1))
+            .collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Arc<dyn FileSystem>)> {
+        self.mounts.iter()
+    }
+}
+
+pub fn get_mount_table() -> &'static RwLock<MountTable> {
+    &MOUNT_TABLE
+}
+
+pub fn mount(path: &str, fs: Arc<dyn FileSystem>) -> FsResult {
+    MOUNT_TABLE.write().mount(path, fs)
+}
+
+pub fn umount(path: &str) -> FsResult<Arc<dyn FileSystem>> {
+    MOUNT_TABLE.write().umount(path)
+}
@@ -0,0 +1,81 @@
+//! Path helpers
+//!
+//! All paths handed to the mount table are absolute and normalized:
+//! they start with `/`, contain no `.`, `..` or empty components,
+//! and have no trailing `/` (except the root itself).
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub const SEPARATOR: char = '/';
+
+/// Resolve `path` against `cwd` into an absolute, normalized path
+///
+/// `..` at the root stays at the root, like on unix.
+pub fn normalize(cwd: &str, path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+
+    let full = if path.starts_with(SEPARATOR) {
+        [path, ""]
+    } else {
+        [cwd, path]
+    };
+
+    for part in full.iter().flat_map(|p| p.split(SEPARATOR)) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+
+    join(&parts)
+}
+
+/// Build an absolute path from its components
+pub fn join(parts: &[&str]) -> String {
+    if parts.is_empty() {
+        return String::from("/");
+    }
+
+    let mut ret = String::new();
+    for part in parts {
+        ret.push(SEPARATOR);
+        ret.push_str(part);
+    }
+    ret
+}
+
+/// Split a normalized path into its parent and the last component
+///
+/// `/a/b/c` -> (`/a/b`, `c`), `/a` -> (`/`, `a`), `/` -> (`/`, ``)
+pub fn split_parent(path: &str) -> (&str, &str) {
+    match path.rfind(SEPARATOR) {
+        Some(0) => ("/", &path[1..]),
+        Some(idx) => (&path[..idx], &path[idx + 1..]),
+        None => ("/", path),
+    }
+}
+
+/// Iterate over the components of a normalized path
+pub fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split(SEPARATOR).filter(|p| !p.is_empty())
+}
+
+/// Strip the mount point `prefix` from `path` if it is one of its ancestors
+///
+/// Returns the remaining part of the path relative to the mount point.
+pub fn strip_mount_point<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    if prefix == "/" {
+        return Some(path);
+    }
+
+    let rest = path.strip_prefix(prefix)?;
+    if rest.is_empty() || rest.starts_with(SEPARATOR) {
+        Some(rest)
+    } else {
+        None
+    }
+}
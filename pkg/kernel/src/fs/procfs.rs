@@ -0,0 +1,313 @@
+//! Process and kernel state as files
+//!
+//! ```text
+//! /proc/meminfo  uptime  cpuinfo  apps  idle
+//! /proc/<pid>/   status  name  parent  ticks  memory  fds  environ
+//! /proc/self  -> the calling process
+//! ```
+//!
+//! Nothing is stored: every read generates the text from the current
+//! state of the `ProcessManager` and the allocators.
+
+use super::*;
+use crate::proc::{self, ProcessId};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::ToString;
+use core::fmt::Write;
+
+type Generator = Box<dyn Fn() -> Option<String> + Send + Sync>;
+
+const GLOBAL_FILES: [&str; 5] = ["meminfo", "uptime", "cpuinfo", "apps", "idle"];
+const PID_FILES: [&str; 7] = [
+    "status", "name", "parent", "ticks", "memory", "fds", "environ",
+];
+
+pub struct ProcFs;
+
+impl FileSystem for ProcFs {
+    fn name(&self) -> &str {
+        "procfs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(ProcRoot)
+    }
+}
+
+struct ProcRoot;
+
+impl Inode for ProcRoot {
+    fn metadata(&self) -> FsResult<Metadata> {
+        Ok(Metadata::new(1, FileType::Directory, 0))
+    }
+
+    fn lookup(&self, name: &str) -> FsResult<Arc<dyn Inode>> {
+        if let Some(idx) = GLOBAL_FILES.iter().position(|&f| f == name) {
+            return Ok(Arc::new(ProcFile::new(idx as u64 + 2, global_file(idx))));
+        }
+
+        let pid = match name {
+            "self" => proc::processor::get_pid(),
+            _ => ProcessId(name.parse().map_err(|_| FsError::NotFound)?),
+        };
+        proc::process_info(pid).ok_or(FsError::NotFound)?;
+
+        Ok(Arc::new(ProcPidDir { pid }))
+    }
+
+    fn read_dir(&self) -> FsResult<Vec<DirEntry>> {
+        let mut entries = GLOBAL_FILES
+            .iter()
+            .map(|name| DirEntry::new(name, FileType::File, 0))
+            .collect::<Vec<_>>();
+
+        entries.push(DirEntry::new("self", FileType::Directory, 0));
+        entries.extend(
+            proc::process_list()
+                .iter()
+                .map(|p| DirEntry::new(&p.pid.0.to_string(), FileType::Directory, 0)),
+        );
+
+        Ok(entries)
+    }
+}
+
+struct ProcPidDir {
+    pid: ProcessId,
+}
+
+impl Inode for ProcPidDir {
+    fn metadata(&self) -> FsResult<Metadata> {
+        Ok(Metadata::new(
+            (self.pid.0 as u64) << 16,
+            FileType::Directory,
+            0,
+        ))
+    }
+
+    fn lookup(&self, name: &str) -> FsResult<Arc<dyn Inode>> {
+        let idx = PID_FILES
+            .iter()
+            .position(|&f| f == name)
+            .ok_or(FsError::NotFound)?;
+
+        let ino = ((self.pid.0 as u64) << 16) | (idx as u64 + 1);
+        Ok(Arc::new(ProcFile::new(ino, pid_file(self.pid, idx))))
+    }
+
+    fn read_dir(&self) -> FsResult<Vec<DirEntry>> {
+        proc::process_info(self.pid).ok_or(FsError::NotFound)?;
+
+        Ok(PID_FILES
+            .iter()
+            .map(|name| DirEntry::new(name, FileType::File, 0))
+            .collect())
+    }
+}
+
+/// A read-only file whose content is generated on every access
+struct ProcFile {
+    ino: u64,
+    generate: Generator,
+}
+
+impl ProcFile {
+    fn new(ino: u64, generate: Generator) -> Self {
+        Self { ino, generate }
+    }
+
+    fn content(&self) -> FsResult<String> {
+        (self.generate)().ok_or(FsError::NotFound)
+    }
+}
+
+impl Inode for ProcFile {
+    fn metadata(&self) -> FsResult<Metadata> {
+        let size = self.content()?.len() as u64;
+        Ok(Metadata::new(self.ino, FileType::File, size))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> FsResult<usize> {
+        let content = self.content()?;
+        let bytes = content.as_bytes();
+
+        let start = (offset as usize).min(bytes.len());
+        let count = buf.len().min(bytes.len() - start);
+        buf[..count].copy_from_slice(&bytes[start..start + count]);
+        Ok(count)
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> FsResult<usize> {
+        Err(FsError::PermissionDenied)
+    }
+}
+
+fn global_file(idx: usize) -> Generator {
+    match GLOBAL_FILES[idx] {
+        "meminfo" => Box::new(|| Some(meminfo())),
+        "uptime" => Box::new(|| {
+            let uptime = crate::clock::uptime();
+            Some(format!(
+                "{}.{:03}\n",
+                uptime.num_seconds(),
+                uptime.num_milliseconds() % 1000
+            ))
+        }),
+        "cpuinfo" => Box::new(|| Some(cpuinfo())),
+        "apps" => Box::new(|| Some(apps())),
+        "idle" => Box::new(|| Some(idle())),
+        _ => unreachable!(),
+    }
+}
+
+fn pid_file(pid: ProcessId, idx: usize) -> Generator {
+    match PID_FILES[idx] {
+        "status" => Box::new(move || {
+            let info = proc::process_info(pid)?;
+            Some(format!(
+                "Name:\t{}\nPid:\t{}\nPPid:\t{}\nState:\t{:?}\nTicks:\t{}\nMemory:\t{}\nExit:\t{}\n",
+                info.name,
+                info.pid,
+                info.ppid.map(|p| p.0).unwrap_or(0),
+                info.status,
+                info.ticks,
+                info.memory,
+                info.exit_code.unwrap_or(0)
+            ))
+        }),
+        "name" => Box::new(move || Some(format!("{}\n", proc::process_info(pid)?.name))),
+        "parent" => Box::new(move || {
+            let ppid = proc::process_info(pid)?.ppid.map(|p| p.0).unwrap_or(0);
+            Some(format!("{}\n", ppid))
+        }),
+        "ticks" => Box::new(move || Some(format!("{}\n", proc::process_info(pid)?.ticks))),
+        "memory" => Box::new(move || Some(format!("{}\n", proc::process_info(pid)?.memory))),
+        "fds" => Box::new(move || {
+            let fds = proc::with_process_data(pid, |data| data.fd_list())?;
+            let mut output = String::new();
+            for (fd, desc) in fds {
+                let _ = writeln!(output, "{}\t{}", fd, desc);
+            }
+            Some(output)
+        }),
+        "environ" => Box::new(move || {
+            let env = proc::with_process_data(pid, |data| data.env_list())?;
+            let mut output = String::new();
+            for (k, v) in env {
+                let _ = writeln!(output, "{}={}", k, v);
+            }
+            Some(output)
+        }),
+        _ => unreachable!(),
+    }
+}
+
+fn meminfo() -> String {
+    use crate::memory::{allocator::ALLOCATOR, get_frame_alloc_for_sure, PAGE_SIZE};
+
+    let (frames_used, frames_total) = x86_64::instructions::interrupts::without_interrupts(|| {
+        let alloc = get_frame_alloc_for_sure();
+        (alloc.frames_used(), alloc.frames_total())
+    });
+    let (heap_used, heap_size) = x86_64::instructions::interrupts::without_interrupts(|| {
+        let heap = ALLOCATOR.lock();
+        (heap.used(), heap.size())
+    });
+
+    let kib = |bytes: usize| bytes / 1024;
+    let page = PAGE_SIZE as usize;
+
+    format!(
+        "MemTotal:\t{} kB\nMemUsed:\t{} kB\nMemFree:\t{} kB\nFramesTotal:\t{}\nFramesUsed:\t{}\n\
+         HeapTotal:\t{} kB\nHeapUsed:\t{} kB\nHeapFree:\t{} kB\n",
+        kib(frames_total * page),
+        kib(frames_used * page),
+        kib(frames_total.saturating_sub(frames_used) * page),
+        frames_total,
+        frames_used,
+        kib(heap_size),
+        kib(heap_used),
+        kib(heap_size - heap_used),
+    )
+}
+
+fn cpuinfo() -> String {
+    use x86::cpuid::CpuId;
+
+    let cpuid = CpuId::new();
+    let vendor = cpuid
+        .get_vendor_info()
+        .map(|v| v.as_str().to_string())
+        .unwrap_or_default();
+    let brand = cpuid
+        .get_processor_brand_string()
+        .map(|b| b.as_str().trim().to_string())
+        .unwrap_or_default();
+
+    let mut output = format!("vendor_id\t: {}\nmodel name\t: {}\n", vendor, brand);
+
+    if let Some(info) = cpuid.get_feature_info() {
+        output += &format!(
+            "cpu family\t: {}\nmodel\t\t: {}\nstepping\t: {}\napicid\t\t: {}\n",
+            info.family_id(),
+            info.model_id(),
+            info.stepping_id(),
+            info.initial_local_apic_id()
+        );
+
+        let flags = [
+            ("fpu", info.has_fpu()),
+            ("tsc", info.has_tsc()),
+            ("msr", info.has_msr()),
+            ("apic", info.has_apic()),
+            ("sse", info.has_sse()),
+            ("sse2", info.has_sse2()),
+            ("sse3", info.has_sse3()),
+            ("x2apic", info.has_x2apic()),
+            ("tsc_deadline", info.has_tsc_deadline()),
+        ];
+        let flags = flags
+            .iter()
+            .filter(|(_, has)| *has)
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(" ");
+        output += &format!("flags\t\t: {}\n", flags);
+    }
+
+    output
+}
+
+fn apps() -> String {
+    let mut output = String::new();
+    for app in proc::app_records().iter() {
+        let _ = writeln!(
+            output,
+            "{}\t{}\t{:#x}\t{}\t{}",
+            app.name(),
+            app.size,
+            app.entry,
+            app.segments,
+            app.memory
+        );
+    }
+    output
+}
+
+/// Seconds each online CPU has spent in its idle process
+fn idle() -> String {
+    let now = crate::clock::monotonic_ns();
+    let mut output = String::new();
+    for (cpu, p) in proc::processor::online() {
+        let idle = p.idle_time(now);
+        let _ = writeln!(
+            output,
+            "cpu{}\t{}.{:03}",
+            cpu,
+            idle / 1_000_000_000,
+            idle / 1_000_000 % 1000
+        );
+    }
+    output
+}
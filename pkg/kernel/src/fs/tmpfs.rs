@@ -0,0 +1,209 @@
+//! In-memory filesystem
+//!
+//! Files and directories live in the kernel heap and are gone when the
+//! filesystem is unmounted (or the machine reboots).
+
+use super::*;
+use alloc::collections::BTreeMap;
+use chrono::NaiveDateTime;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::RwLock;
+
+pub struct TmpFs {
+    root: Arc<TmpInode>,
+}
+
+impl TmpFs {
+    pub fn new() -> Self {
+        let root = TmpInode {
+            ino: 1,
+            kind: FileType::Directory,
+            next_ino: Arc::new(AtomicU64::new(2)),
+            data: RwLock::new(TmpData::Dir(BTreeMap::new())),
+            modified: RwLock::new(None),
+        };
+
+        Self {
+            root: Arc::new(root),
+        }
+    }
+}
+
+impl Default for TmpFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileSystem for TmpFs {
+    fn name(&self) -> &str {
+        "tmpfs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
+
+enum TmpData {
+    File(Vec<u8>),
+    Dir(BTreeMap<String, Arc<TmpInode>>),
+}
+
+pub struct TmpInode {
+    ino: u64,
+    kind: FileType,
+    /// Shared by every inode of the filesystem
+    next_ino: Arc<AtomicU64>,
+    data: RwLock<TmpData>,
+    modified: RwLock<Option<NaiveDateTime>>,
+}
+
+impl TmpInode {
+    fn touch(&self) {
+        *self.modified.write() = crate::utils::clock::now();
+    }
+}
+
+impl Inode for TmpInode {
+    fn metadata(&self) -> FsResult<Metadata> {
+        let size = match &*self.data.read() {
+            TmpData::File(data) => data.len() as u64,
+            TmpData::Dir(_) => 0,
+        };
+
+        let mut meta = Metadata::new(self.ino, self.kind, size);
+        meta.modified = *self.modified.read();
+        Ok(meta)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> FsResult<usize> {
+        match &*self.data.read() {
+            TmpData::File(data) => {
+                let start = (offset as usize).min(data.len());
+                let count = buf.len().min(data.len() - start);
+                buf[..count].copy_from_slice(&data[start..start + count]);
+                Ok(count)
+            }
+            TmpData::Dir(_) => Err(FsError::IsADirectory),
+        }
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> FsResult<usize> {
+        match &mut *self.data.write() {
+            TmpData::File(data) => {
+                let end = usize::try_from(offset)
+                    .ok()
+                    .and_then(|start| start.checked_add(buf.len()))
+                    .ok_or(FsError::NoSpace)?;
+                let start = end - buf.len();
+                if end > data.len() {
+                    data.try_reserve(end - data.len())
+                        .map_err(|_| FsError::NoSpace)?;
+                    data.resize(end, 0);
+                }
+                data[start..end].copy_from_slice(buf);
+            }
+            TmpData::Dir(_) => return Err(FsError::IsADirectory),
+        }
+
+        self.touch();
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, size: u64) -> FsResult {
+        match &mut *self.data.write() {
+            TmpData::File(data) => {
+                let size = size as usize;
+                if size > data.len() {
+                    data.try_reserve(size - data.len())
+                        .map_err(|_| FsError::NoSpace)?;
+                }
+                data.resize(size, 0);
+            }
+            TmpData::Dir(_) => return Err(FsError::IsADirectory),
+        }
+
+        self.touch();
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> FsResult<Arc<dyn Inode>> {
+        match &*self.data.read() {
+            TmpData::Dir(children) => children
+                .get(name)
+                .map(|c| c.clone() as Arc<dyn Inode>)
+                .ok_or(FsError::NotFound),
+            TmpData::File(_) => Err(FsError::NotADirectory),
+        }
+    }
+
+    fn read_dir(&self) -> FsResult<Vec<DirEntry>> {
+        match &*self.data.read() {
+            TmpData::Dir(children) => Ok(children
+                .iter()
+                .map(|(name, child)| {
+                    let size = child.metadata().map(|m| m.size).unwrap_or(0);
+                    DirEntry::new(name, child.kind, size)
+                })
+                .collect()),
+            TmpData::File(_) => Err(FsError::NotADirectory),
+        }
+    }
+
+    fn create(&self, name: &str, kind: FileType) -> FsResult<Arc<dyn Inode>> {
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(FsError::InvalidPath);
+        }
+
+        let data = match kind {
+            FileType::File => TmpData::File(Vec::new()),
+            FileType::Directory => TmpData::Dir(BTreeMap::new()),
+            _ => return Err(FsError::NotSupported),
+        };
+
+        let mut guard = self.data.write();
+        let children = match &mut *guard {
+            TmpData::Dir(children) => children,
+            TmpData::File(_) => return Err(FsError::NotADirectory),
+        };
+
+        if children.contains_key(name) {
+            return Err(FsError::AlreadyExists);
+        }
+
+        let inode = Arc::new(TmpInode {
+            ino: self.next_ino.fetch_add(1, Ordering::Relaxed),
+            kind,
+            next_ino: self.next_ino.clone(),
+            data: RwLock::new(data),
+            modified: RwLock::new(crate::utils::clock::now()),
+        });
+        children.insert(name.into(), inode.clone());
+        drop(guard);
+
+        self.touch();
+        Ok(inode)
+    }
+
+    fn unlink(&self, name: &str) -> FsResult {
+        let mut guard = self.data.write();
+        let children = match &mut *guard {
+            TmpData::Dir(children) => children,
+            TmpData::File(_) => return Err(FsError::NotADirectory),
+        };
+
+        let child = children.get(name).ok_or(FsError::NotFound)?;
+        if let TmpData::Dir(grandchildren) = &*child.data.read() {
+            if !grandchildren.is_empty() {
+                return Err(FsError::DirectoryNotEmpty);
+            }
+        }
+
+        children.remove(name);
+        drop(guard);
+
+        self.touch();
+        Ok(())
+    }
+}
@@ -45,7 +45,7 @@ impl IoApic {
         // Mark all interrupts edge-triggered, active high, disabled,
         // and not routed to any CPUs.
         for i in 0..=self.maxintr() {
-            self.write_irq(i, RedirectionEntry::DISABLED, 0);
+            self.write_irq(i, 32 + i, RedirectionEntry::DISABLED, 0);
         }
     }
 
@@ -59,9 +59,9 @@ impl IoApic {
         self.data.write_volatile(data);
     }
 
-    fn write_irq(&mut self, irq: u8, flags: RedirectionEntry, dest: u8) {
+    fn write_irq(&mut self, irq: u8, vector: u8, flags: RedirectionEntry, dest: u8) {
         unsafe {
-            self.write(0x10 + 2 * irq, (32 + irq) as u32 | flags.bits());
+            self.write(0x10 + 2 * irq, vector as u32 | flags.bits());
             self.write(0x10 + 2 * irq + 1, (dest as u32) << 24);
         }
     }
@@ -70,12 +70,20 @@ impl IoApic {
         // Mark interrupt edge-triggered, active high,
         // enabled, and routed to the given cpuid,
         // which happens to be that cpu's APIC ID.
-        self.write_irq(irq, RedirectionEntry::NONE, cpuid);
-        trace!("Enable IOApic: IRQ={}, CPU={}", irq, cpuid);
+        self.route(irq, 32 + irq, false, false, cpuid);
+    }
+
+    /// Deliver input `pin` as `vector` to `cpuid`
+    pub fn route(&mut self, pin: u8, vector: u8, level: bool, active_low: bool, cpuid: u8) {
+        let mut flags = RedirectionEntry::NONE;
+        flags.set(RedirectionEntry::LEVEL, level);
+        flags.set(RedirectionEntry::ACTIVELOW, active_low);
+        self.write_irq(pin, vector, flags, cpuid);
+        trace!("Enable IOApic: PIN={}, VECTOR={}, CPU={}", pin, vector, cpuid);
     }
 
     pub fn disable(&mut self, irq: u8, cpuid: u8) {
-        self.write_irq(irq, RedirectionEntry::DISABLED, cpuid);
+        self.write_irq(irq, 32 + irq, RedirectionEntry::DISABLED, cpuid);
     }
 
     pub fn id(&mut self) -> u8 {
@@ -9,11 +9,29 @@
 //! Reference: [OSDev Wiki](https://wiki.osdev.org/APIC)
 
 pub use ioapic::{IoApic, IOAPIC_ADDR};
-pub use xapic::{XApic, LAPIC_ADDR};
+pub use xapic::{InterruptCommandFlags, XApic, LAPIC_ADDR};
 
 mod ioapic;
 mod xapic;
 
+use crate::memory::physical_to_virtual;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Physical address of the local APICs, from the MADT if there is one
+static LAPIC_BASE: AtomicU64 = AtomicU64::new(LAPIC_ADDR);
+
+/// Take the LAPIC address from the MADT
+pub fn init_addr() {
+    if let Some(madt) = crate::acpi::madt() {
+        LAPIC_BASE.store(madt.lapic_addr, Ordering::Relaxed);
+    }
+}
+
+/// Local APIC of the running CPU
+pub fn lapic() -> XApic {
+    unsafe { XApic::new(physical_to_virtual(LAPIC_BASE.load(Ordering::Relaxed))) }
+}
+
 pub trait LocalApic {
     /// If this type APIC is supported
     fn support() -> bool;
@@ -2,12 +2,31 @@ use super::LocalApic;
 use bit_field::BitField;
 use core::fmt::{Debug, Error, Formatter};
 use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use x86::cpuid::CpuId;
 use crate::interrupt::consts;
 
 /// Default physical address of xAPIC
 pub const LAPIC_ADDR: u64 = 0xFEE00000;
 
+/// Timer counts at bus clock / 16
+const TIMER_DIVIDE: u32 = 0b0011;
+
+/// How long the timer is measured against the PIT, in microseconds
+const CALIBRATE_US: u64 = 10_000;
+
+/// Initial count for one tick, set by `calibrate_timer`
+static TIMER_COUNT: AtomicU32 = AtomicU32::new(0x20000);
+
+/// Timer counts per second after the divider, set by `calibrate_timer`
+static TIMER_FREQUENCY: AtomicU64 = AtomicU64::new(0);
+
+/// If the timer has a TSC-deadline mode, set by `calibrate_timer`
+static TSC_DEADLINE: AtomicBool = AtomicBool::new(false);
+
+/// IA32_TSC_DEADLINE, the timer fires once the TSC reaches it
+const MSR_TSC_DEADLINE: u32 = 0x6E0;
+
 use bitflags::bitflags;
 
 bitflags! {
@@ -22,6 +41,7 @@ bitflags! {
     pub struct LvtTimerFlags: u32 {
         const MASKED                  = 1 << 16;
         const PERIODIC                = 1 << 17;
+        const TSC_DEADLINE            = 1 << 18;
     }
 }
 
@@ -47,6 +67,9 @@ bitflags! {
         const DELIVERY_MODE_FIXED     = 0b000 << 8;
         const DELIVERY_MODE_LOWEST    = 0b001 << 8;
         const DELIVERY_MODE_SMI       = 0b010 << 8;
+        const DELIVERY_MODE_INIT      = 0b101 << 8;
+        const DELIVERY_MODE_STARTUP   = 0b110 << 8;
+        const LEVEL_ASSERT            = 1 << 14;
         // 根据APIC文档，可以继续添加更多标志位
     }
 }
@@ -85,9 +108,83 @@ impl XApic {
     unsafe fn set_lvt_timer(&mut self, flags: LvtTimerFlags, vector: u8) {
         let mut lvt_timer: u32 = self.read(0x320);
         lvt_timer.set_bits(0..=7, vector as u32); // 设置Vector
-        lvt_timer.set_bits(16..=17, flags.bits() >> 16); // 应用标志位
+        lvt_timer.set_bits(16..=18, flags.bits() >> 16); // 应用标志位
         self.write(0x320, lvt_timer);
     }
+
+    /// Measure the timer against the PIT and work out the initial count
+    /// for `hz` interrupts per second
+    ///
+    /// All LAPICs run at the bus clock, so once on the BSP is enough.
+    pub fn calibrate_timer(&mut self, hz: u64) {
+        let vector = consts::Interrupts::IrqBase as u8 + consts::Irq::Timer as u8;
+        let elapsed = unsafe {
+            self.write(0x3E0, TIMER_DIVIDE);
+            // masked one shot, from the top
+            self.set_lvt_timer(LvtTimerFlags::MASKED, vector);
+            self.write(0x380, u32::MAX);
+
+            crate::pit::udelay(CALIBRATE_US);
+
+            let current = self.read(0x390);
+            self.write(0x380, 0);
+            u32::MAX - current
+        };
+
+        let frequency = elapsed as u64 * 1_000_000 / CALIBRATE_US;
+        let count = (frequency / hz).clamp(1, u32::MAX as u64) as u32;
+        TIMER_COUNT.store(count, Ordering::Relaxed);
+        TIMER_FREQUENCY.store(frequency, Ordering::Relaxed);
+
+        let deadline = CpuId::new()
+            .get_feature_info()
+            .is_some_and(|info| info.has_tsc_deadline());
+        TSC_DEADLINE.store(deadline, Ordering::Relaxed);
+
+        info!(
+            "LAPIC Timer      : {} kHz, {} Hz tick, initial count {:#x}",
+            frequency / 1000,
+            hz,
+            count
+        );
+    }
+
+    /// If the timer has a TSC-deadline mode
+    pub fn has_tsc_deadline() -> bool {
+        TSC_DEADLINE.load(Ordering::Relaxed)
+    }
+
+    /// Back to one interrupt per tick
+    pub fn set_timer_periodic(&mut self) {
+        let vector = consts::Interrupts::IrqBase as u8 + consts::Irq::Timer as u8;
+        unsafe {
+            self.set_lvt_timer(LvtTimerFlags::PERIODIC, vector);
+            self.write(0x380, TIMER_COUNT.load(Ordering::Relaxed));
+        }
+    }
+
+    /// A single interrupt in `ns` nanoseconds, as far as the counter
+    /// reaches if that is too long
+    pub fn set_timer_oneshot(&mut self, ns: u64) {
+        let vector = consts::Interrupts::IrqBase as u8 + consts::Irq::Timer as u8;
+        let frequency = TIMER_FREQUENCY.load(Ordering::Relaxed) as u128;
+        let count = (ns as u128 * frequency / 1_000_000_000).clamp(1, u32::MAX as u128) as u32;
+        unsafe {
+            self.set_lvt_timer(LvtTimerFlags::empty(), vector);
+            self.write(0x380, count);
+        }
+    }
+
+    /// A single interrupt when the TSC reaches `tsc`
+    pub fn set_timer_deadline(&mut self, tsc: u64) {
+        let vector = consts::Interrupts::IrqBase as u8 + consts::Irq::Timer as u8;
+        unsafe {
+            self.set_lvt_timer(LvtTimerFlags::TSC_DEADLINE, vector);
+            // the mode switch has to land before the deadline is written
+            core::arch::x86_64::_mm_mfence();
+            x86_64::registers::model_specific::Msr::new(MSR_TSC_DEADLINE).write(tsc.max(1));
+        }
+    }
 }
 
 impl LocalApic for XApic {
@@ -111,8 +208,8 @@ impl LocalApic for XApic {
 
             // FIXME: The timer repeatedly counts down at bus frequency
 
-            self.write(0x3E0, 0b1011); // set Timer Divide to 1
-            self.write(0x380, 0x20000); // set initial count to 0x20000
+            self.write(0x3E0, TIMER_DIVIDE); // set Timer Divide to 16
+            self.write(0x380, TIMER_COUNT.load(Ordering::Relaxed)); // calibrated count for one tick
 
             // let mut lvt_timer: u32 = self.read(0x320);
             // // clear and set Vector
@@ -21,18 +21,55 @@
 
 use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
 use crate::memory::gdt;
-use crate::proc::ProcessContext;
+use crate::proc::{processor, ProcessContext};
 
+use super::apic::{lapic, XApic};
 use super::consts::*;
 
+/// Longest an idle CPU sleeps without a deadline, in case a wakeup is lost
+const MAX_IDLE_NS: u64 = crate::clock::NANOS_PER_SEC;
+
 pub unsafe fn reg_idt(idt: &mut InterruptDescriptorTable) {
     idt[Interrupts::IrqBase as u8 + Irq::Timer as u8].set_handler_fn(teapot_handler).set_stack_index(gdt::CLOCK_INTERRUPT_INDX);
 }
 
 pub extern "C" fn teapot(mut context: ProcessContext) {
+    crate::clock::tick();
+    crate::proc::poll_tick();
     crate::proc::switch(&mut context);
     //info!("clock");
     super::ack();
 }
 
-as_handler!(teapot);
\ No newline at end of file
+as_handler!(teapot);
+
+/// Called on every switch: an idle CPU stops its periodic tick and only
+/// wakes up for the next deadline, a busy one gets the tick back
+///
+/// Anything else that makes work for an idle CPU comes with an interrupt
+/// of its own, input or a reschedule IPI.
+pub fn update_tick(idle: bool) {
+    let cpu = processor::current();
+    let now = crate::clock::monotonic_ns();
+
+    if !idle {
+        if cpu.leave_idle(now) && crate::clock::tickless() {
+            lapic().set_timer_periodic();
+        }
+        return;
+    }
+
+    cpu.enter_idle(now);
+    if !crate::clock::tickless() {
+        return;
+    }
+
+    let deadline = crate::proc::next_wakeup()
+        .unwrap_or(u64::MAX)
+        .min(now + MAX_IDLE_NS);
+    let mut lapic = lapic();
+    match crate::clock::tsc_at(deadline).filter(|_| XApic::has_tsc_deadline()) {
+        Some(tsc) => lapic.set_timer_deadline(tsc),
+        None => lapic.set_timer_oneshot(deadline.saturating_sub(now)),
+    }
+}
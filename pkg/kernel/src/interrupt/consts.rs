@@ -44,5 +44,7 @@ pub enum Irq {
     Ide0 = 14,
     Ide1 = 15,
     Error = 19,
+    Reschedule = 20, // IPI, not a device irq
+    TlbShootdown = 21, // IPI
     Spurious = 31,
 }
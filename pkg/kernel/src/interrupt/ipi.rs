@@ -0,0 +1,29 @@
+//! Inter-processor interrupts
+
+use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
+use crate::memory::gdt;
+use crate::proc::ProcessContext;
+
+use super::consts::*;
+
+pub unsafe fn register_idt(idt: &mut InterruptDescriptorTable) {
+    idt[Interrupts::IrqBase as u8 + Irq::Reschedule as u8]
+        .set_handler_fn(reschedule_handler)
+        .set_stack_index(gdt::CLOCK_INTERRUPT_INDX);
+    idt[Interrupts::IrqBase as u8 + Irq::TlbShootdown as u8]
+        .set_handler_fn(tlb_shootdown_handler);
+}
+
+/// Sent to an idle CPU when a process is queued on it
+pub extern "C" fn reschedule(mut context: ProcessContext) {
+    crate::proc::switch(&mut context);
+    super::ack();
+}
+
+as_handler!(reschedule);
+
+/// Sent by a CPU that unmapped pages before freeing their frames
+pub extern "x86-interrupt" fn tlb_shootdown_handler(_sf: InterruptStackFrame) {
+    crate::smp::tlb::flush();
+    super::ack();
+}
@@ -0,0 +1,16 @@
+use super::consts::*;
+use crate::interrupt::InterruptDescriptorTable;
+use x86_64::structures::idt::InterruptStackFrame;
+
+use crate::drivers::keyboard;
+
+pub unsafe fn register_idt(idt: &mut InterruptDescriptorTable) {
+    idt[Interrupts::IrqBase as u8 + Irq::Keyboard as u8].set_handler_fn(keyboard_handler);
+}
+
+pub extern "x86-interrupt" fn keyboard_handler(_st: InterruptStackFrame) {
+    keyboard::handle_irq();
+    // same as serial input, a poller on stdin can go on
+    crate::proc::poll_tick();
+    super::ack();
+}
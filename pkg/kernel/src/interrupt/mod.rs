@@ -2,8 +2,11 @@ mod apic;
 mod consts;
 pub mod clock;
 mod serial;
+mod keyboard;
+mod rtc;
 mod exceptions;
 mod syscall;
+mod ipi;
 
 use apic::*;
 use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
@@ -16,7 +19,10 @@ lazy_static! {
             exceptions::register_idt(&mut idt);
             clock::reg_idt(&mut idt);
             serial::register_idt(&mut idt);
+            keyboard::register_idt(&mut idt);
+            rtc::register_idt(&mut idt);
             syscall::register_idt(&mut idt);
+            ipi::register_idt(&mut idt);
             //info!("IDT loaded!");
         }
         idt
@@ -41,25 +47,100 @@ pub fn init() {
     IDT.load();
 
     // FIXME: check and init APIC
-    let mut Apic = unsafe{XApic::new(physical_to_virtual(LAPIC_ADDR))};
+    apic::init_addr();
+    let mut Apic = lapic();
     if XApic::support(){
+        Apic.calibrate_timer(crate::clock::hz());
         Apic.cpu_init();
     }
     
     // FIXME: enable serial irq with IO APIC (use enable_irq)
     enable_irq(consts::Irq::Serial0 as u8, 0);
+    enable_irq(consts::Irq::Keyboard as u8, 0);
+    enable_irq(consts::Irq::RealTimeClock as u8, 0);
 
     info!("Interrupts Initialized.");
 }
 
-#[inline(always)]
+/// Interrupts of an application processor, the IO APIC stays with the BSP
+pub fn init_ap() {
+    IDT.load();
+
+    let mut lapic = lapic();
+    lapic.cpu_init();
+}
+
+/// APIC id of the running CPU
+pub fn apic_id() -> u8 {
+    let lapic = lapic();
+    lapic.id() as u8
+}
+
+/// Send INIT-SIPI-SIPI to `apic_id`, it starts in real mode at `page` << 12
+pub fn start_ap(apic_id: u8, page: u8) {
+    let mut lapic = lapic();
+    let dest = (apic_id as u64) << 56;
+
+    lapic.set_icr(
+        dest | (InterruptCommandFlags::DELIVERY_MODE_INIT | InterruptCommandFlags::LEVEL_ASSERT)
+            .bits(),
+    );
+    crate::pit::udelay(10_000);
+
+    // the second SIPI is for CPUs that missed the first one
+    for _ in 0..2 {
+        lapic.set_icr(
+            dest | (InterruptCommandFlags::DELIVERY_MODE_STARTUP
+                | InterruptCommandFlags::LEVEL_ASSERT)
+                .bits()
+                | page as u64,
+        );
+        crate::pit::udelay(200);
+    }
+}
+
+/// Make `cpu` look at its run queue now
+pub fn send_reschedule(cpu: u8) {
+    send_ipi(cpu, consts::Irq::Reschedule);
+}
+
+/// Make `cpu` flush its TLB
+pub fn send_tlb_shootdown(cpu: u8) {
+    send_ipi(cpu, consts::Irq::TlbShootdown);
+}
+
+fn send_ipi(cpu: u8, irq: consts::Irq) {
+    let mut lapic = lapic();
+    let vector = consts::Interrupts::IrqBase as u64 + irq as u64;
+    lapic.set_icr(
+        (cpu as u64) << 56
+            | (InterruptCommandFlags::DELIVERY_MODE_FIXED | InterruptCommandFlags::LEVEL_ASSERT)
+                .bits()
+            | vector,
+    );
+}
+
+/// Enable ISA irq `irq` on `cpuid`, through the MADT overrides
 pub fn enable_irq(irq: u8, cpuid: u8) {
-    let mut ioapic = unsafe { IoApic::new(physical_to_virtual(IOAPIC_ADDR)) };
-    ioapic.enable(irq, cpuid);
+    let Some(madt) = crate::acpi::madt() else {
+        let mut ioapic = unsafe { IoApic::new(physical_to_virtual(IOAPIC_ADDR)) };
+        ioapic.enable(irq, cpuid);
+        return;
+    };
+
+    let route = madt.isa_irq(irq);
+    let Some(io) = madt.io_apic_for(route.gsi) else {
+        warn!("No IO APIC for irq {} (GSI {})", irq, route.gsi);
+        return;
+    };
+
+    let mut ioapic = unsafe { IoApic::new(physical_to_virtual(io.addr as u64)) };
+    let vector = consts::Interrupts::IrqBase as u8 + irq;
+    ioapic.route((route.gsi - io.gsi_base) as u8, vector, route.level, route.active_low, cpuid);
 }
 
 #[inline(always)]
 pub fn ack() {
-    let mut lapic = unsafe { XApic::new(physical_to_virtual(LAPIC_ADDR)) };
+    let mut lapic = lapic();
     lapic.eoi();
 }
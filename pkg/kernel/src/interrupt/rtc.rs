@@ -0,0 +1,14 @@
+use super::consts::*;
+use crate::interrupt::InterruptDescriptorTable;
+use x86_64::structures::idt::InterruptStackFrame;
+
+use crate::drivers::rtc;
+
+pub unsafe fn register_idt(idt: &mut InterruptDescriptorTable) {
+    idt[Interrupts::IrqBase as u8 + Irq::RealTimeClock as u8].set_handler_fn(rtc_handler);
+}
+
+pub extern "x86-interrupt" fn rtc_handler(_st: InterruptStackFrame) {
+    rtc::handle_irq();
+    super::ack();
+}
@@ -13,6 +13,8 @@ pub unsafe fn register_idt(idt: &mut InterruptDescriptorTable) {
 pub extern "x86-interrupt" fn serial_handler(_st: InterruptStackFrame) {
     //info!("serial_handler starts");
     receive();
+    // a poller on stdin can go on, an idle CPU has no tick to notice it
+    crate::proc::poll_tick();
     super::ack();  // 向局部APIC（高级可编程中断控制器）发送一个结束中断（EOI）信号，表示当前的中断处理程序已经完成处理，并且系统可以处理下一个中断
     //info!("serial_handler ends");
 }
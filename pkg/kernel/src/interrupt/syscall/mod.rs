@@ -59,6 +59,30 @@ pub fn dispatcher(context: &mut ProcessContext) {
         /* FIXME: write to fd & return length */
         Syscall::Write => {context.set_rax(sys_write(&args))},
 
+        // path: &str (ptr: arg0 as *const u8, len: arg1), flags: arg2 -> fd: isize
+        Syscall::Open => context.set_rax(sys_open(&args)),
+
+        // fd: arg0 as u8 -> ret: isize
+        Syscall::Close => context.set_rax(sys_close(&args)),
+
+        // path: &str (ptr: arg0 as *const u8, len: arg1), stat: arg2 as *mut FileStat -> ret: isize
+        Syscall::PathStat => context.set_rax(sys_stat(&args)),
+
+        // fd: arg0 as u8, stat: arg1 as *mut FileStat -> ret: isize
+        Syscall::FStat => context.set_rax(sys_fstat(&args)),
+
+        // fd: arg0 as u8, offset: arg1 as i64, whence: arg2 -> pos: isize
+        Syscall::Seek => context.set_rax(sys_seek(&args)),
+
+        // fd: arg0 as u8, buf: &mut [DirEntry] (ptr: arg1, len: arg2) -> count: isize
+        Syscall::Getdents => context.set_rax(sys_getdents(&args)),
+
+        // buf: &mut [u8] (ptr: arg0 as *mut u8, len: arg1) -> len: isize
+        Syscall::Getcwd => context.set_rax(sys_getcwd(&args)),
+
+        // path: &str (ptr: arg0 as *const u8, len: arg1) -> ret: isize
+        Syscall::Chdir => context.set_rax(sys_chdir(&args)),
+
         // None -> pid: u16
         /* FIXME: get current pid */
         Syscall::GetPid => {context.set_rax(u16::from(processor::get_pid()) as usize)}, // 把processor从private改成了public
@@ -78,22 +102,75 @@ pub fn dispatcher(context: &mut ProcessContext) {
         // None
          /* FIXME: list processes */
         Syscall::Stat => {list_process()},
+
+        // buf: arg0 as *mut ProcessRecord, count: arg1 -> total: usize
+        Syscall::ListProcess => context.set_rax(sys_list_process(&args)),
         
         // None
         /* FIXME: list avaliable apps */
         Syscall::ListApp => {list_app()},
 
+        // buf: arg0 as *mut AppRecord, count: arg1 -> total: usize
+        Syscall::ListAppInfo => context.set_rax(sys_list_app_info(&args)),
+
         // 加分项 None -> u64
         Syscall::Time => {context.set_rax(sys_clock() as usize)},
 
+        // clock: arg0 as usize, ts: arg1 as *mut TimeSpec -> 0 or usize::MAX
+        Syscall::ClockGettime => context.set_rax(sys_clock_gettime(&args)),
+
         // None -> pid: u16 or 0 or -1
         Syscall::Fork => {
             fork(context);
         },
 
-        // op: u8, key: u32, val: usize -> ret: any
+        // entry: arg0 as usize, arg: arg1 as usize -> tid: u16 or 0
+        Syscall::ThreadCreate => context.set_rax(sys_thread_create(&args)),
+
+        // tid: arg0 as u16 -> ret: isize
+        Syscall::ThreadJoin => thread_join(ProcessId(args.arg0 as u16), context),
+
+        // ret: arg0 as isize
+        Syscall::ThreadExit => thread_exit(args.arg0 as isize, context),
+
+        // code: arg0 as usize, addr: arg1 as u64 -> ret: isize
+        Syscall::ArchPrctl => context.set_rax(sys_arch_prctl(&args)),
+
+        // None -> !
+        Syscall::Reboot => sys_reboot(),
+
+        // addr: arg0 as *const u32, op: arg1, val: arg2 -> ret: isize
+        Syscall::Futex => sys_futex(&args, context),
+
+        // op: arg0 as usize, key: arg1 as u32, val: arg2 -> SEM_OK, SEM_NOT_FOUND or SEM_EXISTS
         Syscall::Sem => sys_sem(&args, context),
 
+        // pid: arg0 as u16 (0 for self), mask: arg1 as u64 -> 0 or usize::MAX
+        Syscall::SchedSetAffinity => context.set_rax(sys_set_affinity(&args)),
+        // pid: arg0 as u16 (0 for self) -> mask or usize::MAX
+        Syscall::SchedGetAffinity => context.set_rax(sys_get_affinity(&args)),
+
+        // fds: arg0 as *mut PollFd, nfds: arg1 as usize, timeout: arg2 as isize (ms) -> ready count
+        Syscall::Poll => sys_poll(&args, context),
+
+        // key: arg0 as u32, flags: arg1 as IpcFlags -> id or -errno
+        Syscall::MsgGet => context.set_rax(sys_msg_get(&args)),
+        // id: arg0 as u32, buf: arg1 as *const MsgBuf, flags: arg2 as IpcFlags -> 0 or -errno
+        Syscall::MsgSnd => sys_msg_snd(&args, context),
+        // id: arg0 as u32, buf: arg1 as *mut MsgBuf, flags: arg2 as IpcFlags -> len or -errno
+        Syscall::MsgRcv => sys_msg_rcv(&args, context),
+        // id: arg0 as u32, cmd: arg1 as usize, stat: arg2 as *mut MsqStat -> 0 or -errno
+        Syscall::MsgCtl => context.set_rax(sys_msg_ctl(&args)),
+
+        // key: arg0 as u32, size: arg1 as usize, flags: arg2 as IpcFlags -> id or -errno
+        Syscall::ShmGet => context.set_rax(sys_shm_get(&args)),
+        // id: arg0 as u32, addr: arg1 as u64 (0 for any) -> addr or -errno
+        Syscall::ShmAt => context.set_rax(sys_shm_at(&args)),
+        // addr: arg0 as u64 -> 0 or -errno
+        Syscall::ShmDt => context.set_rax(sys_shm_dt(&args)),
+        // id: arg0 as u32, cmd: arg1 as usize, stat: arg2 as *mut ShmStat -> 0 or -errno
+        Syscall::ShmCtl => context.set_rax(sys_shm_ctl(&args)),
+
         // ----------------------------------------------------
         // NOTE: following syscall examples are implemented
         // ----------------------------------------------------
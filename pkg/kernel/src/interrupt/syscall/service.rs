@@ -1,11 +1,15 @@
+use alloc::sync::Arc;
 use core::alloc::Layout;
+use spin::Mutex;
 
+use crate::fs::{self, FsError, FsResult, Metadata, OpenFlags, Whence};
 use crate::proc::*;
+use crate::resource::Resource;
 use crate::utils::*;
+use syscall_def::fs::{DirEntry, FileStat, FileType, PollFd};
+use syscall_def::ipc::{IpcFlags, MsgBuf, MsqStat, ShmStat};
 
 
-use self::processor::current;
-
 use super::SyscallArgs;
 
 pub fn spawn_process(args: &SyscallArgs) -> usize {
@@ -27,6 +31,21 @@ pub fn spawn_process(args: &SyscallArgs) -> usize {
     u16::from(pid.unwrap()) as usize
 }
 
+/// The resource behind `fd` of the current process
+///
+/// The process is unlocked again before the resource is used, reading a
+/// /proc file looks at the process itself.
+fn current_resource(fd: u8) -> Option<Arc<Mutex<Resource>>> {
+    let proc = get_process_manager().current();
+    let inner = proc.read();
+    inner.data()?.resource(fd)
+}
+
+/// Run `f` on the resource behind `fd`, `None` if the fd is not open
+fn with_resource<T>(fd: u8, f: impl FnOnce(&mut Resource) -> T) -> Option<T> {
+    current_resource(fd).map(|res| f(&mut res.lock()))
+}
+
 pub fn sys_write(args: &SyscallArgs) -> usize {
     // FIXME: get buffer and fd by args
     //       - core::slice::from_raw_parts
@@ -35,26 +54,24 @@ pub fn sys_write(args: &SyscallArgs) -> usize {
     };
     let fd = args.arg0 as u8;
     // FIXME: call proc::write -> isize
-    let pid = current().get_pid().unwrap();
-    let proc = get_process_manager().get_proc(&pid).unwrap();
-    let proc_data = proc.get_data_mut();
-    let result = proc_data.write(fd, buffer);
+    let result = current_resource(fd)
+        .ok_or(FsError::BadDescriptor)
+        .and_then(|res| res.lock().write(buffer));
     // FIXME: return the result as usize
     
-    result as usize
+    fs_ret(result)
 }
 
 pub fn sys_read(args: &SyscallArgs) -> usize {
-    let mut buffer = unsafe{
+    let buffer = unsafe{
         core::slice::from_raw_parts_mut(args.arg1 as *mut u8, args.arg2)
     };
     let fd = args.arg0 as u8;
 
-    let pid = current().get_pid().unwrap();
-    let proc = get_process_manager().get_proc(&pid).unwrap();
-    let mut proc_inner = proc.get_data_mut();
-    let result = proc_inner.proc_data_read(fd, buffer);
-    result as usize
+    let result = current_resource(fd)
+        .ok_or(FsError::BadDescriptor)
+        .and_then(|res| res.lock().read(buffer));
+    fs_ret(result)
 }
 
 pub fn exit_process(args: &SyscallArgs, context: &mut ProcessContext) {
@@ -67,6 +84,41 @@ pub fn list_process() {
     get_process_manager().print_process_list();
 }
 
+/// Copy app records into the user buffer, returns the number of apps
+pub fn sys_list_app_info(args: &SyscallArgs) -> usize {
+    let list = app_records();
+    if args.arg0 == 0 {
+        return list.len();
+    }
+
+    let buf = unsafe {
+        core::slice::from_raw_parts_mut(args.arg0 as *mut syscall_def::proc::AppRecord, args.arg1)
+    };
+    for (record, app) in buf.iter_mut().zip(list.iter()) {
+        *record = *app;
+    }
+
+    list.len()
+}
+
+/// Copy process records into the user buffer, returns the number of
+/// processes (which may be more than the buffer holds)
+pub fn sys_list_process(args: &SyscallArgs) -> usize {
+    let list = process_list();
+    if args.arg0 == 0 {
+        return list.len();
+    }
+
+    let buf = unsafe {
+        core::slice::from_raw_parts_mut(args.arg0 as *mut syscall_def::proc::ProcessRecord, args.arg1)
+    };
+    for (record, info) in buf.iter_mut().zip(list.iter()) {
+        *record = info.to_record();
+    }
+
+    list.len()
+}
+
 pub fn sys_allocate(args: &SyscallArgs) -> usize {
     let layout = unsafe { (args.arg0 as *const Layout).as_ref().unwrap() };
 
@@ -118,15 +170,285 @@ pub fn sys_clock() -> i64 {
     }
 }
 
+/// Fill the `TimeSpec` at `arg1` with the time of clock `arg0`
+pub fn sys_clock_gettime(args: &SyscallArgs) -> usize {
+    use syscall_def::time::*;
+
+    let Some(ts) = (unsafe { (args.arg1 as *mut TimeSpec).as_mut() }) else {
+        return usize::MAX;
+    };
+
+    let nanos = match args.arg0 {
+        CLOCK_REALTIME => clock::realtime_ns(),
+        CLOCK_MONOTONIC => Some(clock::monotonic_ns()),
+        CLOCK_PROCESS_CPUTIME_ID => Some(process_cpu_time_ns()),
+        _ => None,
+    };
+
+    match nanos {
+        Some(nanos) => {
+            *ts = TimeSpec::from_nanos(nanos);
+            0
+        }
+        None => usize::MAX,
+    }
+}
+
 // lab5
 
+/// Returns the tid of the new thread, 0 if it could not be created
+pub fn sys_thread_create(args: &SyscallArgs) -> usize {
+    thread_create(args.arg0, args.arg1)
+        .map(|tid| u16::from(tid) as usize)
+        .unwrap_or(0)
+}
+
+/// `ARCH_SET_FS` sets the thread pointer, `ARCH_GET_FS` stores it at
+/// `addr`; returns 0 on success
+pub fn sys_arch_prctl(args: &SyscallArgs) -> usize {
+    use syscall_def::proc::{ARCH_GET_FS, ARCH_SET_FS};
+
+    match args.arg0 {
+        ARCH_SET_FS if set_fs_base(args.arg1 as u64) => 0,
+        ARCH_GET_FS if args.arg1 != 0 => {
+            unsafe { (args.arg1 as *mut u64).write(fs_base()) };
+            0
+        }
+        _ => usize::MAX,
+    }
+}
+
+/// Reset the machine, through ACPI or UEFI
+pub fn sys_reboot() -> ! {
+    crate::reboot(crate::boot_info())
+}
+
+pub fn sys_set_affinity(args: &SyscallArgs) -> usize {
+    if set_affinity(ProcessId(args.arg0 as u16), args.arg1 as u64) {
+        0
+    } else {
+        usize::MAX
+    }
+}
+
+pub fn sys_get_affinity(args: &SyscallArgs) -> usize {
+    get_affinity(ProcessId(args.arg0 as u16))
+        .map(|mask| mask as usize)
+        .unwrap_or(usize::MAX)
+}
+
+/// `val` is the expected value for `FUTEX_WAIT`, the number of threads
+/// for `FUTEX_WAKE` and the address of the target for `FUTEX_REQUEUE`
+pub fn sys_futex(args: &SyscallArgs, context: &mut ProcessContext) {
+    use syscall_def::proc::{FUTEX_REQUEUE, FUTEX_WAIT, FUTEX_WAKE};
+
+    match args.arg1 {
+        FUTEX_WAIT => futex_wait(args.arg0, args.arg2 as u32, context),
+        FUTEX_WAKE => context.set_rax(futex_wake(args.arg0, args.arg2)),
+        FUTEX_REQUEUE => context.set_rax(futex_requeue(args.arg0, args.arg2)),
+        _ => context.set_rax(usize::MAX),
+    }
+}
+
 pub fn sys_sem(args: &SyscallArgs, context: &mut ProcessContext) {
+    use syscall_def::proc::*;
+
+    let key = args.arg1 as u32;
     match args.arg0 {
-        0 => context.set_rax(new_sem(args.arg1 as u32, args.arg2)),
-        1 => context.set_rax(remove_sem(args.arg1 as u32)),
-        2 => sem_signal(args.arg1 as u32, context),
-        3 => sem_wait(args.arg1 as u32, context),
+        SEM_CREATE => context.set_rax(new_sem(key, args.arg2)),
+        SEM_UNLINK => context.set_rax(remove_sem(key)),
+        SEM_SIGNAL => sem_signal(key, context),
+        SEM_WAIT => sem_wait(key, context),
+        SEM_OPEN => context.set_rax(open_sem(key)),
+        SEM_CLOSE => context.set_rax(close_sem(key)),
         _ => context.set_rax(usize::MAX),
     }
 }
 
+pub fn sys_poll(args: &SyscallArgs, context: &mut ProcessContext) {
+    let fds = unsafe { core::slice::from_raw_parts_mut(args.arg0 as *mut PollFd, args.arg1) };
+    poll(fds, args.arg2 as isize, context);
+}
+
+pub fn sys_msg_get(args: &SyscallArgs) -> usize {
+    let flags = IpcFlags::from_bits_truncate(args.arg1);
+    msg_get(args.arg0 as u32, flags) as usize
+}
+
+pub fn sys_msg_snd(args: &SyscallArgs, context: &mut ProcessContext) {
+    let buf = unsafe { &*(args.arg1 as *const MsgBuf) };
+    let flags = IpcFlags::from_bits_truncate(args.arg2);
+    msg_send(args.arg0 as u32, buf, flags, context);
+}
+
+pub fn sys_msg_rcv(args: &SyscallArgs, context: &mut ProcessContext) {
+    let buf = unsafe { &mut *(args.arg1 as *mut MsgBuf) };
+    let flags = IpcFlags::from_bits_truncate(args.arg2);
+    msg_receive(args.arg0 as u32, buf, flags, context);
+}
+
+pub fn sys_msg_ctl(args: &SyscallArgs) -> usize {
+    // only IPC_STAT and IPC_SET look at it, may be null otherwise
+    msg_ctl(args.arg0 as u32, args.arg1, args.arg2 as *mut MsqStat) as usize
+}
+
+pub fn sys_shm_get(args: &SyscallArgs) -> usize {
+    let flags = IpcFlags::from_bits_truncate(args.arg2);
+    shm_get(args.arg0 as u32, args.arg1, flags) as usize
+}
+
+pub fn sys_shm_at(args: &SyscallArgs) -> usize {
+    shm_attach(args.arg0 as u32, args.arg1 as u64) as usize
+}
+
+pub fn sys_shm_dt(args: &SyscallArgs) -> usize {
+    shm_detach(args.arg0 as u64) as usize
+}
+
+pub fn sys_shm_ctl(args: &SyscallArgs) -> usize {
+    // only IPC_STAT looks at it, may be null otherwise
+    shm_ctl(args.arg0 as u32, args.arg1, args.arg2 as *mut ShmStat) as usize
+}
+
+
+// lab6: filesystem
+
+/// Borrow a utf-8 string passed from user space
+fn user_str<'a>(ptr: usize, len: usize) -> Option<&'a str> {
+    if ptr == 0 {
+        return None;
+    }
+    core::str::from_utf8(unsafe { core::slice::from_raw_parts(ptr as *const u8, len) }).ok()
+}
+
+/// Encode a filesystem result in `rax`
+fn fs_ret(ret: FsResult<usize>) -> usize {
+    match ret {
+        Ok(val) => val,
+        Err(e) => e.as_code() as usize,
+    }
+}
+
+pub fn sys_open(args: &SyscallArgs) -> usize {
+    let Some(path) = user_str(args.arg0, args.arg1) else {
+        return FsError::InvalidPath.as_code() as usize;
+    };
+    let flags = OpenFlags::from_bits_truncate(args.arg2);
+
+    let proc = get_process_manager().current();
+    let inner = proc.read();
+
+    fs_ret(fs::open(inner.cwd(), path, flags).and_then(|file| {
+        inner
+            .open(Resource::File(file))
+            .map(|fd| fd as usize)
+            .ok_or(FsError::TooManyOpenFiles)
+    }))
+}
+
+pub fn sys_close(args: &SyscallArgs) -> usize {
+    let proc = get_process_manager().current();
+    if proc.read().close(args.arg0 as u8) {
+        0
+    } else {
+        FsError::BadDescriptor.as_code() as usize
+    }
+}
+
+pub fn sys_fstat(args: &SyscallArgs) -> usize {
+    let Some(stat) = (unsafe { (args.arg1 as *mut FileStat).as_mut() }) else {
+        return FsError::InvalidPath.as_code() as usize;
+    };
+
+    let ret = with_resource(args.arg0 as u8, |res| match res {
+        Resource::File(file) => file.stat(),
+        _ => Ok(Metadata::new(0, FileType::CharDevice, 0)),
+    })
+    .unwrap_or(Err(FsError::BadDescriptor));
+
+    fs_ret(ret.map(|meta| {
+        *stat = meta.to_stat();
+        0
+    }))
+}
+
+pub fn sys_stat(args: &SyscallArgs) -> usize {
+    let Some(path) = user_str(args.arg0, args.arg1) else {
+        return FsError::InvalidPath.as_code() as usize;
+    };
+    let Some(stat) = (unsafe { (args.arg2 as *mut FileStat).as_mut() }) else {
+        return FsError::InvalidPath.as_code() as usize;
+    };
+
+    let proc = get_process_manager().current();
+    let ret = fs::stat(proc.read().cwd(), path);
+
+    fs_ret(ret.map(|meta| {
+        *stat = meta.to_stat();
+        0
+    }))
+}
+
+pub fn sys_seek(args: &SyscallArgs) -> usize {
+    let whence = Whence::from(args.arg2);
+
+    let ret = with_resource(args.arg0 as u8, |res| match res {
+        Resource::File(file) => file.seek(args.arg1 as i64, whence),
+        _ => Err(FsError::InvalidSeek),
+    })
+    .unwrap_or(Err(FsError::BadDescriptor));
+
+    fs_ret(ret.map(|pos| pos as usize))
+}
+
+pub fn sys_getdents(args: &SyscallArgs) -> usize {
+    if args.arg1 == 0 {
+        return FsError::InvalidPath.as_code() as usize;
+    }
+    let buf = unsafe { core::slice::from_raw_parts_mut(args.arg1 as *mut DirEntry, args.arg2) };
+
+    let ret = with_resource(args.arg0 as u8, |res| match res {
+        Resource::File(file) => file.read_dir(buf.len()),
+        _ => Err(FsError::NotADirectory),
+    })
+    .unwrap_or(Err(FsError::BadDescriptor));
+
+    fs_ret(ret.map(|entries| {
+        for (record, entry) in buf.iter_mut().zip(entries.iter()) {
+            *record = entry.to_record();
+        }
+        entries.len()
+    }))
+}
+
+pub fn sys_getcwd(args: &SyscallArgs) -> usize {
+    if args.arg0 == 0 {
+        return FsError::InvalidPath.as_code() as usize;
+    }
+    let buf = unsafe { core::slice::from_raw_parts_mut(args.arg0 as *mut u8, args.arg1) };
+
+    let proc = get_process_manager().current();
+    let inner = proc.read();
+    let cwd = inner.cwd().as_bytes();
+
+    if cwd.len() > buf.len() {
+        return FsError::BufferTooSmall.as_code() as usize;
+    }
+
+    buf[..cwd.len()].copy_from_slice(cwd);
+    cwd.len()
+}
+
+pub fn sys_chdir(args: &SyscallArgs) -> usize {
+    let Some(path) = user_str(args.arg0, args.arg1) else {
+        return FsError::InvalidPath.as_code() as usize;
+    };
+
+    let proc = get_process_manager().current();
+    let mut inner = proc.write();
+
+    fs_ret(fs::canonical_dir(inner.cwd(), path).map(|dir| {
+        inner.set_cwd(dir);
+        0
+    }))
+}
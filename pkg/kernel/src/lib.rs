@@ -29,32 +29,55 @@ pub use drivers::*;
 pub mod memory;
 pub mod interrupt;
 pub mod proc;
+pub mod fs;
+pub mod smp;
 
 pub use alloc::format;
 use boot::BootInfo;
 
 use crate::memory::user;
 
+/// Kept for `reboot` from the syscall handler
+struct BootInfoRef(&'static BootInfo);
+
+// never written after `init`, only read by `reboot`
+unsafe impl Send for BootInfoRef {}
+unsafe impl Sync for BootInfoRef {}
+
+static BOOT_INFO: spin::Once<BootInfoRef> = spin::Once::new();
+
 pub fn init(boot_info: &'static BootInfo) {
+    BOOT_INFO.call_once(|| BootInfoRef(boot_info));
     serial::init(); // init serial output
     logger::init(); // init logger system
     memory::address::init(boot_info);
     memory::gdt::init(); // init gdt
     memory::allocator::init(); // init kernel heap allocator
+    acpi::init(boot_info); // find ACPI tables, before the APICs
+    clock::init(boot_info); // tick rate and clock source, before the timer starts
     interrupt::init(); // init interrupts
     memory::init(boot_info); // init memory manager
     proc::init(boot_info);
     user::init();
-    uefi::init(boot_info); // 计时
+    ata::init(); // probe disks
+    keyboard::init(); // PS/2 keyboard, feeds the serial input buffer
+    chardev::init(); // register /dev entries
+    rtc::init(); // /dev/rtc, alarm and periodic irqs
+    fs::init();
+    smp::init(boot_info); // start other CPUs
     x86_64::instructions::interrupts::enable(); 
     info!("Interrupts Enabled.");
 
+    logger::start_klogd(); // save the kernel log to disk
+
     info!("YatSenOS initialized.");
 }
 
 pub fn wait(init: proc::ProcessId) {
     loop {
         if proc::still_alive(init) {
+            // blocked until init exits, the BSP idles meanwhile
+            proc::wait_exit(init);
             x86_64::instructions::hlt();
         } else {
             break;
@@ -64,6 +87,9 @@ pub fn wait(init: proc::ProcessId) {
 
 pub fn shutdown(boot_info: &'static BootInfo) -> ! {
     info!("YatSenOS shutting down.");
+    logger::stop_klogd();
+    acpi::power_off();
+    // UEFI is the fallback
     unsafe {
         boot_info.system_table.runtime_services().reset(
             boot::ResetType::SHUTDOWN,
@@ -72,3 +98,20 @@ pub fn shutdown(boot_info: &'static BootInfo) -> ! {
         );
     }
 }
+
+/// The `BootInfo` passed to `init`
+pub fn boot_info() -> &'static BootInfo {
+    BOOT_INFO.get().expect("Kernel not initialized").0
+}
+
+pub fn reboot(boot_info: &'static BootInfo) -> ! {
+    info!("YatSenOS rebooting.");
+    acpi::reset();
+    unsafe {
+        boot_info.system_table.runtime_services().reset(
+            boot::ResetType::COLD,
+            boot::UefiStatus::SUCCESS,
+            None,
+        );
+    }
+}
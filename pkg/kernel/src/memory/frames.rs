@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 use boot::{MemoryMap, MemoryType};
 use x86_64::structures::paging::{FrameAllocator, FrameDeallocator, PhysFrame, Size4KiB};
 use x86_64::PhysAddr;
@@ -8,6 +9,9 @@ guard_access_fn! {
     pub get_frame_alloc(FRAME_ALLOCATOR: BootInfoFrameAllocator)
 }
 
+/// Frames below 1 MiB are left for real mode code, e.g. the AP trampoline
+pub const LOW_MEMORY_END: u64 = 0x10_0000;
+
 type BootInfoFrameIter = impl Iterator<Item = PhysFrame>;
 
 /// A FrameAllocator that returns usable frames from the bootloader's memory map.
@@ -15,6 +19,8 @@ pub struct BootInfoFrameAllocator {
     size: usize,
     used: usize,
     frames: BootInfoFrameIter,
+    /// Frames given back, handed out again before new ones
+    recycled: Vec<PhysFrame>,
 }
 
 impl BootInfoFrameAllocator {
@@ -28,6 +34,7 @@ impl BootInfoFrameAllocator {
             size,
             frames: create_frame_iter(memory_map),
             used: 0,
+            recycled: Vec::new(),
         }
     }
 
@@ -43,13 +50,14 @@ impl BootInfoFrameAllocator {
 unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
     fn allocate_frame(&mut self) -> Option<PhysFrame> {
         self.used += 1;
-        self.frames.next()
+        self.recycled.pop().or_else(|| self.frames.next())
     }
 }
 
 impl FrameDeallocator<Size4KiB> for BootInfoFrameAllocator {
-    unsafe fn deallocate_frame(&mut self, _frame: PhysFrame) {
-        // TODO: deallocate frame (not for lab 2)
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame) {
+        self.used -= 1;
+        self.recycled.push(frame);
     }
 }
 
@@ -61,6 +69,7 @@ unsafe fn create_frame_iter(memory_map: &MemoryMap) -> BootInfoFrameIter {
         .filter(|r| r.ty == MemoryType::CONVENTIONAL)
         // align to page boundary
         .flat_map(|r| (0..r.page_count).map(move |v| (v * 4096 + r.phys_start)))
+        .filter(|&addr| addr >= LOW_MEMORY_END)
         // create `PhysFrame` types from the start addresses
         .map(|addr| PhysFrame::containing_address(PhysAddr::new(addr)))
 }
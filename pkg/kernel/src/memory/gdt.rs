@@ -1,3 +1,5 @@
+use alloc::boxed::Box;
+use alloc::vec;
 use lazy_static::lazy_static;
 use x86_64::registers::segmentation::Segment;
 use x86_64::structures::gdt::{Descriptor, GlobalDescriptorTable, SegmentSelector};
@@ -139,19 +141,8 @@ pub fn get_user_selector() -> &'static UserSelectors{
 
 
 pub fn init() {
-    use x86_64::instructions::segmentation::{CS, DS, ES, FS, GS, SS};
-    use x86_64::instructions::tables::load_tss;
-    use x86_64::PrivilegeLevel;
     GDT.0.load();
-    unsafe {
-        CS::set_reg(GDT.1.code_selector);
-        DS::set_reg(GDT.1.data_selector);
-        SS::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
-        ES::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
-        FS::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
-        GS::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
-        load_tss(GDT.1.tss_selector);
-    }
+    unsafe { load_selectors(&GDT.1) };
     let mut size = 0;
 
     for &s in IST_SIZES.iter() {
@@ -168,3 +159,54 @@ pub fn get_selector() -> &'static KernelSelectors {
     &GDT.1
 }
 
+unsafe fn load_selectors(selectors: &KernelSelectors) {
+    use x86_64::instructions::segmentation::{CS, DS, ES, FS, GS, SS};
+    use x86_64::instructions::tables::load_tss;
+    use x86_64::PrivilegeLevel;
+
+    CS::set_reg(selectors.code_selector);
+    DS::set_reg(selectors.data_selector);
+    SS::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
+    ES::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
+    FS::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
+    GS::set_reg(SegmentSelector::new(0, PrivilegeLevel::Ring0));
+    load_tss(selectors.tss_selector);
+}
+
+/// GDT and TSS of an application processor
+///
+/// Every CPU needs its own TSS, the stacks in it must not be shared. The
+/// layout is the same as the BSP's so `get_selector` and
+/// `get_user_selector` hold on every CPU. APs never stop, so the tables
+/// and stacks are leaked.
+pub fn init_ap() {
+    let stack = |size: usize| {
+        let stack = Box::leak(vec![0u8; size].into_boxed_slice());
+        VirtAddr::from_ptr(stack.as_ptr()) + size as u64
+    };
+
+    let mut tss = TaskStateSegment::new();
+    tss.privilege_stack_table[0] = stack(IST_SIZES[0]);
+    for (i, &size) in IST_SIZES[1..].iter().enumerate() {
+        tss.interrupt_stack_table[i] = stack(size);
+    }
+    let tss: &'static TaskStateSegment = Box::leak(Box::new(tss));
+
+    let gdt: &'static mut GlobalDescriptorTable = Box::leak(Box::new(GlobalDescriptorTable::new()));
+    let selectors = KernelSelectors {
+        code_selector: gdt.append(Descriptor::kernel_code_segment()),
+        data_selector: gdt.append(Descriptor::kernel_data_segment()),
+        tss_selector: gdt.append(Descriptor::tss_segment(tss)),
+    };
+    let user_code_selector = gdt.append(Descriptor::user_code_segment());
+    let user_data_selector = gdt.append(Descriptor::user_data_segment());
+
+    assert_eq!(selectors.code_selector, GDT.1.code_selector);
+    assert_eq!(selectors.tss_selector, GDT.1.tss_selector);
+    assert_eq!(user_code_selector, GDT.2.user_code_selector);
+    assert_eq!(user_data_selector, GDT.2.user_data_selector);
+
+    gdt.load();
+    unsafe { load_selectors(&selectors) };
+}
+
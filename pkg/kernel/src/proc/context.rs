@@ -32,6 +32,20 @@ impl ProcessContext {
         self.value.regs.rax = value;
     }
     
+    /// Pass the first arguments of a function started with this context
+    #[inline]
+    pub fn set_args(&mut self, args: [usize; 2]) {
+        self.value.regs.rdi = args[0];
+        self.value.regs.rsi = args[1];
+    }
+
+    /// Make the process run the `int 0x80` that entered the kernel again
+    /// when it is switched back to
+    #[inline]
+    pub fn restart_syscall(&mut self) {
+        self.value.stack_frame.instruction_pointer -= 2u64;
+    }
+
     #[inline]
     pub fn save(&mut self, context: &ProcessContext) {
         self.value = context.as_ref().as_ptr().read();
@@ -1,11 +1,13 @@
 use alloc::{collections::BTreeMap, sync::Arc};
-use spin::RwLock;
+use spin::{Mutex, RwLock};
 use x86_64::structures::paging::{
     page::{PageRange, PageRangeInclusive},
     Page,
 };
-use crate::resource::ResourceSet;
+use crate::resource::{Resource, ResourceSet};
+use crate::proc::shm::ShmSpace;
 use crate::proc::sync::SemaphoreSet;
+use crate::proc::tls::TlsTemplate;
 
 use super::*;
 
@@ -24,6 +26,15 @@ pub struct ProcessData {
     pub(super) resources: Arc<RwLock<ResourceSet>>,
 
     pub(super) semaphores: Arc<RwLock<SemaphoreSet>>,
+
+    // attached shared memory, shared by threads and copied on fork
+    pub(super) shm: Arc<RwLock<ShmSpace>>,
+
+    // current working directory, always absolute and normalized
+    pub(super) cwd: String,
+
+    // PT_TLS of the program, every thread gets a copy
+    pub(super) tls: Option<Arc<TlsTemplate>>,
 }
 
 impl Default for ProcessData {
@@ -35,6 +46,9 @@ impl Default for ProcessData {
             stack_memory_usage: 0,
             resources: Arc::new(RwLock::new(ResourceSet::default())),
             semaphores: Arc::new(RwLock::new(SemaphoreSet::default())),
+            shm: Arc::new(RwLock::new(ShmSpace::default())),
+            cwd: String::from("/"),
+            tls: None,
         }
     }
 }
@@ -44,10 +58,29 @@ impl ProcessData {
         Self::default()
     }
 
+    /// Data of a forked child, shared like a thread's except that the
+    /// child closes its own references to the semaphores and detaches its
+    /// own shared memory
+    pub fn fork(&self) -> Self {
+        let mut data = self.clone();
+        data.semaphores = Arc::new(RwLock::new(self.semaphores.read().clone()));
+        data.shm = Arc::new(RwLock::new(self.shm.read().clone()));
+        data
+    }
+
     pub fn env(&self, key: &str) -> Option<String> {
         self.env.read().get(key).cloned()
     }
 
+    /// All environment variables, sorted by key
+    pub fn env_list(&self) -> Vec<(String, String)> {
+        self.env
+            .read()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
     pub fn set_env(&mut self, key: &str, val: &str) {
         self.env.write().insert(key.into(), val.into());
     }
@@ -71,12 +104,49 @@ impl ProcessData {
     }
     
     // lab4新增
-    pub fn read(&self, fd: u8, buf: &mut [u8]) -> isize {
-        self.resources.read().read(fd, buf)
+    /// The resource behind `fd`, the fd table is unlocked again on return
+    pub fn resource(&self, fd: u8) -> Option<Arc<Mutex<Resource>>> {
+        self.resources.read().get(fd)
     }
-    
-    pub fn write(&self, fd: u8, buf: &[u8]) -> isize {
-        self.resources.read().write(fd, buf)
+
+    pub fn open(&self, res: Resource) -> Option<u8> {
+        self.resources.write().open(res)
+    }
+
+    pub fn close(&self, fd: u8) -> bool {
+        self.resources.write().close(fd)
+    }
+
+    /// Open fds and what they refer to
+    ///
+    /// A resource that is locked right now (e.g. the one being read by
+    /// the caller) is reported as busy instead of waiting for it.
+    pub fn fd_list(&self) -> Vec<(u8, String)> {
+        self.resources
+            .read()
+            .handles
+            .iter()
+            .map(|(fd, res)| {
+                let desc = res
+                    .try_lock()
+                    .map(|r| r.to_string())
+                    .unwrap_or_else(|| String::from("(busy)"));
+                (*fd, desc)
+            })
+            .collect()
+    }
+
+    /// Stop waiting on any semaphore, `pid` is being killed
+    pub fn cancel_sem_wait(&self, pid: ProcessId) {
+        self.semaphores.read().cancel_wait(pid);
+    }
+
+    pub fn cwd(&self) -> &str {
+        &self.cwd
+    }
+
+    pub fn set_cwd(&mut self, path: String) {
+        self.cwd = path;
     }
 
     pub fn stack_memory_usage(&self) -> usize {
@@ -101,7 +171,11 @@ impl ProcessData {
         }
     }
 
+    pub fn shm_memory_usage(&self) -> usize {
+        self.shm.read().memory_usage()
+    }
+
     pub fn total_memory_usage(&self) -> usize {
-        self.stack_memory_usage() + self.code_memory_usage()
+        self.stack_memory_usage() + self.code_memory_usage() + self.shm_memory_usage()
     }
 }
@@ -0,0 +1,61 @@
+//! Futex wait queues
+//!
+//! A futex is a `u32` in user memory. Queues are keyed by the physical
+//! address of that word, so threads of a process and processes sharing
+//! the page find each other without agreeing on any key.
+
+use super::ProcessId;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+use spin::Mutex;
+
+pub static FUTEXES: Mutex<FutexTable> = Mutex::new(FutexTable::new());
+
+#[derive(Debug, Default)]
+pub struct FutexTable {
+    queues: BTreeMap<u64, VecDeque<ProcessId>>,
+}
+
+impl FutexTable {
+    pub const fn new() -> Self {
+        Self {
+            queues: BTreeMap::new(),
+        }
+    }
+
+    pub fn wait(&mut self, key: u64, pid: ProcessId) {
+        self.queues.entry(key).or_default().push_back(pid);
+    }
+
+    /// Take up to `count` waiters of `key` in FIFO order
+    pub fn wake(&mut self, key: u64, count: usize) -> Vec<ProcessId> {
+        let Some(queue) = self.queues.get_mut(&key) else {
+            return Vec::new();
+        };
+
+        let count = count.min(queue.len());
+        let woken = queue.drain(..count).collect();
+        if queue.is_empty() {
+            self.queues.remove(&key);
+        }
+        woken
+    }
+
+    /// Take the first waiter of `from` and move the others to `to`
+    pub fn requeue(&mut self, from: u64, to: u64) -> Option<ProcessId> {
+        let mut queue = self.queues.remove(&from)?;
+        let first = queue.pop_front();
+        if !queue.is_empty() {
+            self.queues.entry(to).or_default().append(&mut queue);
+        }
+        first
+    }
+
+    /// Forget a waiter that went away without being woken
+    pub fn remove(&mut self, pid: ProcessId) {
+        self.queues.retain(|_, queue| {
+            queue.retain(|&p| p != pid);
+            !queue.is_empty()
+        });
+    }
+}
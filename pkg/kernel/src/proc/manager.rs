@@ -31,6 +31,12 @@ pub fn init(init: Arc<Process>, apps:AppListRef) {
     PROCESS_MANAGER.call_once(|| ProcessManager::new(init,apps));
 }
 
+extern "C" fn idle() -> ! {
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
 pub fn get_process_manager() -> &'static ProcessManager {
     PROCESS_MANAGER
         .get()
@@ -44,15 +50,15 @@ pub fn get_process_manager() -> &'static ProcessManager {
 
 pub struct ProcessManager {
     processes: RwLock<BTreeMap<ProcessId, Arc<Process>>>,
-    ready_queue: Mutex<VecDeque<ProcessId>>,
     app_list: boot::AppListRef,
+    /// Processes blocked in `wait_exit`, by the pid they wait for
+    exit_waiters: Mutex<BTreeMap<ProcessId, Vec<ProcessId>>>,
 }
 
 // lab3有个莫名其妙的处理函数尚未实现，等到wait_pid要用的时候再写
 impl ProcessManager {
     pub fn new(init: Arc<Process>, app_list: boot::AppListRef) -> Self {
         let mut processes = BTreeMap::new();
-        let ready_queue = VecDeque::new();
         let pid = init.pid();
 
         trace!("Init {:#?}", init);
@@ -60,14 +66,42 @@ impl ProcessManager {
         processes.insert(pid, init);
         Self {
             processes: RwLock::new(processes),
-            ready_queue: Mutex::new(ready_queue),
             app_list,
+            exit_waiters: Mutex::new(BTreeMap::new()),
         }
     }
 
-    #[inline]
+    /// Queue `pid` on the least busy CPU it may run on
     pub fn push_ready(&self, pid: ProcessId) {
-        self.ready_queue.lock().push_back(pid);
+        let affinity = self.get_proc(&pid).map_or(AFFINITY_ALL, |p| p.affinity());
+        let cpu = processor::select(affinity);
+        let target = processor::get(cpu);
+        target.push(pid);
+
+        // an idle CPU has no tick and only looks at its queue when
+        // interrupted, this one included if we are in an irq handler
+        if target.is_idle() {
+            crate::interrupt::send_reschedule(cpu as u8);
+        }
+    }
+
+    /// If `pid` may run on CPU `cpu`
+    fn allowed_on(&self, pid: ProcessId, cpu: usize) -> bool {
+        self.get_proc(&pid)
+            .is_some_and(|p| p.affinity() & (1 << cpu) != 0)
+    }
+
+    /// Take a process from the busiest other CPU for this one
+    fn steal(&self) -> Option<ProcessId> {
+        let this = processor::current_id();
+        let mut victims: Vec<_> = processor::online()
+            .filter(|&(i, p)| i != this && p.queue_len() > 0)
+            .collect();
+        victims.sort_by_key(|(_, p)| core::cmp::Reverse(p.queue_len()));
+
+        victims.into_iter().find_map(|(_, p)| {
+            p.steal(|pid| !processor::is_running(pid) && self.allowed_on(pid, this))
+        })
     }
 
     #[inline]
@@ -80,6 +114,11 @@ impl ProcessManager {
         self.processes.read().get(pid).cloned()
     }
 
+    /// All processes (including dead ones) ordered by pid
+    pub fn processes(&self) -> Vec<Arc<Process>> {
+        self.processes.read().values().cloned().collect()
+    }
+
     pub fn current(&self) -> Arc<Process> {
         self.get_proc(&processor::get_pid())
             .expect("No current process")
@@ -95,7 +134,8 @@ impl ProcessManager {
         inner.save(context);
         
         // FIXME: push current process to ready queue if still alive
-        if inner.status() != ProgramStatus::Dead{
+        // the idle process of a CPU is never queued
+        if inner.status() != ProgramStatus::Dead && processor::current().idle() != Some(process.pid()) {
             self.push_ready(process.pid()); 
         }
 
@@ -103,66 +143,143 @@ impl ProcessManager {
     }
 
     pub fn switch_next(&self, context: &mut ProcessContext) -> ProcessId {
+        let prev = self.current();
+        let next = self.pick_next(prev.pid(), context);
+
+        // off its stack and page table now, a killed one can be freed
+        if next != prev.pid() {
+            let mut inner = prev.write();
+            if inner.status() == ProgramStatus::Dead {
+                inner.reap();
+            }
+        }
+        next
+    }
 
-        let mut pid = self.current().pid();
-
-        while let Some(next) = self.ready_queue.lock().pop_front() {
-            let map = self.processes.read();
-            let proc = map.get(&next).expect("Process not found");
-            
-            if !proc.read().is_ready() {
-                continue;
+    fn pick_next(&self, pid: ProcessId, context: &mut ProcessContext) -> ProcessId {
+        let cpu = processor::current();
+
+        // each pid at most once, ones skipped below go back to the queue
+        let len = cpu.queue_len();
+        for _ in 0..len {
+            let Some(next) = cpu.pop() else {
+                break;
+            };
+            if let Some(pid) = self.try_switch(pid, next, context) {
+                return pid;
             }
-            
-            if pid != next {
-                //println!("Before switching, current status:{:?}, next status:{:?}, current pid:{:?}, next pid:{:?},",self.current().read().status(),proc.read().status(),self.current().pid(),proc.pid());
-                proc.write().restore(context);
-                processor::set_pid(next);
-                //debug!("switch from pid:{} to pid:{}",pid,next);
-                pid = next;
-                //println!("After switching, current status:{:?}, next status:{:?}, current pid:{:?}, next pid:{:?},",self.current().read().status(),proc.read().status(),self.current().pid(),proc.pid());
+        }
 
+        // own queue is empty, help out another CPU
+        while let Some(next) = self.steal() {
+            if let Some(pid) = self.try_switch(pid, next, context) {
+                return pid;
             }
-            break;
         }
+
+        // nothing to run, the others are busy on other CPUs
+        match cpu.idle() {
+            Some(idle) => self.switch_to(idle, context),
+            None => pid,
+        }
+    }
+
+    /// Switch from `pid` to `next` popped from a queue, `None` if it can't run
+    fn try_switch(&self, pid: ProcessId, next: ProcessId, context: &mut ProcessContext) -> Option<ProcessId> {
+        let proc = self.get_proc(&next).expect("Process not found");
+
+        // held until it runs, so `kill` either sees it running or frees it first
+        let mut inner = proc.write();
+        if !inner.is_ready() {
+            return None;
+        }
+
+        // another CPU has queued it but not switched away yet
+        if next != pid && processor::is_running(next) {
+            processor::current().push(next);
+            return None;
+        }
+
+        // its affinity changed after it was queued here
+        if !self.allowed_on(next, processor::current_id()) && processor::online_mask() & proc.affinity() != 0 {
+            drop(inner);
+            self.push_ready(next);
+            return None;
+        }
+
+        if pid != next {
+            //debug!("switch from pid:{} to pid:{}",pid,next);
+            Self::enter(next, &mut inner, context);
+        }
+        Some(next)
+    }
+
+    fn switch_to(&self, next: ProcessId, context: &mut ProcessContext) -> ProcessId {
+        let proc = self.get_proc(&next).expect("Process not found");
+        Self::enter(next, &mut proc.write(), context);
+        next
+    }
+
+    /// Run `next` on this CPU, locked by the caller
+    fn enter(next: ProcessId, inner: &mut ProcessInner, context: &mut ProcessContext) {
+        inner.restore(context);
+        processor::set_pid(next);
+        crate::interrupt::clock::update_tick(processor::current().is_idle());
+    }
+
+    /// Create the idle process of the current CPU
+    ///
+    /// An AP becomes its own idle process: the code running now is saved
+    /// into it on the first switch, like the kernel process on the BSP.
+    /// The BSP already runs the kernel process and gets a kernel thread.
+    pub fn add_idle(&self) -> ProcessId {
+        let kproc = self.get_proc(&KERNEL_PID).unwrap();
+        let page_table = kproc.read().page_table_fork();
+        let name = format!("idle{}", processor::current_id());
+        let proc = Process::new(name, Some(Arc::downgrade(&kproc)), page_table, None);
+        let pid = proc.pid();
+
+        let mut inner = proc.write();
+        if processor::current().is_free() {
+            inner.resume();
+            set_pid(pid);
+        } else {
+            inner.init_kernel_thread(VirtAddr::new(idle as usize as u64), [0; 2]);
+        }
+        drop(inner);
+
+        self.add_proc(pid, proc);
+        processor::current().set_idle(pid);
+        processor::current().set_online();
+
         pid
     }
 
-    // lab3 创建内核进程，lab4中注释
-    // pub fn spawn_kernel_thread(
-    //     &self,
-    //     entry: VirtAddr,
-    //     name: String,
-    //     proc_data: Option<ProcessData>,
-    // ) -> ProcessId {
-    //     let kproc = self.get_proc(&KERNEL_PID).unwrap();
-    //     let page_table = kproc.read().clone_page_table();
-    //     let proc = Process::new(name, Some(Arc::downgrade(&kproc)), page_table, proc_data);
-
-    //     // alloc stack for the new process base on pid
-    //     let stack_top = proc.alloc_init_stack();
-    //     //info!("alloc_init_stack success!");
-
-    //     // FIXME: set the stack frame
-    //     let mut inner = proc.write();
-    //     inner.pause();
-    //     inner.init_stack_frame(entry,stack_top);
-        
-    //     //info!("init_stack_frame success!");
+    pub fn spawn_kernel_thread(
+        &self,
+        entry: VirtAddr,
+        args: [usize; 2],
+        name: String,
+        proc_data: Option<ProcessData>,
+    ) -> ProcessId {
+        let kproc = self.get_proc(&KERNEL_PID).unwrap();
+        // kernel threads run on the kernel page table, no user mappings
+        let page_table = kproc.read().page_table_fork();
+        let proc = Process::new(name, Some(Arc::downgrade(&kproc)), page_table, proc_data);
 
-    //     // FIXME: add to process map
-    //     let new_pid = proc.pid();
-    //     info!("Spawn process: {}#{}", inner.name(), new_pid);
-    //     drop(inner);
-    //     self.add_proc(new_pid, proc.clone());
+        let mut inner = proc.write();
+        inner.init_kernel_thread(entry, args);
+        inner.pause();
 
-    //     // FIXME: push to ready queue
-    //     self.push_ready(new_pid);
-    //     //info!("push_ready success!");
+        let new_pid = proc.pid();
+        info!("Spawn kernel thread: {}#{}", inner.name(), new_pid);
+        drop(inner);
 
-    //     // FIXME: return new process pid
-    //     new_pid
-    // }
+        self.add_proc(new_pid, proc);
+        self.push_ready(new_pid);
+
+        new_pid
+    }
 
     pub fn kill_current(&self, ret: isize) {
         self.kill(processor::get_pid(), ret);
@@ -171,8 +288,11 @@ impl ProcessManager {
     pub fn handle_page_fault(&self, addr: VirtAddr, err_code: PageFaultErrorCode) -> bool {
         // FIXME: handle page fault
         let process = self.current();
-        let pid = process.pid().0 as u64;
-        let min_addr = STACK_MAX - (pid)*STACK_MAX_SIZE;
+        // the slot its stack grows in, a thread's may not be the one of its pid
+        let Some(stack) = process.read().data().and_then(|data| data.stack_segment) else {
+            return false;
+        };
+        let min_addr = stack.start.start_address().as_u64() & STACK_START_MASK;
         let max_addr = min_addr + STACK_MAX_SIZE;
         let addr_u64 = addr.as_u64();
         println!("addr:{:X}, min_addr:{:X}, max_addr:{:X}",addr_u64,min_addr,max_addr);
@@ -201,7 +321,59 @@ impl ProcessManager {
 
         trace!("Kill {:#?}", &proc);
 
-        proc.kill(ret);
+        if let Some(data) = proc.read().data() {
+            data.cancel_sem_wait(pid);
+        }
+        // the CPU running it frees it when switching away
+        if let Some(cpu) = proc.kill(ret).filter(|&cpu| cpu != processor::current_id()) {
+            crate::interrupt::send_reschedule(cpu as u8);
+        }
+        super::futex::FUTEXES.lock().remove(pid);
+        super::msg::MSG_QUEUES.lock().cancel_wait(pid);
+        super::poll::POLL_WAITERS.lock().remove(pid);
+
+        // threads die with their process
+        let threads = proc.write().take_threads();
+        for thread in threads {
+            if thread.read().status() != ProgramStatus::Dead {
+                self.kill(thread.pid(), ret);
+            }
+        }
+
+        // hand the exit code to the thread waiting in `ThreadJoin`
+        let joiner = proc.write().take_joiner();
+        if let Some(joiner) = joiner.and_then(|pid| self.get_proc(&pid)) {
+            let mut inner = joiner.write();
+            if inner.status() == ProgramStatus::Blocked {
+                inner.get_process_context().set_rax(ret as usize);
+                inner.pause();
+                drop(inner);
+                self.push_ready(joiner.pid());
+            }
+        }
+
+        let waiters = self.exit_waiters.lock().remove(&pid).unwrap_or_default();
+        for waiter in waiters {
+            super::wakeup_blocked(self, waiter);
+        }
+    }
+
+    /// Block the current process until `pid` is killed, right away if it
+    /// is dead already
+    pub fn wait_exit(&self, pid: ProcessId) {
+        let mut waiters = self.exit_waiters.lock();
+        // checked under the lock, `kill` takes the waiters after marking it
+        if self.get_proc(&pid).map_or(true, |p| p.read().status() == ProgramStatus::Dead) {
+            return;
+        }
+
+        let current = processor::get_pid();
+        let list = waiters.entry(pid).or_default();
+        if !list.contains(&current) {
+            list.push(current);
+        }
+        drop(waiters);
+        self.block(current);
     }
 
     pub fn print_process_list(&self) {
@@ -215,7 +387,7 @@ impl ProcessManager {
 
         // TODO: print memory usage of kernel heap
 
-        output += format!("Queue  : {:?}\n", self.ready_queue.lock()).as_str();
+        output += &processor::print_queues();
 
         output += &processor::print_processors();
 
@@ -285,6 +457,24 @@ impl ProcessManager {
         child_pid
     }
 
+    /// Create a user thread in the process of the current one
+    pub fn spawn_thread(&self, entry: VirtAddr, arg: usize) -> Option<ProcessId> {
+        let current = self.current();
+        let leader = match current.read().leader() {
+            Some(pid) => self.get_proc(&pid)?,
+            None => current.clone(),
+        };
+
+        let thread = current.new_thread(&leader, entry, arg)?;
+        let tid = thread.pid();
+        leader.write().add_thread(thread.clone());
+
+        self.add_proc(tid, thread);
+        self.push_ready(tid);
+
+        Some(tid)
+    }
+
     pub fn block(&self, pid: ProcessId){
         if let Some(proc) = self.get_proc(&pid) {
             proc.write().block();
@@ -293,7 +483,12 @@ impl ProcessManager {
 
     pub fn wakeup(&self, pid: ProcessId){
         if let Some(proc) = self.get_proc(&pid) {
-            proc.write().pause();
+            let mut inner = proc.write();
+            if inner.status() == ProgramStatus::Dead {
+                return;
+            }
+            inner.pause();
+            drop(inner);
             self.push_ready(pid);
         }
     }
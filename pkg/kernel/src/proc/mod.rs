@@ -6,12 +6,17 @@ mod pid;
 mod process;
 pub mod processor;
 pub mod sync;
+mod tls;
+mod futex;
+pub mod msg;
+pub mod shm;
+mod poll;
 
 
 pub use manager::*;
 use process::*;
 use crate::memory::PAGE_SIZE;
-use crate::proc::sync::SemaphoreResult;
+use crate::proc::sync::{SemaphoreResult, SemaphoreSet};
 
 use alloc::string::String;
 pub use context::ProcessContext;
@@ -25,6 +30,16 @@ use alloc::vec::Vec;
 use alloc::sync::Arc;
 use crate::alloc::string::ToString;
 use xmas_elf::ElfFile;
+use msg::IpcError;
+use crate::resource::Resource;
+use syscall_def::fs::{PollEvents, PollFd};
+use syscall_def::ipc::{IpcFlags, MsgBuf, MsqStat, ShmStat, IPC_OPEN, IPC_RMID, IPC_SET, IPC_STAT};
+use syscall_def::proc::{
+    AppRecord, ProcessRecord, ProcessStatus, SEM_EXISTS, SEM_NOT_FOUND, SEM_OK, THREAD_JOIN_ERROR,
+};
+
+/// End of the lower canonical half, user addresses are below it
+pub const USER_SPACE_END: u64 = 0x0000_8000_0000_0000;
 
 // 0xffff_ff00_0000_0000 is the kernel's address space
 pub const STACK_MAX: u64 = 0x0000_4000_0000_0000;
@@ -48,6 +63,15 @@ pub const KSTACK_INIT_TOP: u64 = KSTACK_MAX - 8;
 
 pub const KERNEL_PID: ProcessId = ProcessId(1);
 
+/// Affinity of a new process: every CPU
+pub const AFFINITY_ALL: u64 = u64::MAX;
+
+/// Stack size of each kernel thread
+pub const KTHREAD_STACK_SIZE: usize = 0x10000;
+
+/// Body of a kernel thread, its return value becomes the exit code
+pub type KernelThreadFn = fn(usize) -> isize;
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ProgramStatus {
     Running,
@@ -56,6 +80,41 @@ pub enum ProgramStatus {
     Dead,
 }
 
+/// A snapshot of a process, used by procfs and process listings
+#[derive(Debug, Clone)]
+pub struct ProcessInfo {
+    pub pid: ProcessId,
+    pub ppid: Option<ProcessId>,
+    pub name: String,
+    pub status: ProgramStatus,
+    pub ticks: usize,
+    /// Stack and code pages in bytes
+    pub memory: usize,
+    pub exit_code: Option<isize>,
+}
+
+impl ProcessInfo {
+    /// Convert to the record written by `ListProcess`
+    pub fn to_record(&self) -> ProcessRecord {
+        let status = match self.status {
+            ProgramStatus::Running => ProcessStatus::Running,
+            ProgramStatus::Ready => ProcessStatus::Ready,
+            ProgramStatus::Blocked => ProcessStatus::Blocked,
+            ProgramStatus::Dead => ProcessStatus::Dead,
+        };
+
+        let mut record = ProcessRecord::new(
+            self.pid.0,
+            self.ppid.map(|p| p.0).unwrap_or(0),
+            &self.name,
+            status,
+        );
+        record.ticks = self.ticks as u64;
+        record.memory = self.memory as u64;
+        record
+    }
+}
+
 /// init process manager
 pub fn init(boot_info: &'static boot::BootInfo) {
     let mut kproc_data = ProcessData::new();
@@ -87,12 +146,57 @@ pub fn switch(context: &mut ProcessContext) {
     });
 }
 
-// pub fn spawn_kernel_thread(entry: fn() -> !, name: String, data: Option<ProcessData>) -> ProcessId {
-//     x86_64::instructions::interrupts::without_interrupts(|| {
-//         let entry = VirtAddr::new(entry as usize as u64);
-//         get_process_manager().spawn_kernel_thread(entry, name, data)
-//     })
-// }
+/// Give the current CPU its idle process, see `ProcessManager::add_idle`
+pub fn init_idle() -> ProcessId {
+    x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().add_idle())
+}
+
+/// Start `entry(arg)` in a new kernel thread named `name`
+///
+/// The thread runs in ring 0 on the kernel page table with a stack of its
+/// own, and exits with the return value of `entry`.
+pub fn spawn_kernel_thread(name: &str, entry: KernelThreadFn, arg: usize) -> ProcessId {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let start = VirtAddr::new(kernel_thread_start as usize as u64);
+        get_process_manager().spawn_kernel_thread(
+            start,
+            [entry as usize, arg],
+            name.to_string(),
+            None,
+        )
+    })
+}
+
+extern "C" fn kernel_thread_start(entry: usize, arg: usize) -> ! {
+    let entry: KernelThreadFn = unsafe { core::mem::transmute(entry) };
+    process_exit(entry(arg))
+}
+
+/// Wait for a kernel thread to exit
+///
+/// Sleeps with `hlt`, so interrupts must be enabled. Its stack is freed
+/// once its CPU has switched away. Returns the exit code, `None` if there
+/// is no such process.
+pub fn join_kernel_thread(pid: ProcessId) -> Option<isize> {
+    let proc = x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().get_proc(&pid)
+    })?;
+
+    loop {
+        let ret = x86_64::instructions::interrupts::without_interrupts(|| {
+            let inner = proc.read();
+            if inner.status() != ProgramStatus::Dead || processor::is_running(pid) {
+                return None;
+            }
+            inner.exit_code()
+        });
+
+        if ret.is_some() {
+            return ret;
+        }
+        x86_64::instructions::hlt();
+    }
+}
 
 pub fn print_process_list() {
     x86_64::instructions::interrupts::without_interrupts(|| {
@@ -100,6 +204,31 @@ pub fn print_process_list() {
     })
 }
 
+pub fn process_list() -> Vec<ProcessInfo> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager()
+            .processes()
+            .iter()
+            .map(|p| p.info())
+            .collect()
+    })
+}
+
+pub fn process_info(pid: ProcessId) -> Option<ProcessInfo> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().get_proc(&pid).map(|p| p.info())
+    })
+}
+
+/// Run `f` on the data of process `pid`, `None` if it is gone
+pub fn with_process_data<T>(pid: ProcessId, f: impl FnOnce(&ProcessData) -> T) -> Option<T> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let proc = get_process_manager().get_proc(&pid)?;
+        let inner = proc.read();
+        inner.data().map(f)
+    })
+}
+
 pub fn env(key: &str) -> Option<String> {
     x86_64::instructions::interrupts::without_interrupts(|| {
         // FIXME: get current process's environment variable
@@ -140,19 +269,81 @@ pub fn list_app() {
             .collect::<Vec<&str>>()
             .join(", ");
 
-        // TODO: print more information like size, entry point, etc.
-
         println!("[+] App list: {}", apps);
     });
 }
 
-pub fn spawn(name: &str) -> Option<ProcessId> {
-    info!("start to spawn");
-    let app = x86_64::instructions::interrupts::without_interrupts(|| {
-        let app_list = get_process_manager().app_list()?;
-        app_list.iter().find(|&app| app.name.eq(name))
-    })?;
-    elf_spawn(name.to_string(), &app.elf)
+/// Describe every preloaded app, empty for diskless boots without apps
+pub fn app_records() -> Vec<AppRecord> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager()
+            .app_list()
+            .map(|list| list.iter().map(app_record).collect())
+            .unwrap_or_default()
+    })
+}
+
+fn app_record(app: &boot::App) -> AppRecord {
+    use xmas_elf::program::Type;
+
+    let mut record = AppRecord::new(&app.name);
+    record.size = app.elf.input.len() as u64;
+    record.entry = app.elf.header.pt2.entry_point();
+
+    for segment in app.elf.program_iter() {
+        if segment.get_type() != Ok(Type::Load) {
+            continue;
+        }
+        // whole pages get mapped, as in `elf::load_elf`
+        let start = segment.virtual_addr() & !(PAGE_SIZE - 1);
+        let end = (segment.virtual_addr() + segment.mem_size()).next_multiple_of(PAGE_SIZE);
+        record.segments += 1;
+        record.memory += end - start;
+    }
+
+    record
+}
+
+/// Spawn a program by path, e.g. `/APP/sh` or `../bin/app`
+///
+/// A bare name (no `/`) is looked up in `/APP` on the root filesystem.
+/// If the disk has no such file the preloaded boot `AppList` is tried,
+/// so diskless boots still work.
+pub fn spawn(path: &str) -> Option<ProcessId> {
+    let name = crate::fs::path::split_parent(path).1;
+    if name.is_empty() {
+        return None;
+    }
+
+    match load_from_disk(path) {
+        Ok(data) => {
+            let elf = ElfFile::new(&data)
+                .map_err(|e| warn!("Invalid ELF {}: {}", path, e))
+                .ok()?;
+            elf_spawn(name.to_string(), &elf)
+        }
+        Err(e) => {
+            debug!("{} not found on disk: {:?}, trying app list", path, e);
+            let app = x86_64::instructions::interrupts::without_interrupts(|| {
+                let app_list = get_process_manager().app_list()?;
+                app_list.iter().find(|&app| app.name.eq(name))
+            })?;
+            elf_spawn(name.to_string(), &app.elf)
+        }
+    }
+}
+
+fn load_from_disk(path: &str) -> crate::fs::FsResult<Vec<u8>> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let path = if path.contains('/') {
+            let current = get_process_manager().current();
+            let cwd = current.read().cwd().to_string();
+            crate::fs::path::normalize(&cwd, path)
+        } else {
+            crate::fs::path::join(&["APP", path])
+        };
+        crate::fs::read_all(&path)
+    })
 }
 
 pub fn elf_spawn(name: String, elf: &ElfFile) -> Option<ProcessId> {
@@ -191,6 +382,15 @@ pub fn exit(id: isize, context: &mut ProcessContext) {
     })
 }
 
+/// Block the current process until `pid` exits
+///
+/// It keeps running until the next switch, so `hlt` right after this.
+pub fn wait_exit(pid: ProcessId) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().wait_exit(pid)
+    })
+}
+
 #[inline]
 pub fn still_alive(pid: ProcessId) -> bool {
     x86_64::instructions::interrupts::without_interrupts(|| {
@@ -215,55 +415,588 @@ pub fn fork(context: &mut ProcessContext) -> u16 {
     })
 }
 
+/// Start a user thread of the current process at `entry(arg)`
+pub fn thread_create(entry: usize, arg: usize) -> Option<ProcessId> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let entry = VirtAddr::try_new(entry as u64).ok()?;
+        get_process_manager().spawn_thread(entry, arg)
+    })
+}
+
+fn proc_or_current(pid: ProcessId) -> Option<Arc<Process>> {
+    let manager = get_process_manager();
+    if pid.0 == 0 {
+        Some(manager.current())
+    } else {
+        manager.get_proc(&pid)
+    }
+}
+
+/// Let `pid`, 0 for the caller, run only on the CPUs in `mask`
+///
+/// Fails if none of them is online. A running process moves when it is
+/// queued the next time, at the latest on the next tick.
+pub fn set_affinity(pid: ProcessId, mask: u64) -> bool {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        match proc_or_current(pid) {
+            Some(proc) if mask & processor::online_mask() != 0 => {
+                proc.set_affinity(mask);
+                true
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Online CPUs `pid`, 0 for the caller, may run on
+pub fn get_affinity(pid: ProcessId) -> Option<u64> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        proc_or_current(pid).map(|proc| proc.affinity() & processor::online_mask())
+    })
+}
+
+/// Nanoseconds the caller's process, all of its threads together, has run
+pub fn process_cpu_time_ns() -> u64 {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let manager = get_process_manager();
+        let now = crate::clock::monotonic_ns();
+        let leader = match manager.get_proc(&manager.current().tgid()) {
+            Some(leader) => leader,
+            None => return 0,
+        };
+
+        let inner = leader.read();
+        inner.cpu_time(now)
+            + inner
+                .threads()
+                .iter()
+                .map(|t| t.read().cpu_time(now))
+                .sum::<u64>()
+    })
+}
+
+/// Wait for thread `tid` of the current process to exit
+///
+/// The exit code is returned in rax, right away if the thread is already
+/// dead, otherwise when it exits. `THREAD_JOIN_ERROR` if `tid` is not
+/// a thread of this process or someone else is joining it.
+pub fn thread_join(tid: ProcessId, context: &mut ProcessContext) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let manager = get_process_manager();
+        let current = manager.current();
+        let error = THREAD_JOIN_ERROR as usize;
+
+        let thread = match manager.get_proc(&tid) {
+            Some(thread) if tid != current.pid() => thread,
+            _ => return context.set_rax(error),
+        };
+
+        let mut inner = thread.write();
+        if inner.leader() != Some(current.tgid()) || inner.joiner().is_some() {
+            return context.set_rax(error);
+        }
+        if inner.status() == ProgramStatus::Dead {
+            return context.set_rax(inner.exit_code().unwrap_or(0) as usize);
+        }
+
+        // woken up by `ProcessManager::kill` with the exit code
+        inner.set_joiner(current.pid());
+        drop(inner);
+
+        manager.save_current(context);
+        manager.block(current.pid());
+        manager.switch_next(context);
+    })
+}
+
+/// Exit the current thread, for a process this is a process exit that
+/// takes all of its threads down as well
+pub fn thread_exit(ret: isize, context: &mut ProcessContext) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let manager = get_process_manager();
+        manager.kill_current(ret);
+        manager.switch_next(context);
+    })
+}
+
+/// Set `FS_BASE` of the current thread, only user addresses are allowed
+pub fn set_fs_base(addr: u64) -> bool {
+    if addr >= USER_SPACE_END {
+        return false;
+    }
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().current().write().set_fs_base(addr);
+    });
+    true
+}
+
+pub fn fs_base() -> u64 {
+    x86_64::registers::model_specific::FsBase::read().as_u64()
+}
+
+/// Physical address of the futex word at `addr` in the current address
+/// space, `None` if it is unaligned or not mapped
+fn futex_key(addr: usize) -> Option<u64> {
+    use x86_64::structures::paging::Translate;
+
+    let addr = VirtAddr::try_new(addr as u64).ok()?;
+    if !addr.is_aligned(4u64) || addr.as_u64() >= USER_SPACE_END {
+        return None;
+    }
+    let phys = PageTableContext::new().mapper().translate_addr(addr)?;
+    Some(phys.as_u64())
+}
+
+/// Wake up a process waiting in a syscall, false if it is gone already
+///
+/// It may not have blocked yet when it joined the wait queue on another
+/// CPU, then it won't block at all.
+fn wakeup_blocked(manager: &ProcessManager, pid: ProcessId) -> bool {
+    let Some(proc) = manager.get_proc(&pid) else {
+        return false;
+    };
+    let mut inner = proc.write();
+    if inner.status() == ProgramStatus::Dead {
+        return false;
+    }
+    let blocked = inner.wake();
+    drop(inner);
+    if blocked {
+        manager.push_ready(pid);
+    }
+    true
+}
+
+/// `FUTEX_WAIT`: sleep while `*addr == val`
+///
+/// Returns 0 when woken up, 1 right away if the value has changed.
+pub fn futex_wait(addr: usize, val: u32, context: &mut ProcessContext) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let Some(key) = futex_key(addr) else {
+            return context.set_rax(usize::MAX);
+        };
+
+        // held from the compare to the enqueue, so a wake in between
+        // can't be missed
+        let mut futexes = futex::FUTEXES.lock();
+        let word = unsafe { &*(addr as *const core::sync::atomic::AtomicU32) };
+        if word.load(core::sync::atomic::Ordering::SeqCst) != val {
+            return context.set_rax(1);
+        }
+
+        let manager = get_process_manager();
+        let pid = processor::get_pid();
+        futexes.wait(key, pid);
+        drop(futexes);
+
+        // this is what the thread sees once it is woken up
+        context.set_rax(0);
+        manager.save_current(context);
+        manager.block(pid);
+        manager.switch_next(context);
+    })
+}
+
+/// `FUTEX_WAKE`: wake up at most `count` waiters, returns how many
+pub fn futex_wake(addr: usize, count: usize) -> usize {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let Some(key) = futex_key(addr) else {
+            return usize::MAX;
+        };
+
+        let manager = get_process_manager();
+        let mut woken = 0;
+        while woken < count {
+            let waiters = futex::FUTEXES.lock().wake(key, count - woken);
+            if waiters.is_empty() {
+                break;
+            }
+            woken += waiters
+                .into_iter()
+                .filter(|&pid| wakeup_blocked(manager, pid))
+                .count();
+        }
+        woken
+    })
+}
+
+/// `FUTEX_REQUEUE`: wake up one waiter of `addr` and move the others to
+/// the futex at `target`, so they wake up one by one from there
+pub fn futex_requeue(addr: usize, target: usize) -> usize {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let (Some(from), Some(to)) = (futex_key(addr), futex_key(target)) else {
+            return usize::MAX;
+        };
+
+        let first = futex::FUTEXES.lock().requeue(from, to);
+        match first {
+            Some(pid) if wakeup_blocked(get_process_manager(), pid) => 1,
+            _ => 0,
+        }
+    })
+}
+
+/// Block the current process, it runs the syscall again when woken up
+///
+/// A wakeup that comes in after it joined the wait queue but before it
+/// gets to `block` is not lost, see `wakeup_blocked`.
+fn block_and_restart(context: &mut ProcessContext) {
+    let manager = get_process_manager();
+    let pid = processor::get_pid();
+
+    context.restart_syscall();
+    manager.save_current(context);
+    manager.block(pid);
+    manager.switch_next(context);
+}
+
+fn wakeup_all(waiters: Vec<ProcessId>) {
+    let manager = get_process_manager();
+    for pid in waiters {
+        wakeup_blocked(manager, pid);
+    }
+}
+
+/// Find or create the message queue of `key`, returns its id
+pub fn msg_get(key: u32, flags: IpcFlags) -> isize {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        match msg::MSG_QUEUES.lock().get(key, flags) {
+            Ok(id) => id as isize,
+            Err(e) => e.as_code(),
+        }
+    })
+}
+
+/// Send the message described by `buf`, blocks while the queue is full
+pub fn msg_send(id: u32, buf: &MsgBuf, flags: IpcFlags, context: &mut ProcessContext) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let data = unsafe { core::slice::from_raw_parts(buf.ptr as *const u8, buf.len as usize) };
+        let pid = processor::get_pid();
+
+        let ret = msg::MSG_QUEUES.lock().send(id, buf.mtype, data, flags, pid);
+        match ret {
+            Ok(waiters) => {
+                context.set_rax(0);
+                wakeup_all(waiters);
+            }
+            Err(IpcError::WouldBlock) => block_and_restart(context),
+            Err(e) => context.set_rax(e.as_code() as usize),
+        }
+    })
+}
+
+/// Receive into the buffer described by `buf` and write back the type,
+/// returns the length; blocks until there is a matching message
+pub fn msg_receive(id: u32, buf: &mut MsgBuf, flags: IpcFlags, context: &mut ProcessContext) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let data = unsafe { core::slice::from_raw_parts_mut(buf.ptr as *mut u8, buf.len as usize) };
+        let pid = processor::get_pid();
+
+        let ret = msg::MSG_QUEUES
+            .lock()
+            .receive(id, buf.mtype, data, flags, pid);
+        match ret {
+            Ok((mtype, len, waiters)) => {
+                buf.mtype = mtype;
+                context.set_rax(len);
+                wakeup_all(waiters);
+            }
+            Err(IpcError::WouldBlock) => block_and_restart(context),
+            Err(e) => context.set_rax(e.as_code() as usize),
+        }
+    })
+}
+
+/// Read of a message queue fd, takes the first message without blocking
+pub fn msg_read(id: u32, buf: &mut [u8]) -> Option<usize> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let flags = IpcFlags::NOWAIT | IpcFlags::NOERROR;
+        let pid = processor::get_pid();
+
+        let ret = msg::MSG_QUEUES.lock().receive(id, 0, buf, flags, pid);
+        match ret {
+            Ok((_, len, waiters)) => {
+                wakeup_all(waiters);
+                Some(len)
+            }
+            Err(IpcError::NoMessage) => Some(0),
+            Err(_) => None,
+        }
+    })
+}
+
+/// Write of a message queue fd, sends a message of type 1 without blocking
+pub fn msg_write(id: u32, buf: &[u8]) -> Option<usize> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let pid = processor::get_pid();
+
+        let ret = msg::MSG_QUEUES.lock().send(id, 1, buf, IpcFlags::NOWAIT, pid);
+        match ret {
+            Ok(waiters) => {
+                wakeup_all(waiters);
+                Some(buf.len())
+            }
+            Err(IpcError::Again) => Some(0),
+            Err(_) => None,
+        }
+    })
+}
+
+/// `IPC_STAT`, `IPC_SET` (capacity), `IPC_RMID` or `IPC_OPEN` on a message
+/// queue, `IPC_OPEN` returns the new fd
+pub fn msg_ctl(id: u32, cmd: usize, stat: *mut MsqStat) -> isize {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        if cmd == IPC_OPEN {
+            if let Err(e) = msg::MSG_QUEUES.lock().stat(id) {
+                return e.as_code();
+            }
+            return match get_process_manager().current().read().open(Resource::MsgQueue(id)) {
+                Some(fd) => fd as isize,
+                None => IpcError::TooManyFiles.as_code(),
+            };
+        }
+
+        let mut queues = msg::MSG_QUEUES.lock();
+        let ret = match cmd {
+            IPC_STAT => match unsafe { stat.as_mut() } {
+                Some(stat) => queues.stat(id).map(|s| *stat = s),
+                None => Err(IpcError::InvalidArgument),
+            },
+            IPC_SET => match unsafe { stat.as_ref() } {
+                Some(stat) => queues
+                    .set_max_bytes(id, stat.max_bytes as usize)
+                    .map(wakeup_all),
+                None => Err(IpcError::InvalidArgument),
+            },
+            IPC_RMID => queues.remove(id).map(wakeup_all),
+            _ => Err(IpcError::InvalidArgument),
+        };
+
+        match ret {
+            Ok(()) => 0,
+            Err(e) => e.as_code(),
+        }
+    })
+}
+
+fn uptime_ms() -> i64 {
+    crate::utils::clock::uptime_ms() as i64
+}
+
+/// Wait until one of `fds` is ready or `timeout` milliseconds have passed,
+/// a negative timeout waits forever; returns the number of ready entries
+pub fn poll(fds: &mut [PollFd], timeout: isize, context: &mut ProcessContext) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let pid = processor::get_pid();
+        let resources = get_process_manager().current().read().resources.clone();
+
+        let mut ready = 0;
+        {
+            let resources = resources.read();
+            for pfd in fds.iter_mut() {
+                pfd.revents = match u8::try_from(pfd.fd) {
+                    Ok(fd) => resources.poll(fd, pfd.events),
+                    Err(_) if pfd.fd < 0 => PollEvents::empty(),
+                    Err(_) => PollEvents::NVAL,
+                };
+                if !pfd.revents.is_empty() {
+                    ready += 1;
+                }
+            }
+        }
+
+        let mut waiters = poll::POLL_WAITERS.lock();
+        if ready > 0 || timeout == 0 {
+            waiters.remove(pid);
+            context.set_rax(ready);
+            return;
+        }
+
+        let now = uptime_ms();
+        let deadline = match waiters.get(pid) {
+            // woken up by the timer, keep the deadline of the first call
+            Some(waiter) => waiter.deadline,
+            None if timeout > 0 => Some(now + timeout as i64),
+            None => None,
+        };
+        if deadline.is_some_and(|d| now >= d) {
+            waiters.remove(pid);
+            context.set_rax(0);
+            return;
+        }
+
+        let fds = fds
+            .iter()
+            .filter_map(|pfd| Some((u8::try_from(pfd.fd).ok()?, pfd.events)))
+            .collect();
+        waiters.insert(pid, poll::PollWaiter::new(resources, fds, deadline));
+        drop(waiters);
+
+        block_and_restart(context);
+    })
+}
+
+/// Wake up pollers that have a ready fd or ran out of time, called by
+/// the timer on every tick
+pub fn poll_tick() {
+    let Some(waiters) = poll::POLL_WAITERS.try_lock() else {
+        return;
+    };
+    if waiters.is_empty() {
+        return;
+    }
+    let due = waiters.due(uptime_ms);
+    drop(waiters);
+
+    // pollers stay registered while they run, so only wake the blocked
+    // ones; one that is not blocked yet is due again on the next tick
+    let manager = get_process_manager();
+    for pid in due {
+        if manager.get_proc(&pid).is_some_and(|p| p.read().status() == ProgramStatus::Blocked) {
+            wakeup_blocked(manager, pid);
+        }
+    }
+}
+
+/// Monotonic time an idle CPU has to look at the scheduler again, `None`
+/// if only an interrupt can bring it work
+pub fn next_wakeup() -> Option<u64> {
+    let now = crate::clock::monotonic_ns();
+    let tick = now + crate::clock::tick_ns();
+
+    // queued work another CPU hasn't got to yet, come back to steal it
+    let this = processor::current_id();
+    if processor::online().any(|(i, p)| i != this && p.queue_len() > 0) {
+        return Some(tick);
+    }
+
+    match poll::POLL_WAITERS.try_lock() {
+        Some(waiters) => waiters.next_deadline().map(|ms| ms.max(0) as u64 * 1_000_000),
+        // being changed, look again on the next tick
+        None => Some(tick),
+    }
+}
+
+fn ipc_ret<T: Into<u64>>(ret: msg::IpcResult<T>) -> isize {
+    match ret {
+        Ok(val) => val.into() as isize,
+        Err(e) => e.as_code(),
+    }
+}
+
+/// Find or create the shared memory segment of `key`, returns its id
+pub fn shm_get(key: u32, size: usize, flags: IpcFlags) -> isize {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        ipc_ret(shm::SHM_TABLE.lock().get(key, size, flags))
+    })
+}
+
+/// Map segment `id` into the current process, at `addr` or anywhere if
+/// it is 0, returns the address
+pub fn shm_attach(id: u32, addr: u64) -> isize {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let ret = get_process_manager()
+            .current()
+            .write()
+            .with_shm(|space, mapper| space.attach(id, addr, mapper));
+        ipc_ret(ret.unwrap_or(Err(IpcError::InvalidArgument)))
+    })
+}
+
+/// Unmap the segment mapped at `addr`
+pub fn shm_detach(addr: u64) -> isize {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let ret = get_process_manager()
+            .current()
+            .write()
+            .with_shm(|space, mapper| space.detach(addr, mapper));
+        ipc_ret(ret.unwrap_or(Err(IpcError::InvalidArgument)).map(|_| 0u64))
+    })
+}
+
+/// `IPC_STAT` or `IPC_RMID` on a shared memory segment, `stat` is the
+/// user buffer of `IPC_STAT`
+pub fn shm_ctl(id: u32, cmd: usize, stat: *mut ShmStat) -> isize {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut table = shm::SHM_TABLE.lock();
+        let ret = match cmd {
+            IPC_STAT => match unsafe { stat.as_mut() } {
+                Some(stat) => table.stat(id).map(|s| *stat = s),
+                None => Err(IpcError::InvalidArgument),
+            },
+            IPC_RMID => table.remove(id),
+            _ => Err(IpcError::InvalidArgument),
+        };
+        ipc_ret(ret.map(|_| 0u64))
+    })
+}
+
+/// Return code of the `Sem` syscall for `ret`
+fn sem_ret(ret: SemaphoreResult) -> usize {
+    match ret {
+        SemaphoreResult::Ok => SEM_OK,
+        SemaphoreResult::NotExist => SEM_NOT_FOUND,
+        SemaphoreResult::AlreadyExists => SEM_EXISTS,
+        // Block and WakeUp are handled by the callers
+        ret => {
+            warn!("Unexpected semaphore result: {:?}", ret);
+            usize::MAX
+        }
+    }
+}
+
+/// Run `f` on the semaphores of the current process
+fn with_semaphores(f: impl FnOnce(&mut SemaphoreSet) -> SemaphoreResult) -> SemaphoreResult {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().current().read().with_semaphores(f)
+    })
+}
+
 pub fn sem_wait(key: u32, context: &mut ProcessContext) {
     x86_64::instructions::interrupts::without_interrupts(|| {
         let manager = get_process_manager();
         let pid = processor::current().get_pid().unwrap();
-        let ret = manager.current().write().sem_wait(key, pid);
+        let ret = with_semaphores(|sems| sems.wait(key, pid));
         match ret {
-            SemaphoreResult::Ok => context.set_rax(0),
-            SemaphoreResult::NotExist => context.set_rax(1),
             SemaphoreResult::Block(pid) => {
                 // FIXME: save, block it, then switch to next
                 //        maybe use `save_current` and `switch_next`
+                // woken up by a signal, that is a successful wait
+                context.set_rax(SEM_OK);
                 manager.save_current(context);
                 manager.block(pid);
                 manager.switch_next(context);
             }
-            _ => unreachable!(),
+            ret => context.set_rax(sem_ret(ret)),
         }
     })
 }
 
 pub fn sem_signal(key: u32, context: &mut ProcessContext) {
     x86_64::instructions::interrupts::without_interrupts(|| {
-        let manager = get_process_manager();
-        let ret = manager.current().write().sem_signal(key);
-        match ret{
-            SemaphoreResult::WakeUp(pid) => manager.wakeup(pid),
-            SemaphoreResult::Ok => context.set_rax(114),
-            SemaphoreResult::NotExist => context.set_rax(514), // any
-            _ => unreachable!(),
+        let ret = with_semaphores(|sems| sems.signal(key));
+        match ret {
+            SemaphoreResult::WakeUp(pid) => {
+                wakeup_blocked(get_process_manager(), pid);
+                context.set_rax(SEM_OK);
+            }
+            ret => context.set_rax(sem_ret(ret)),
         }
     })
 }
 
 pub fn new_sem(key: u32, value: usize) -> usize {
-    x86_64::instructions::interrupts::without_interrupts(|| {
-        if get_process_manager().current().write().new_sem(key, value) {
-            0
-        } else {
-            1
-        }
-    })
+    sem_ret(with_semaphores(|sems| sems.insert(key, value)))
+}
+
+pub fn open_sem(key: u32) -> usize {
+    sem_ret(with_semaphores(|sems| sems.open(key)))
+}
+
+pub fn close_sem(key: u32) -> usize {
+    sem_ret(with_semaphores(|sems| sems.close(key)))
 }
 
 pub fn remove_sem(key: u32) -> usize {
-    x86_64::instructions::interrupts::without_interrupts(|| {
-        if get_process_manager().current().write().remove_sem(key) {
-            0
-        } else {
-            1
-        }
-    })
-}
\ No newline at end of file
+    sem_ret(with_semaphores(|sems| sems.remove(key)))
+}
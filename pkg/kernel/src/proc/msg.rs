@@ -0,0 +1,266 @@
+//! System V style message queues
+//!
+//! Queues are global and found by a `u32` key, see `MsgGet`. A process
+//! that would block is put on the waiter list of the queue and runs the
+//! syscall again once anything happens to it.
+
+use super::ProcessId;
+use crate::fs::PollEvents;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+use spin::Mutex;
+use syscall_def::ipc::*;
+
+pub static MSG_QUEUES: Mutex<MsgQueueTable> = Mutex::new(MsgQueueTable::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpcError {
+    NotFound,
+    AlreadyExists,
+    InvalidArgument,
+    /// The message is bigger than the receive buffer
+    TooBig,
+    /// Nothing to receive, with `NOWAIT`
+    NoMessage,
+    /// No room to send, with `NOWAIT`
+    Again,
+    /// Out of frames or address space
+    NoMemory,
+    /// No free file descriptor for `IPC_OPEN`
+    TooManyFiles,
+    /// Nothing to receive or no room to send, the caller should block
+    WouldBlock,
+}
+
+pub type IpcResult<T = usize> = Result<T, IpcError>;
+
+impl IpcError {
+    /// Negative code returned in `rax` for a failed syscall
+    pub fn as_code(&self) -> isize {
+        -(match self {
+            IpcError::NotFound => 2,
+            IpcError::TooBig => 7,
+            IpcError::Again | IpcError::WouldBlock => 11,
+            IpcError::NoMemory => 12,
+            IpcError::AlreadyExists => 17,
+            IpcError::TooManyFiles => 24,
+            IpcError::InvalidArgument => 22,
+            IpcError::NoMessage => 42,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Message {
+    mtype: i64,
+    data: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct MsgQueue {
+    key: u32,
+    messages: VecDeque<Message>,
+    bytes: usize,
+    max_bytes: usize,
+    sent: u64,
+    received: u64,
+    last_send_pid: Option<ProcessId>,
+    last_recv_pid: Option<ProcessId>,
+    /// Blocked senders and receivers, all of them retry on a change
+    waiters: Vec<ProcessId>,
+}
+
+impl MsgQueue {
+    fn new(key: u32) -> Self {
+        Self {
+            key,
+            messages: VecDeque::new(),
+            bytes: 0,
+            max_bytes: MSG_QUEUE_BYTES,
+            sent: 0,
+            received: 0,
+            last_send_pid: None,
+            last_recv_pid: None,
+            waiters: Vec::new(),
+        }
+    }
+
+    /// Index of the message `MsgRcv` takes for `mtype`
+    fn find(&self, mtype: i64) -> Option<usize> {
+        match mtype {
+            0 => (!self.messages.is_empty()).then_some(0),
+            t if t > 0 => self.messages.iter().position(|m| m.mtype == t),
+            t => {
+                let limit = t.checked_neg().unwrap_or(i64::MAX);
+                self.messages
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, m)| m.mtype <= limit)
+                    .min_by_key(|(i, m)| (m.mtype, *i))
+                    .map(|(i, _)| i)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MsgQueueTable {
+    queues: BTreeMap<u32, MsgQueue>,
+    next_id: u32,
+}
+
+impl MsgQueueTable {
+    pub const fn new() -> Self {
+        Self {
+            queues: BTreeMap::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Find the queue of `key` or create it, returns the queue id
+    pub fn get(&mut self, key: u32, flags: IpcFlags) -> IpcResult<u32> {
+        if key != IPC_PRIVATE {
+            if let Some((&id, _)) = self.queues.iter().find(|(_, q)| q.key == key) {
+                if flags.contains(IpcFlags::CREATE | IpcFlags::EXCL) {
+                    return Err(IpcError::AlreadyExists);
+                }
+                return Ok(id);
+            }
+            if !flags.contains(IpcFlags::CREATE) {
+                return Err(IpcError::NotFound);
+            }
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.queues.insert(id, MsgQueue::new(key));
+        debug!("Message queue #{} created for key {:#x}", id, key);
+        Ok(id)
+    }
+
+    /// Append a message, returns the processes to wake up
+    pub fn send(
+        &mut self,
+        id: u32,
+        mtype: i64,
+        data: &[u8],
+        flags: IpcFlags,
+        pid: ProcessId,
+    ) -> IpcResult<Vec<ProcessId>> {
+        let queue = self.queues.get_mut(&id).ok_or(IpcError::NotFound)?;
+        if mtype <= 0 || data.len() > MSG_MAX || data.len() > queue.max_bytes {
+            return Err(IpcError::InvalidArgument);
+        }
+
+        if queue.bytes + data.len() > queue.max_bytes {
+            return Err(if flags.contains(IpcFlags::NOWAIT) {
+                IpcError::Again
+            } else {
+                queue.waiters.push(pid);
+                IpcError::WouldBlock
+            });
+        }
+
+        queue.messages.push_back(Message {
+            mtype,
+            data: data.to_vec(),
+        });
+        queue.bytes += data.len();
+        queue.sent += 1;
+        queue.last_send_pid = Some(pid);
+
+        Ok(core::mem::take(&mut queue.waiters))
+    }
+
+    /// Take a message of `mtype` into `buf`, returns its type, length and
+    /// the processes to wake up
+    pub fn receive(
+        &mut self,
+        id: u32,
+        mtype: i64,
+        buf: &mut [u8],
+        flags: IpcFlags,
+        pid: ProcessId,
+    ) -> IpcResult<(i64, usize, Vec<ProcessId>)> {
+        let queue = self.queues.get_mut(&id).ok_or(IpcError::NotFound)?;
+
+        let Some(index) = queue.find(mtype) else {
+            return Err(if flags.contains(IpcFlags::NOWAIT) {
+                IpcError::NoMessage
+            } else {
+                queue.waiters.push(pid);
+                IpcError::WouldBlock
+            });
+        };
+
+        let len = queue.messages[index].data.len();
+        if len > buf.len() && !flags.contains(IpcFlags::NOERROR) {
+            return Err(IpcError::TooBig);
+        }
+
+        let message = queue.messages.remove(index).unwrap();
+        let count = len.min(buf.len());
+        buf[..count].copy_from_slice(&message.data[..count]);
+
+        queue.bytes -= len;
+        queue.received += 1;
+        queue.last_recv_pid = Some(pid);
+
+        Ok((message.mtype, count, core::mem::take(&mut queue.waiters)))
+    }
+
+    /// Delete a queue, returns the processes to wake up
+    pub fn remove(&mut self, id: u32) -> IpcResult<Vec<ProcessId>> {
+        let queue = self.queues.remove(&id).ok_or(IpcError::NotFound)?;
+        debug!("Message queue #{} removed", id);
+        Ok(queue.waiters)
+    }
+
+    pub fn stat(&self, id: u32) -> IpcResult<MsqStat> {
+        let queue = self.queues.get(&id).ok_or(IpcError::NotFound)?;
+        Ok(MsqStat {
+            key: queue.key,
+            id,
+            messages: queue.messages.len() as u64,
+            bytes: queue.bytes as u64,
+            max_bytes: queue.max_bytes as u64,
+            sent: queue.sent,
+            received: queue.received,
+            last_send_pid: queue.last_send_pid.map(|p| p.0).unwrap_or(0),
+            last_recv_pid: queue.last_recv_pid.map(|p| p.0).unwrap_or(0),
+            waiting: queue.waiters.len() as u16,
+        })
+    }
+
+    /// Change the capacity, senders waiting for room may fit now
+    pub fn set_max_bytes(&mut self, id: u32, max_bytes: usize) -> IpcResult<Vec<ProcessId>> {
+        let queue = self.queues.get_mut(&id).ok_or(IpcError::NotFound)?;
+        if max_bytes == 0 {
+            return Err(IpcError::InvalidArgument);
+        }
+        queue.max_bytes = max_bytes;
+        Ok(core::mem::take(&mut queue.waiters))
+    }
+
+    /// Readiness of a queue opened as a file descriptor
+    pub fn poll(&self, id: u32) -> PollEvents {
+        let Some(queue) = self.queues.get(&id) else {
+            return PollEvents::ERR | PollEvents::HUP;
+        };
+        let mut events = PollEvents::empty();
+        if !queue.messages.is_empty() {
+            events |= PollEvents::IN;
+        }
+        if queue.bytes < queue.max_bytes {
+            events |= PollEvents::OUT;
+        }
+        events
+    }
+
+    /// Forget a waiter that is being killed
+    pub fn cancel_wait(&mut self, pid: ProcessId) {
+        for queue in self.queues.values_mut() {
+            queue.waiters.retain(|&p| p != pid);
+        }
+    }
+}
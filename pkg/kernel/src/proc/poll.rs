@@ -0,0 +1,90 @@
+//! Processes blocked in `Poll`
+//!
+//! Nothing wakes a poller directly: the timer checks every waiter on each
+//! tick, and input interrupts after new data, and wakes those with a ready
+//! fd or a passed deadline. The woken process runs the syscall again,
+//! which fills in `revents`.
+
+use super::ProcessId;
+use crate::fs::PollEvents;
+use crate::resource::ResourceSet;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::{Mutex, RwLock};
+
+pub static POLL_WAITERS: Mutex<PollWaiters> = Mutex::new(PollWaiters::new());
+
+pub struct PollWaiter {
+    resources: Arc<RwLock<ResourceSet>>,
+    fds: Vec<(u8, PollEvents)>,
+    /// Uptime in milliseconds to give up at, `None` to wait forever
+    pub deadline: Option<i64>,
+}
+
+impl PollWaiter {
+    pub fn new(
+        resources: Arc<RwLock<ResourceSet>>,
+        fds: Vec<(u8, PollEvents)>,
+        deadline: Option<i64>,
+    ) -> Self {
+        Self {
+            resources,
+            fds,
+            deadline,
+        }
+    }
+
+    fn ready(&self) -> bool {
+        let Some(resources) = self.resources.try_read() else {
+            return false;
+        };
+        self.fds
+            .iter()
+            .any(|&(fd, events)| !resources.poll(fd, events).is_empty())
+    }
+}
+
+pub struct PollWaiters {
+    waiters: BTreeMap<ProcessId, PollWaiter>,
+}
+
+impl PollWaiters {
+    pub const fn new() -> Self {
+        Self {
+            waiters: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waiters.is_empty()
+    }
+
+    pub fn get(&self, pid: ProcessId) -> Option<&PollWaiter> {
+        self.waiters.get(&pid)
+    }
+
+    pub fn insert(&mut self, pid: ProcessId, waiter: PollWaiter) {
+        self.waiters.insert(pid, waiter);
+    }
+
+    pub fn remove(&mut self, pid: ProcessId) {
+        self.waiters.remove(&pid);
+    }
+
+    /// Waiters that should run again, `now` gives the uptime in
+    /// milliseconds
+    pub fn due(&self, now: impl FnOnce() -> i64) -> Vec<ProcessId> {
+        let now = now();
+        self.waiters
+            .iter()
+            .filter(|(_, w)| w.deadline.is_some_and(|d| now >= d) || w.ready())
+            .map(|(&pid, _)| pid)
+            .collect()
+    }
+
+    /// Earliest deadline of all waiters, in milliseconds of uptime
+    pub fn next_deadline(&self) -> Option<i64> {
+        self.waiters.values().filter_map(|w| w.deadline).min()
+    }
+}
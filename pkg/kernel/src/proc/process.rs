@@ -8,13 +8,20 @@ use x86_64::structures::paging::mapper::MapToError;
 use x86_64::structures::paging::page::PageRange;
 use x86_64::structures::paging::*;
 use alloc::sync::Arc;
-use crate::proc::sync::SemaphoreResult;
+use alloc::boxed::Box;
+use crate::proc::shm::ShmSpace;
+use crate::proc::sync::{SemaphoreResult, SemaphoreSet};
+use crate::proc::tls::{TlsBlock, TlsTemplate};
+use x86_64::registers::model_specific::FsBase;
+use core::sync::atomic::{AtomicU64, Ordering};
 
 
-#[derive(Clone)]
 pub struct Process {
     pid: ProcessId,
     inner: Arc<RwLock<ProcessInner>>,
+    /// Mask of the CPUs it may run on, outside `inner` so the scheduler
+    /// can read it while the process is locked
+    affinity: AtomicU64,
 }
 
 pub struct ProcessInner {
@@ -22,11 +29,29 @@ pub struct ProcessInner {
     parent: Option<Weak<Process>>,
     children: Vec<Arc<Process>>,
     ticks_passed: usize,
+    /// Nanoseconds spent running, not counting the current run
+    cpu_time: u64,
+    /// Monotonic time it was switched in, 0 while switched out
+    run_since: u64,
     status: ProgramStatus,
+    /// Woken up before it got to `block`, the next `block` is skipped
+    wakeup_pending: bool,
     exit_code: Option<isize>,
     context: ProcessContext,
     page_table: Option<PageTableContext>,
     proc_data: Option<ProcessData>,
+    /// Heap allocated stack of a kernel thread, freed when it is reaped
+    kernel_stack: Option<Box<[u8]>>,
+    /// Pid of the process a user thread belongs to, `None` for a process
+    leader: Option<ProcessId>,
+    /// User threads of this process, killed together with it
+    threads: Vec<Arc<Process>>,
+    /// Thread blocked in `ThreadJoin` until this one exits
+    joiner: Option<ProcessId>,
+    /// Thread local storage, `FS_BASE` points into it
+    tls_block: Option<TlsBlock>,
+    /// `FS_BASE` while the thread is switched out
+    fs_base: u64,
 }
 
 impl Process {
@@ -35,6 +60,17 @@ impl Process {
         self.pid
     }
 
+    /// Mask of the CPUs it may run on
+    #[inline]
+    pub fn affinity(&self) -> u64 {
+        self.affinity.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn set_affinity(&self, mask: u64) {
+        self.affinity.store(mask, Ordering::Relaxed);
+    }
+
     #[inline]
     pub fn write(&self) -> RwLockWriteGuard<ProcessInner> {
         self.inner.write()
@@ -62,10 +98,19 @@ impl Process {
             status: ProgramStatus::Ready,
             context: ProcessContext::default(),
             ticks_passed: 0,
+            cpu_time: 0,
+            run_since: 0,
             exit_code: Some(0),
             children: Vec::new(),
             page_table: Some(page_table),
             proc_data: Some(proc_data.unwrap_or_default()),
+            kernel_stack: None,
+            leader: None,
+            threads: Vec::new(),
+            joiner: None,
+            wakeup_pending: false,
+            tls_block: None,
+            fs_base: 0,
         };
 
         trace!("New process {}#{} created.", &inner.name, pid);
@@ -74,10 +119,14 @@ impl Process {
         Arc::new(Self {
             pid,
             inner: Arc::new(RwLock::new(inner)),
+            affinity: AtomicU64::new(AFFINITY_ALL),
         })
     }
 
-    pub fn kill(&self, ret: isize) {
+    /// Mark the process dead, and free it now unless a CPU is running it
+    ///
+    /// Returns that CPU, it frees the process when switching away.
+    pub fn kill(&self, ret: isize) -> Option<usize> {
         let mut inner = self.inner.write();
 
         debug!(
@@ -88,6 +137,12 @@ impl Process {
         );
 
         inner.kill(ret);
+        // under the lock held by `switch_to`, it can't start running now
+        let cpu = processor::running_on(self.pid);
+        if cpu.is_none() {
+            inner.reap();
+        }
+        cpu
     }
 
     // pub fn alloc_init_stack(&self) -> VirtAddr {
@@ -111,6 +166,24 @@ impl Process {
     //     VirtAddr::new(STACK_INIT_TOP - (pid-1)*STACK_MAX_SIZE) // pid从2开始算
     // }
 
+    /// Take a snapshot of the process for listings
+    pub fn info(&self) -> ProcessInfo {
+        let inner = self.inner.read();
+        ProcessInfo {
+            pid: self.pid,
+            ppid: inner.parent().map(|p| p.pid),
+            name: inner.name.clone(),
+            status: inner.status,
+            ticks: inner.ticks_passed,
+            memory: inner
+                .proc_data
+                .as_ref()
+                .map(|d| d.total_memory_usage())
+                .unwrap_or(0),
+            exit_code: inner.exit_code,
+        }
+    }
+
     pub fn get_data_mut(&self) -> RwLockWriteGuard<ProcessInner> {
         self.inner.write()
     }
@@ -129,6 +202,7 @@ impl Process {
         let child = Arc::new(Self {
             pid: ProcessId::new(),
             inner: Arc::new(RwLock::new(child_inner)),
+            affinity: AtomicU64::new(self.affinity()),
         });
         // FIXME: add child to current process's children list
         inner.children.push(Arc::clone(&child));
@@ -140,6 +214,41 @@ impl Process {
         child
     }
 
+    /// Pid of the thread group: the process itself, or the process a
+    /// thread belongs to
+    pub fn tgid(&self) -> ProcessId {
+        self.read().leader.unwrap_or(self.pid)
+    }
+
+    /// Create a user thread of this process starting at `entry(arg)`
+    ///
+    /// `leader` is the process owning the thread group, `self` may be
+    /// the leader or one of its threads.
+    pub fn new_thread(&self, leader: &Arc<Process>, entry: VirtAddr, arg: usize) -> Option<Arc<Self>> {
+        let tid = ProcessId::new();
+        // as big as the main stack has grown so far
+        let stack_pages = leader
+            .read()
+            .data()
+            .and_then(|data| data.stack_segment)
+            .map_or(STACK_DEF_PAGE, |stack| stack.count() as u64);
+        let inner = self.read().thread(
+            tid,
+            Arc::downgrade(leader),
+            leader.pid,
+            entry,
+            arg,
+            stack_pages,
+        )?;
+
+        trace!("New thread {}#{} of #{}", inner.name, tid, leader.pid);
+
+        Some(Arc::new(Self {
+            pid: tid,
+            inner: Arc::new(RwLock::new(inner)),
+            affinity: AtomicU64::new(self.affinity()),
+        }))
+    }
 }
 
 impl ProcessInner {
@@ -147,10 +256,23 @@ impl ProcessInner {
         &self.name
     }
 
+    /// Process data, `None` once the process has been killed
+    pub fn data(&self) -> Option<&ProcessData> {
+        self.proc_data.as_ref()
+    }
+
     pub fn tick(&mut self) {
         self.ticks_passed += 1;
     }
 
+    /// Nanoseconds spent running up to `now`
+    pub fn cpu_time(&self, now: u64) -> u64 {
+        match self.run_since {
+            0 => self.cpu_time,
+            since => self.cpu_time + now.saturating_sub(since),
+        }
+    }
+
     pub fn status(&self) -> ProgramStatus {
         self.status
     }
@@ -171,6 +293,11 @@ impl ProcessInner {
         self.page_table.as_ref().unwrap().clone_l4()
     }
 
+    /// Share this page table instead of copying it
+    pub fn page_table_fork(&self) -> PageTableContext {
+        self.page_table.as_ref().unwrap().fork()
+    }
+
     pub fn is_ready(&self) -> bool {
         self.status == ProgramStatus::Ready
     }
@@ -180,15 +307,41 @@ impl ProcessInner {
     pub(super) fn save(&mut self, context: &ProcessContext) {
         // FIXME: save the process's context
         self.context.save(context);
+        self.fs_base = FsBase::read().as_u64();
+        let now = crate::clock::monotonic_ns();
+        self.cpu_time = self.cpu_time(now);
+        self.run_since = 0;
         if self.status == ProgramStatus::Running {
             self.status = ProgramStatus::Ready;
         }
     }
 
     pub fn block(&mut self){
+        // joined a wait queue and was woken up before getting here
+        if core::mem::take(&mut self.wakeup_pending) || self.status == ProgramStatus::Dead {
+            return;
+        }
         self.status = ProgramStatus::Blocked;
     }
 
+    /// Wake up from a wait queue, true if it was blocked and must be
+    /// queued again
+    ///
+    /// One that is still on its way to `block` won't block at all.
+    pub fn wake(&mut self) -> bool {
+        match self.status {
+            ProgramStatus::Blocked => {
+                self.pause();
+                true
+            }
+            ProgramStatus::Dead => false,
+            _ => {
+                self.wakeup_pending = true;
+                false
+            }
+        }
+    }
+
     /// Restore the process's context
     /// mark the process as running
     pub(super) fn restore(&mut self, context: &mut ProcessContext) {
@@ -196,6 +349,8 @@ impl ProcessInner {
         self.context.restore(context);
         // FIXME: restore the process's page table
         self.page_table.as_ref().unwrap().load();
+        FsBase::write(VirtAddr::new_truncate(self.fs_base));
+        self.run_since = crate::clock::monotonic_ns();
         self.status = ProgramStatus::Running;
     }
 
@@ -208,9 +363,22 @@ impl ProcessInner {
         self.exit_code = Some(ret);
         // FIXME: set status to dead
         self.status = ProgramStatus::Dead;
+    }
+
+    /// Free what a dead process holds, once no CPU runs it any more
+    pub(super) fn reap(&mut self) {
         // FIXME: take and drop unused resources
-        self.proc_data.take();
+        self.free_thread_stack();
+        if let Some(proc_data) = self.proc_data.take() {
+            // the last one of the threads unmaps the shared memory
+            let shm = Arc::into_inner(proc_data.shm).map(RwLock::into_inner);
+            if let (Some(mut shm), Some(page_table)) = (shm, self.page_table.as_ref()) {
+                shm.detach_all(&mut page_table.mapper());
+            }
+        }
         self.page_table.take();
+        self.tls_block.take();
+        self.take_kernel_stack();
     }
 
     // 辅助函数，获取ProcessContext
@@ -226,6 +394,41 @@ impl ProcessInner {
         self.context.init_user_stack_frame(entry, stack_top);
     }
 
+    /// Give a kernel thread its own stack and start it at `entry(args)`
+    pub fn init_kernel_thread(&mut self, entry: VirtAddr, args: [usize; 2]) {
+        let stack = alloc::vec![0u8; KTHREAD_STACK_SIZE].into_boxed_slice();
+        let bottom = stack.as_ptr() as u64;
+        // as if `entry` was called: 16 byte aligned before the return address
+        let stack_top = ((bottom + KTHREAD_STACK_SIZE as u64) & !0xF) - 8;
+
+        self.context.init_stack_frame(entry, VirtAddr::new(stack_top));
+        self.context.set_args(args);
+
+        self.set_stack(
+            VirtAddr::new(bottom),
+            (KTHREAD_STACK_SIZE as u64).div_ceil(PAGE_SIZE),
+        );
+        self.kernel_stack = Some(stack);
+    }
+
+    /// Free the stack of a dead kernel thread
+    pub fn take_kernel_stack(&mut self) -> Option<Box<[u8]>> {
+        self.kernel_stack.take()
+    }
+
+
+    /// Unmap the stack of a dead thread, the page table outlives it
+    fn free_thread_stack(&mut self) {
+        if self.leader.is_none() {
+            return;
+        }
+        let Some(stack) = self.proc_data.as_mut().and_then(|data| data.stack_segment.take()) else {
+            return;
+        };
+        if let Some(page_table) = self.page_table.as_ref() {
+            crate::smp::tlb::free_frames(elf::unmap_range(stack, &mut page_table.mapper()));
+        }
+    }
 
     pub fn alloc_new_stack_page(&mut self,addr: VirtAddr){
         let alloc = &mut *get_frame_alloc_for_sure();
@@ -238,6 +441,10 @@ impl ProcessInner {
         let user_access = processor::current().get_pid().unwrap() != KERNEL_PID;
 
         let result = elf::map_range(addr.as_u64(), pages, page_table, alloc,user_access);
+        // 记录新的栈底, 下次从这里往下长, 线程退出时也要全部释放
+        if let Ok(range) = result {
+            self.proc_data.as_mut().unwrap().stack_segment = Some(Page::range(range.start, old_stack.end));
+        }
         // if result.is_err(){
         //     error!("map_range failed");
         // }
@@ -255,12 +462,11 @@ impl ProcessInner {
         let proc_data = self.proc_data.as_mut().unwrap();
         proc_data.stack_segment = Some(stack_segment);
         proc_data.code_segments = Some(code_segments);
+        proc_data.tls = TlsTemplate::from_elf(elf).map(Arc::new);
 
-        stack_bot
-    }
+        self.alloc_tls();
 
-    pub fn proc_data_read(&mut self, fd:u8, buf: &mut [u8]) -> isize{
-        self.proc_data.as_ref().expect("invalid proc_data").read(fd,buf)
+        stack_bot
     }
 
     // lab5
@@ -271,7 +477,7 @@ impl ProcessInner {
         let mut new_stack_base = old_stack_base - (self.children.len() as u64 + 1) * STACK_MAX_SIZE;
 
         // FIXME: clone the process data struct
-        let mut child_proc_data = self.proc_data.as_ref().unwrap().clone();
+        let mut child_proc_data = self.proc_data.as_ref().unwrap().fork();
 
         // FIXME: clone the page table context (see instructions)
         let page_table = self.page_table.as_ref().unwrap().clone_l4();
@@ -311,9 +517,18 @@ impl ProcessInner {
         // FIXME: construct the child process inner
         let child_page_table = self.page_table.as_ref().unwrap().fork();
 
+        // the child gets a copy of the TLS, like the rest of the stack
+        let tls_block = self.tls_block.as_ref().and_then(|b| b.duplicate());
+        let fs_base = match (&self.tls_block, &tls_block) {
+            (Some(old), Some(new)) if self.fs_base == old.thread_pointer() => new.thread_pointer(),
+            _ => self.fs_base,
+        };
+
         ProcessInner {
             name: self.name.clone(),
             ticks_passed: 0,
+            cpu_time: 0,
+            run_since: 0,
             proc_data: Some(child_proc_data),
             page_table: Some(child_page_table),
             context: child_context,
@@ -321,41 +536,160 @@ impl ProcessInner {
             children: Vec::new(),
             status: ProgramStatus::Ready,
             exit_code: Some(0),
+            kernel_stack: None,
+            leader: None,
+            threads: Vec::new(),
+            joiner: None,
+            wakeup_pending: false,
+            tls_block,
+            fs_base,
         }
         // NOTE: return inner because there's no pid record in inner
     }
 
-    pub fn sem_wait(&self, key: u32, pid: ProcessId) -> SemaphoreResult{
-        if let Some(proc_data) = &self.proc_data {
-            proc_data.semaphores.write().wait(key, pid)
-        } else {
-            SemaphoreResult::NotExist
-        }
+    /// Build the inner of a new thread
+    ///
+    /// Unlike `fork` nothing is copied: the page table is shared, and so
+    /// are env, fds and semaphores through the `Arc`s in the process
+    /// data. The thread gets a fresh stack in the slot of its own pid.
+    fn thread(
+        &self,
+        tid: ProcessId,
+        parent: Weak<Process>,
+        leader: ProcessId,
+        entry: VirtAddr,
+        arg: usize,
+        stack_pages: u64,
+    ) -> Option<ProcessInner> {
+        let page_table = self.page_table.as_ref()?.fork();
+        let mut proc_data = self.proc_data.as_ref()?.clone();
+
+        // the top of the slot of `tid`, growing down like the main stack;
+        // taken slots are skipped like the stack of a forked child
+        let mut mapper = page_table.mapper();
+        let mut stack_end = STACK_MAX - (tid.0 as u64 - 1) * STACK_MAX_SIZE;
+        let stack_bot = loop {
+            let stack_bot = stack_end - stack_pages * PAGE_SIZE;
+            let first = Page::<Size4KiB>::containing_address(VirtAddr::new(stack_bot));
+            if Page::range(first, first + stack_pages).all(|p| mapper.translate_page(p).is_err()) {
+                break stack_bot;
+            }
+            debug!("Thread stack at {:#X} is taken.", stack_bot);
+            stack_end = stack_end.checked_sub(STACK_MAX_SIZE).filter(|&end| end >= STACK_MAX_SIZE)?;
+        };
+        let stack_segment = elf::map_range(
+            stack_bot,
+            stack_pages,
+            &mut mapper,
+            &mut *get_frame_alloc_for_sure(),
+            true,
+        )
+        .map_err(|e| warn!("Map thread stack to {:#X} failed: {:?}", stack_bot, e))
+        .ok()?;
+
+        proc_data.stack_segment = Some(stack_segment);
+        proc_data.stack_memory_usage = stack_pages as usize;
+        // the code belongs to the process, don't count it twice
+        proc_data.code_segments = None;
+
+        let mut context = ProcessContext::default();
+        let stack_top = stack_end - 8;
+        context.init_user_stack_frame(entry, VirtAddr::new(stack_top));
+        context.set_args([arg, 0]);
+
+        let mut inner = ProcessInner {
+            name: self.name.clone(),
+            ticks_passed: 0,
+            cpu_time: 0,
+            run_since: 0,
+            proc_data: Some(proc_data),
+            page_table: Some(page_table),
+            context,
+            parent: Some(parent),
+            children: Vec::new(),
+            status: ProgramStatus::Ready,
+            exit_code: Some(0),
+            kernel_stack: None,
+            leader: Some(leader),
+            threads: Vec::new(),
+            joiner: None,
+            wakeup_pending: false,
+            tls_block: None,
+            fs_base: 0,
+        };
+        inner.alloc_tls();
+
+        Some(inner)
     }
 
-    pub fn sem_signal(&self, key: u32) -> SemaphoreResult{
-        if let Some(proc_data) = &self.proc_data {
-            proc_data.semaphores.write().signal(key)
-        } else {
-            SemaphoreResult::NotExist
+    /// Give this thread a TLS block of its program and point `FS_BASE`
+    /// at it, nothing to do if the program has no `PT_TLS`
+    fn alloc_tls(&mut self) {
+        let template = match self.proc_data.as_ref().and_then(|d| d.tls.clone()) {
+            Some(template) => template,
+            None => return,
+        };
+
+        match template.alloc_block() {
+            Some(block) => {
+                self.fs_base = block.thread_pointer();
+                self.tls_block = Some(block);
+            }
+            None => warn!("Failed to allocate TLS block for {}", self.name),
         }
     }
 
-    pub fn new_sem(&self, key: u32, value: usize) -> bool {
-        if let Some(proc_data) = &self.proc_data {
-            proc_data.semaphores.write().insert(key, value)
-        } else {
-            false
-        }
+    /// Set `FS_BASE` of the running thread
+    pub fn set_fs_base(&mut self, addr: u64) {
+        self.fs_base = addr;
+        FsBase::write(VirtAddr::new_truncate(addr));
+    }
+
+    pub fn leader(&self) -> Option<ProcessId> {
+        self.leader
+    }
+
+    pub fn threads(&self) -> &[Arc<Process>] {
+        &self.threads
+    }
+
+    pub fn add_thread(&mut self, thread: Arc<Process>) {
+        self.threads.push(thread);
     }
 
-    pub fn remove_sem(&self, key: u32) -> bool {
-        if let Some(proc_data) = &self.proc_data {
-            proc_data.semaphores.write().remove(key)
-        } else {
-            false
+    pub fn take_threads(&mut self) -> Vec<Arc<Process>> {
+        core::mem::take(&mut self.threads)
+    }
+
+    pub fn joiner(&self) -> Option<ProcessId> {
+        self.joiner
+    }
+
+    pub fn set_joiner(&mut self, pid: ProcessId) {
+        self.joiner = Some(pid);
+    }
+
+    pub fn take_joiner(&mut self) -> Option<ProcessId> {
+        self.joiner.take()
+    }
+
+    /// Run `f` on the semaphores this process has open
+    pub fn with_semaphores(
+        &self,
+        f: impl FnOnce(&mut SemaphoreSet) -> SemaphoreResult,
+    ) -> SemaphoreResult {
+        match &self.proc_data {
+            Some(proc_data) => f(&mut proc_data.semaphores.write()),
+            None => SemaphoreResult::NotExist,
         }
     }
+
+    /// Run `f` on the shared memory of this process and its own page table
+    pub fn with_shm<T>(&mut self, f: impl FnOnce(&mut ShmSpace, &mut OffsetPageTable) -> T) -> Option<T> {
+        let proc_data = self.proc_data.as_ref()?;
+        let mut mapper = self.page_table.as_ref()?.mapper();
+        Some(f(&mut proc_data.shm.write(), &mut mapper))
+    }
 }
 
 
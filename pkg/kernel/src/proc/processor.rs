@@ -1,24 +1,58 @@
-use core::sync::atomic::{AtomicU16, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU16, AtomicU64, Ordering};
 
 use crate::proc::ProcessId;
+use alloc::collections::VecDeque;
 use alloc::{string::String, vec::Vec};
+use spin::Mutex;
 use x86::cpuid::CpuId;
 
-const MAX_CPU_COUNT: usize = 4;
+pub const MAX_CPU_COUNT: usize = 4;
 
 #[allow(clippy::declare_interior_mutable_const)]
 const EMPTY: Processor = Processor::new(); // means no process
 
 static PROCESSORS: [Processor; MAX_CPU_COUNT] = [EMPTY; MAX_CPU_COUNT];
 
-/// Returns the current processor based on the current APIC ID
-pub fn current() -> &'static Processor {
-    let cpuid = CpuId::new()
+/// Index of the current processor, its initial APIC ID
+pub fn current_id() -> usize {
+    CpuId::new()
         .get_feature_info()
         .unwrap()
-        .initial_local_apic_id() as usize;
+        .initial_local_apic_id() as usize
+}
 
-    &PROCESSORS[cpuid]
+/// Returns the current processor based on the current APIC ID
+pub fn current() -> &'static Processor {
+    &PROCESSORS[current_id()]
+}
+
+#[inline]
+pub fn get(cpu: usize) -> &'static Processor {
+    &PROCESSORS[cpu]
+}
+
+/// Processors taking processes from their run queue, with their index
+pub fn online() -> impl Iterator<Item = (usize, &'static Processor)> {
+    PROCESSORS.iter().enumerate().filter(|(_, p)| p.is_online())
+}
+
+/// Mask of the online processors
+pub fn online_mask() -> u64 {
+    online().fold(0, |mask, (i, _)| mask | 1 << i)
+}
+
+/// Run queue for a process allowed on the CPUs in `affinity`
+///
+/// The allowed online CPU with the shortest queue, the current one if it
+/// is as short. Falls back to the current CPU if none is allowed, e.g.
+/// before the other CPUs are started.
+pub fn select(affinity: u64) -> usize {
+    let this = current_id();
+    online()
+        .filter(|&(i, _)| affinity & (1 << i) != 0)
+        .min_by_key(|&(i, p)| (p.queue_len(), i != this))
+        .map(|(i, _)| i)
+        .unwrap_or(this)
 }
 
 pub fn print_processors() -> String {
@@ -34,12 +68,56 @@ pub fn print_processors() -> String {
     )
 }
 
-/// Processor holds the current process id
-pub struct Processor(AtomicU16);
+pub fn print_queues() -> String {
+    alloc::format!(
+        "Queue  : {}\n",
+        online()
+            .map(|(i, p)| alloc::format!("[{}: {:?}]", i, p.queue.lock()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// If any processor is running `pid` right now
+pub fn is_running(pid: ProcessId) -> bool {
+    running_on(pid).is_some()
+}
+
+/// The processor running `pid`
+pub fn running_on(pid: ProcessId) -> Option<usize> {
+    PROCESSORS.iter().position(|p| p.get_pid() == Some(pid))
+}
+
+/// Processor holds the current process id and its run queue
+pub struct Processor {
+    pid: AtomicU16,
+    /// Runs when nothing else is ready, never in the ready queue
+    idle: AtomicU16,
+    /// Set once the idle process exists and the CPU schedules
+    online: AtomicBool,
+    queue: Mutex<VecDeque<ProcessId>>,
+    /// Monotonic time the idle process was switched in, 0 while busy
+    idle_since: AtomicU64,
+    /// Nanoseconds spent idle, not counting the current stretch
+    idle_ns: AtomicU64,
+}
 
 impl Processor {
     pub const fn new() -> Self {
-        Self(AtomicU16::new(0))
+        Self {
+            pid: AtomicU16::new(0),
+            idle: AtomicU16::new(0),
+            online: AtomicBool::new(false),
+            queue: Mutex::new(VecDeque::new()),
+            idle_since: AtomicU64::new(0),
+            idle_ns: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Default for Processor {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -56,21 +134,101 @@ pub fn get_pid() -> ProcessId {
 impl Processor {
     #[inline]
     pub fn is_free(&self) -> bool {
-        self.0.load(Ordering::Relaxed) == 0
+        self.pid.load(Ordering::Relaxed) == 0
     }
 
     #[inline]
     pub fn set_pid(&self, pid: ProcessId) {
-        self.0.store(pid.0, Ordering::Relaxed);
+        self.pid.store(pid.0, Ordering::Relaxed);
     }
 
     #[inline]
     pub fn get_pid(&self) -> Option<ProcessId> {
-        let pid = self.0.load(Ordering::Relaxed);
+        let pid = self.pid.load(Ordering::Relaxed);
+        if pid == 0 {
+            None
+        } else {
+            Some(ProcessId(pid))
+        }
+    }
+
+    #[inline]
+    pub fn set_idle(&self, pid: ProcessId) {
+        self.idle.store(pid.0, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn idle(&self) -> Option<ProcessId> {
+        let pid = self.idle.load(Ordering::Relaxed);
         if pid == 0 {
             None
         } else {
             Some(ProcessId(pid))
         }
     }
+
+    /// If the CPU has nothing to do but its idle process
+    #[inline]
+    pub fn is_idle(&self) -> bool {
+        self.idle().is_some() && self.get_pid() == self.idle()
+    }
+
+    #[inline]
+    pub fn set_online(&self) {
+        self.online.store(true, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn is_online(&self) -> bool {
+        self.online.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn push(&self, pid: ProcessId) {
+        self.queue.lock().push_back(pid);
+    }
+
+    #[inline]
+    pub fn pop(&self) -> Option<ProcessId> {
+        self.queue.lock().pop_front()
+    }
+
+    #[inline]
+    pub fn queue_len(&self) -> usize {
+        self.queue.lock().len()
+    }
+
+    /// Start counting idle time, nothing to do if it is idle already
+    pub fn enter_idle(&self, now: u64) {
+        let _ = self
+            .idle_since
+            .compare_exchange(0, now.max(1), Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    /// Stop counting idle time, false if it wasn't idle
+    pub fn leave_idle(&self, now: u64) -> bool {
+        match self.idle_since.swap(0, Ordering::Relaxed) {
+            0 => false,
+            since => {
+                self.idle_ns.fetch_add(now.saturating_sub(since), Ordering::Relaxed);
+                true
+            }
+        }
+    }
+
+    /// Nanoseconds spent idle up to `now`
+    pub fn idle_time(&self, now: u64) -> u64 {
+        let idle = self.idle_ns.load(Ordering::Relaxed);
+        match self.idle_since.load(Ordering::Relaxed) {
+            0 => idle,
+            since => idle + now.saturating_sub(since),
+        }
+    }
+
+    /// Take the last queued process that `allowed` accepts
+    pub fn steal(&self, allowed: impl Fn(ProcessId) -> bool) -> Option<ProcessId> {
+        let mut queue = self.queue.lock();
+        let index = queue.iter().rposition(|&pid| allowed(pid))?;
+        queue.remove(index)
+    }
 }
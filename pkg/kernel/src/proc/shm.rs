@@ -0,0 +1,311 @@
+//! System V style shared memory
+//!
+//! A segment owns its frames and frees them when the last `Arc` goes
+//! away. The table holds one reference until `IPC_RMID`, every mapping
+//! holds another, so a removed segment lives on while it is mapped.
+//!
+//! Every process has its own `ShmSpace`, shared by its threads. Forked
+//! processes share the page table, so their copies of a mapping share the
+//! pages too; they are unmapped when the last copy is detached or exits.
+
+use super::msg::{IpcError, IpcResult};
+use crate::memory::*;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use spin::Mutex;
+use syscall_def::ipc::*;
+use x86_64::structures::paging::page::PageRange;
+use x86_64::structures::paging::*;
+use x86_64::VirtAddr;
+
+pub static SHM_TABLE: Mutex<ShmTable> = Mutex::new(ShmTable::new());
+
+/// Where the kernel looks for room when no address is given
+pub const SHM_START: u64 = 0x3000_0000_0000;
+pub const SHM_END: u64 = 0x4000_0000_0000;
+
+#[derive(Debug)]
+pub struct ShmSegment {
+    frames: Vec<PhysFrame>,
+}
+
+impl ShmSegment {
+    fn new(pages: usize) -> IpcResult<Self> {
+        let mut frame_alloc = get_frame_alloc_for_sure();
+        let mut frames = Vec::with_capacity(pages);
+
+        for _ in 0..pages {
+            let Some(frame) = frame_alloc.allocate_frame() else {
+                for frame in frames {
+                    unsafe { frame_alloc.deallocate_frame(frame) };
+                }
+                return Err(IpcError::NoMemory);
+            };
+            // other processes must not see old data
+            unsafe {
+                core::ptr::write_bytes(
+                    physical_to_virtual(frame.start_address().as_u64()) as *mut u8,
+                    0,
+                    PAGE_SIZE as usize,
+                );
+            }
+            frames.push(frame);
+        }
+
+        Ok(Self { frames })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.frames.len() as u64 * PAGE_SIZE
+    }
+}
+
+impl Drop for ShmSegment {
+    fn drop(&mut self) {
+        // other CPUs may still have the pages of an old mapping cached
+        crate::smp::tlb::free_frames(core::mem::take(&mut self.frames));
+        trace!("Shared memory segment freed.");
+    }
+}
+
+#[derive(Debug)]
+struct ShmEntry {
+    key: u32,
+    /// `None` once removed
+    owner: Option<Arc<ShmSegment>>,
+    segment: Weak<ShmSegment>,
+}
+
+impl ShmEntry {
+    fn attached(&self) -> usize {
+        self.segment.strong_count() - self.owner.is_some() as usize
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ShmTable {
+    segments: BTreeMap<u32, ShmEntry>,
+    next_id: u32,
+}
+
+impl ShmTable {
+    pub const fn new() -> Self {
+        Self {
+            segments: BTreeMap::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Find the segment of `key` or create one of `size` bytes
+    pub fn get(&mut self, key: u32, size: usize, flags: IpcFlags) -> IpcResult<u32> {
+        // forget segments that are removed and unmapped
+        self.segments.retain(|_, e| e.segment.strong_count() > 0);
+
+        if key != IPC_PRIVATE {
+            let found = self
+                .segments
+                .iter()
+                .find(|(_, e)| e.owner.is_some() && e.key == key);
+            if let Some((&id, entry)) = found {
+                if flags.contains(IpcFlags::CREATE | IpcFlags::EXCL) {
+                    return Err(IpcError::AlreadyExists);
+                }
+                if size as u64 > entry.owner.as_ref().unwrap().size() {
+                    return Err(IpcError::InvalidArgument);
+                }
+                return Ok(id);
+            }
+            if !flags.contains(IpcFlags::CREATE) {
+                return Err(IpcError::NotFound);
+            }
+        }
+
+        if size == 0 || size > SHM_MAX {
+            return Err(IpcError::InvalidArgument);
+        }
+
+        let pages = (size as u64).div_ceil(PAGE_SIZE) as usize;
+        let segment = Arc::new(ShmSegment::new(pages)?);
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.segments.insert(
+            id,
+            ShmEntry {
+                key,
+                segment: Arc::downgrade(&segment),
+                owner: Some(segment),
+            },
+        );
+        debug!("Shared memory #{} created for key {:#x}, {} pages", id, key, pages);
+        Ok(id)
+    }
+
+    /// A new reference to the segment for mapping it
+    pub fn segment(&self, id: u32) -> IpcResult<Arc<ShmSegment>> {
+        self.segments
+            .get(&id)
+            .and_then(|e| e.segment.upgrade())
+            .ok_or(IpcError::NotFound)
+    }
+
+    /// Drop the table's reference, the frames go with the last mapping
+    pub fn remove(&mut self, id: u32) -> IpcResult<()> {
+        let entry = self.segments.get_mut(&id).ok_or(IpcError::NotFound)?;
+        if entry.owner.take().is_none() {
+            return Err(IpcError::NotFound);
+        }
+        if entry.attached() == 0 {
+            self.segments.remove(&id);
+        }
+        debug!("Shared memory #{} removed", id);
+        Ok(())
+    }
+
+    pub fn stat(&self, id: u32) -> IpcResult<ShmStat> {
+        let entry = self.segments.get(&id).ok_or(IpcError::NotFound)?;
+        let segment = entry.segment.upgrade().ok_or(IpcError::NotFound)?;
+        Ok(ShmStat {
+            key: entry.key,
+            id,
+            size: segment.size(),
+            // not counting `segment` above
+            attached: entry.attached() as u64 - 1,
+            removed: entry.owner.is_none(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ShmMapping {
+    id: u32,
+    pages: PageRange,
+    segment: Arc<ShmSegment>,
+    /// One per copy of this mapping, the pages go with the last one
+    users: Arc<()>,
+}
+
+impl ShmMapping {
+    /// Drop this copy, unmapping the pages if no other process uses them
+    fn unmap(self, mapper: &mut impl Mapper<Size4KiB>) {
+        if Arc::into_inner(self.users).is_some() {
+            unmap_pages(self.pages, mapper);
+        }
+        // the frames may go with `self.segment` now, after the unmap
+        let id = self.id;
+        drop(self.segment);
+        // last mapping of a removed segment, nothing can find it any more
+        let mut table = SHM_TABLE.lock();
+        if table.segments.get(&id).is_some_and(|e| e.segment.strong_count() == 0) {
+            table.segments.remove(&id);
+        }
+    }
+}
+
+/// Shared memory mapped by one process
+///
+/// Cloned on fork, which takes another reference to every segment.
+#[derive(Debug, Default, Clone)]
+pub struct ShmSpace {
+    mappings: BTreeMap<u64, ShmMapping>,
+}
+
+impl ShmSpace {
+    /// First gap of `size` bytes in the shared memory area
+    fn find_free(&self, size: u64) -> Option<u64> {
+        let mut start = SHM_START;
+        for mapping in self.mappings.values() {
+            if start + size <= mapping.pages.start.start_address().as_u64() {
+                break;
+            }
+            start = start.max(mapping.pages.end.start_address().as_u64());
+        }
+        (start + size <= SHM_END).then_some(start)
+    }
+
+    fn overlaps(&self, pages: &PageRange) -> bool {
+        self.mappings
+            .values()
+            .any(|m| m.pages.start < pages.end && pages.start < m.pages.end)
+    }
+
+    /// Map segment `id` at `addr`, or where there is room if it is 0
+    pub fn attach(
+        &mut self,
+        id: u32,
+        addr: u64,
+        mapper: &mut impl Mapper<Size4KiB>,
+    ) -> IpcResult<u64> {
+        let segment = SHM_TABLE.lock().segment(id)?;
+        let size = segment.size();
+
+        let start = match addr {
+            0 => self.find_free(size).ok_or(IpcError::NoMemory)?,
+            addr if addr % PAGE_SIZE != 0 || addr.saturating_add(size) > super::USER_SPACE_END => {
+                return Err(IpcError::InvalidArgument)
+            }
+            addr => addr,
+        };
+
+        let first = Page::<Size4KiB>::containing_address(VirtAddr::new(start));
+        let pages = Page::range(first, first + segment.frames.len() as u64);
+        if self.overlaps(&pages) || pages.clone().any(|p| mapper.translate_page(p).is_ok()) {
+            return Err(IpcError::InvalidArgument);
+        }
+
+        let flags =
+            PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
+        let mut frame_alloc = get_frame_alloc_for_sure();
+        for (page, &frame) in pages.zip(segment.frames.iter()) {
+            match unsafe { mapper.map_to(page, frame, flags, &mut *frame_alloc) } {
+                Ok(flush) => flush.flush(),
+                Err(e) => {
+                    warn!("Failed to map shared memory at {:?}: {:?}", page, e);
+                    drop(frame_alloc);
+                    unmap_pages(Page::range(first, page), mapper);
+                    return Err(IpcError::NoMemory);
+                }
+            }
+        }
+
+        self.mappings.insert(
+            start,
+            ShmMapping {
+                id,
+                pages,
+                segment,
+                users: Arc::new(()),
+            },
+        );
+        Ok(start)
+    }
+
+    /// Unmap the segment mapped at `addr`
+    pub fn detach(&mut self, addr: u64, mapper: &mut impl Mapper<Size4KiB>) -> IpcResult<()> {
+        let mapping = self.mappings.remove(&addr).ok_or(IpcError::InvalidArgument)?;
+        mapping.unmap(mapper);
+        Ok(())
+    }
+
+    /// Detach everything, the process is exiting
+    pub fn detach_all(&mut self, mapper: &mut impl Mapper<Size4KiB>) {
+        for (_, mapping) in core::mem::take(&mut self.mappings) {
+            mapping.unmap(mapper);
+        }
+    }
+
+    /// Bytes mapped into this address space
+    pub fn memory_usage(&self) -> usize {
+        self.mappings.values().map(|m| m.segment.size() as usize).sum()
+    }
+}
+
+fn unmap_pages(pages: PageRange, mapper: &mut impl Mapper<Size4KiB>) {
+    for page in pages {
+        // the frames belong to the segment, don't free them here
+        if let Ok((_, flush)) = mapper.unmap(page) {
+            flush.flush();
+        }
+    }
+}
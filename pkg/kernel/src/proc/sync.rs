@@ -1,5 +1,6 @@
 use super::ProcessId;
 use alloc::collections::*;
+use alloc::sync::{Arc, Weak};
 use spin::Mutex;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
@@ -23,6 +24,7 @@ pub struct Semaphore {
 pub enum SemaphoreResult {
     Ok,
     NotExist,
+    AlreadyExists,
     Block(ProcessId),
     WakeUp(ProcessId),
 }
@@ -54,6 +56,11 @@ impl Semaphore {
         }
     }
 
+    /// Drop `pid` from the wait queue, it won't wait any more
+    pub fn cancel_wait(&mut self, pid: ProcessId) {
+        self.wait_queue.retain(|&p| p != pid);
+    }
+
     /// Signal the semaphore (release/up/verhogen)
     ///
     /// if the wait queue is not empty, then pop a process from the wait queue
@@ -72,29 +79,81 @@ impl Semaphore {
     }
 }
 
-#[derive(Debug, Default)]
+/// Every semaphore that has a name, by key, shared by all processes
+///
+/// Only weak references are kept here: a semaphore lives as long as some
+/// process has it open, and goes away with its last user.
+static SEMAPHORE_TABLE: Mutex<BTreeMap<SemaphoreId, Weak<Mutex<Semaphore>>>> =
+    Mutex::new(BTreeMap::new());
+
+/// The semaphores a process has created or opened
+///
+/// Forked children get their own references to the open semaphores;
+/// threads share the set.
+#[derive(Debug, Clone, Default)]
 pub struct SemaphoreSet {
-    sems: BTreeMap<SemaphoreId, Mutex<Semaphore>>,
+    sems: BTreeMap<SemaphoreId, Arc<Mutex<Semaphore>>>,
 }
 
 impl SemaphoreSet {
-    pub fn insert(&mut self, key: u32, value: usize) -> bool {
+    /// Create a semaphore named `key` and open it
+    pub fn insert(&mut self, key: u32, value: usize) -> SemaphoreResult {
         trace!("Sem Insert: <{:#x}>{}", key, value);
 
-        // FIXME: insert a new semaphore into the sems
-        //          use `insert(/* ... */).is_none()`
-        
         let sid = SemaphoreId::new(key);
-        self.sems.insert(sid,Mutex::new(Semaphore::new(value))).is_none()
+        let mut table = SEMAPHORE_TABLE.lock();
+        // forget semaphores nobody has open any more
+        table.retain(|_, sem| sem.strong_count() > 0);
+        if table.contains_key(&sid) {
+            return SemaphoreResult::AlreadyExists;
+        }
+
+        let sem = Arc::new(Mutex::new(Semaphore::new(value)));
+        table.insert(sid, Arc::downgrade(&sem));
+        self.sems.insert(sid, sem);
+        SemaphoreResult::Ok
+    }
+
+    /// Open the existing semaphore named `key`
+    pub fn open(&mut self, key: u32) -> SemaphoreResult {
+        trace!("Sem Open: <{:#x}>", key);
+
+        let sid = SemaphoreId::new(key);
+        let sem = SEMAPHORE_TABLE
+            .lock()
+            .get(&sid)
+            .and_then(Weak::upgrade);
+        match sem {
+            Some(sem) => {
+                self.sems.insert(sid, sem);
+                SemaphoreResult::Ok
+            }
+            None => SemaphoreResult::NotExist,
+        }
+    }
+
+    /// Drop this process's reference to `key`
+    pub fn close(&mut self, key: u32) -> SemaphoreResult {
+        trace!("Sem Close: <{:#x}>", key);
+
+        let sid = SemaphoreId::new(key);
+        match self.sems.remove(&sid) {
+            Some(_) => SemaphoreResult::Ok,
+            None => SemaphoreResult::NotExist,
+        }
     }
 
-    pub fn remove(&mut self, key: u32) -> bool {
+    /// Remove the name `key`, processes that have it open keep using it
+    /// until they close it
+    pub fn remove(&mut self, key: u32) -> SemaphoreResult {
         trace!("Sem Remove: <{:#x}>", key);
 
-        // FIXME: remove the semaphore from the sems
-        //          use `remove(/* ... */).is_some()`
         let sid = SemaphoreId::new(key);
-        self.sems.remove(&sid).is_some()
+        let mut table = SEMAPHORE_TABLE.lock();
+        match table.remove(&sid).and_then(|sem| sem.upgrade()) {
+            Some(_) => SemaphoreResult::Ok,
+            None => SemaphoreResult::NotExist,
+        }
     }
 
     /// Wait the semaphore (acquire/down/proberen)
@@ -124,6 +183,13 @@ impl SemaphoreSet {
             SemaphoreResult::NotExist
         }
     }
+
+    /// Take a killed process out of every wait queue it may be in
+    pub fn cancel_wait(&self, pid: ProcessId) {
+        for sem in self.sems.values() {
+            sem.lock().cancel_wait(pid);
+        }
+    }
 }
 
 impl core::fmt::Display for Semaphore {
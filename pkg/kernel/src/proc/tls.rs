@@ -0,0 +1,128 @@
+//! Thread local storage of user programs
+//!
+//! x86_64 uses variant II of the ELF TLS layout: the block of a thread
+//! ends at the thread pointer (`FS_BASE`), and the thread pointer points
+//! at a TCB whose first word is the thread pointer itself.
+//!
+//! ```text
+//! block                                   fs base
+//! | .tdata | .tbss | padding to align     | self pointer |
+//! ```
+//!
+//! Blocks are allocated on the user heap, so every address space can
+//! reach them, and freed when the thread dies.
+
+use crate::memory::user::USER_ALLOCATOR;
+use alloc::sync::Arc;
+use core::alloc::Layout;
+use core::ptr::NonNull;
+use xmas_elf::program::Type;
+use xmas_elf::ElfFile;
+
+/// Size of the TCB, only the self pointer is used
+const TCB_SIZE: usize = 8;
+
+/// The `PT_TLS` segment of a program, copied into every new block
+#[derive(Debug)]
+pub struct TlsTemplate {
+    /// Initialized part (`.tdata`), the rest of the block is zeroed
+    image: Arc<[u8]>,
+    mem_size: usize,
+    align: usize,
+}
+
+impl TlsTemplate {
+    /// Read the `PT_TLS` segment, `None` if the program has none
+    pub fn from_elf(elf: &ElfFile) -> Option<Self> {
+        let segment = elf
+            .program_iter()
+            .find(|segment| segment.get_type() == Ok(Type::Tls))?;
+
+        let start = segment.offset() as usize;
+        let end = start + segment.file_size() as usize;
+        let image = elf.input.get(start..end)?;
+
+        Some(Self {
+            image: Arc::from(image),
+            mem_size: segment.mem_size() as usize,
+            align: (segment.align() as usize).max(TCB_SIZE),
+        })
+    }
+
+    /// Distance from the start of the block to the thread pointer
+    #[inline]
+    fn offset(&self) -> usize {
+        self.mem_size.next_multiple_of(self.align)
+    }
+
+    #[inline]
+    fn layout(&self) -> Option<Layout> {
+        Layout::from_size_align(self.offset() + TCB_SIZE, self.align).ok()
+    }
+
+    /// Allocate a fresh block for a new thread
+    pub fn alloc_block(&self) -> Option<TlsBlock> {
+        let block = TlsBlock::alloc(self.layout()?, self.offset())?;
+
+        unsafe {
+            let start = block.ptr.as_ptr();
+            start.write_bytes(0, self.offset());
+            start.copy_from_nonoverlapping(self.image.as_ptr(), self.image.len());
+        }
+
+        Some(block)
+    }
+}
+
+/// The TLS block of one thread
+#[derive(Debug)]
+pub struct TlsBlock {
+    ptr: NonNull<u8>,
+    layout: Layout,
+    /// Offset of the thread pointer in the block
+    offset: usize,
+}
+
+// the memory lives on the user heap, not on any cpu
+unsafe impl Send for TlsBlock {}
+unsafe impl Sync for TlsBlock {}
+
+impl TlsBlock {
+    fn alloc(layout: Layout, offset: usize) -> Option<Self> {
+        let ptr = USER_ALLOCATOR.lock().allocate_first_fit(layout).ok()?;
+        let block = Self {
+            ptr,
+            layout,
+            offset,
+        };
+
+        let tp = block.thread_pointer();
+        unsafe { (tp as *mut u64).write(tp) };
+
+        Some(block)
+    }
+
+    /// The value for `FS_BASE`
+    #[inline]
+    pub fn thread_pointer(&self) -> u64 {
+        self.ptr.as_ptr() as u64 + self.offset as u64
+    }
+
+    /// Copy the current contents into a new block, for `fork`
+    pub fn duplicate(&self) -> Option<Self> {
+        let block = Self::alloc(self.layout, self.offset)?;
+        unsafe {
+            block
+                .ptr
+                .as_ptr()
+                .copy_from_nonoverlapping(self.ptr.as_ptr(), self.offset);
+        }
+        Some(block)
+    }
+}
+
+impl Drop for TlsBlock {
+    fn drop(&mut self) {
+        unsafe { USER_ALLOCATOR.lock().deallocate(self.ptr, self.layout) };
+    }
+}
@@ -0,0 +1,164 @@
+//! Multi-processor bring-up
+//!
+//! The MADT lists the local APIC of every processor. The BSP copies the
+//! trampoline below 1 MiB and starts the APs one at a time with
+//! INIT-SIPI-SIPI. Each AP loads a GDT/TSS of its own, starts its LAPIC
+//! timer and idles; the timer interrupt lets it take work from the shared
+//! ready queue like the BSP.
+//!
+//! Frames of unmapped pages are freed through `tlb`, which waits for the
+//! other CPUs to flush stale entries first.
+
+pub mod tlb;
+mod trampoline;
+
+use self::trampoline::*;
+use crate::acpi;
+use crate::interrupt;
+use crate::memory::{self, physical_to_virtual, PAGE_SIZE};
+use crate::proc::{self, processor::MAX_CPU_COUNT, KTHREAD_STACK_SIZE};
+use alloc::vec;
+use alloc::vec::Vec;
+use boot::{BootInfo, MemoryType};
+use core::ptr::addr_of;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use x86_64::registers::control::Cr3;
+
+/// Set by an AP once it is ready to run processes
+static AP_STARTED: AtomicBool = AtomicBool::new(false);
+
+static CPU_COUNT: AtomicUsize = AtomicUsize::new(1);
+
+/// How long to wait for an AP, in milliseconds
+const AP_START_TIMEOUT: u64 = 100;
+
+/// Number of CPUs running processes
+pub fn cpu_count() -> usize {
+    CPU_COUNT.load(Ordering::Relaxed)
+}
+
+pub fn init(boot_info: &'static BootInfo) {
+    proc::init_idle();
+
+    let Some(madt) = acpi::madt() else {
+        info!("No MADT, running on the BSP only.");
+        return;
+    };
+
+    let bsp = interrupt::apic_id();
+    let aps: Vec<u8> = madt
+        .local_apics
+        .iter()
+        .filter(|l| l.enabled && l.apic_id != bsp)
+        .map(|l| l.apic_id)
+        .filter(|&id| {
+            let ok = (id as usize) < MAX_CPU_COUNT;
+            if !ok {
+                warn!("CPU with APIC ID {} ignored, at most {} CPUs.", id, MAX_CPU_COUNT);
+            }
+            ok
+        })
+        .collect();
+
+    if aps.is_empty() {
+        info!("Single processor.");
+        return;
+    }
+
+    let Some(page) = trampoline_page(boot_info) else {
+        warn!("No free page below 1 MiB for the AP trampoline.");
+        return;
+    };
+
+    // loaded in real mode, only 32 bits fit
+    let cr3 = Cr3::read().0.start_address().as_u64();
+    if cr3 >> 32 != 0 {
+        warn!("Kernel page table at {:#x} is above 4 GiB, APs not started.", cr3);
+        return;
+    }
+
+    unsafe { install_trampoline(page, cr3) };
+
+    for apic_id in aps {
+        if start(apic_id, page) {
+            CPU_COUNT.fetch_add(1, Ordering::Relaxed);
+        } else {
+            warn!("CPU {} did not start.", apic_id);
+        }
+    }
+
+    info!("SMP Initialized, {} CPUs.", cpu_count());
+}
+
+/// A free page in conventional memory below 1 MiB, except page 0
+fn trampoline_page(boot_info: &BootInfo) -> Option<u64> {
+    boot_info
+        .memory_map
+        .iter()
+        .filter(|r| r.ty == MemoryType::CONVENTIONAL)
+        .flat_map(|r| (0..r.page_count).map(move |i| r.phys_start + i * PAGE_SIZE))
+        .find(|addr| (PAGE_SIZE..0xA0000).contains(addr))
+}
+
+/// Offset of a trampoline symbol from its start
+fn offset(symbol: *const u8) -> u64 {
+    symbol as u64 - unsafe { addr_of!(ap_trampoline) } as u64
+}
+
+/// Copy the trampoline to `page` and fill in what does not change
+unsafe fn install_trampoline(page: u64, cr3: u64) {
+    let start = addr_of!(ap_trampoline);
+    let size = offset(addr_of!(ap_trampoline_end));
+    assert!(size <= PAGE_SIZE, "AP trampoline does not fit into a page");
+
+    let dest = physical_to_virtual(page);
+    core::ptr::copy_nonoverlapping(start, dest as *mut u8, size as usize);
+
+    let field = |symbol: *const u8| dest + offset(symbol);
+    // base of the temporary gdt, after its 16 bit limit
+    ((field(addr_of!(ap_gdt_ptr)) + 2) as *mut u64).write_unaligned(page + offset(addr_of!(ap_gdt)));
+    (field(addr_of!(ap_long_ptr)) as *mut u32)
+        .write_unaligned((page + offset(addr_of!(ap_long_mode))) as u32);
+    (field(addr_of!(ap_cr3)) as *mut u64).write_unaligned(cr3);
+    (field(addr_of!(ap_entry)) as *mut u64).write_unaligned(ap_main as usize as u64);
+}
+
+/// Start one AP and wait for it to come up
+fn start(apic_id: u8, page: u64) -> bool {
+    // never freed, it becomes the stack of the AP's idle process
+    let stack = vec![0u8; KTHREAD_STACK_SIZE].leak();
+    let stack_top = (stack.as_ptr() as u64 + KTHREAD_STACK_SIZE as u64) & !0xF;
+
+    unsafe {
+        let field = physical_to_virtual(page) + offset(addr_of!(ap_stack));
+        (field as *mut u64).write_volatile(stack_top);
+    }
+
+    AP_STARTED.store(false, Ordering::Release);
+    interrupt::start_ap(apic_id, (page / PAGE_SIZE) as u8);
+
+    for _ in 0..AP_START_TIMEOUT {
+        if AP_STARTED.load(Ordering::Acquire) {
+            return true;
+        }
+        crate::pit::udelay(1000);
+    }
+    AP_STARTED.load(Ordering::Acquire)
+}
+
+/// Long mode entry of an AP, on the stack from `start`
+extern "C" fn ap_main() -> ! {
+    memory::gdt::init_ap();
+    interrupt::init_ap();
+    let idle = proc::init_idle();
+    // frames freed before it was online wait for it too
+    tlb::flush();
+
+    info!("CPU {} started, idle #{}.", interrupt::apic_id(), idle);
+    AP_STARTED.store(true, Ordering::Release);
+
+    x86_64::instructions::interrupts::enable();
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
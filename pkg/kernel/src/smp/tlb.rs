@@ -0,0 +1,77 @@
+//! TLB shootdown
+//!
+//! Unmapping a page only flushes the TLB of the current CPU. Threads of the
+//! same process on other CPUs may still reach the old frame, so freed
+//! frames wait here until every other online CPU has flushed on the
+//! shootdown IPI.
+
+use crate::interrupt;
+use crate::memory::get_frame_alloc_for_sure;
+use crate::proc::processor::{self, MAX_CPU_COUNT};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+use x86_64::structures::paging::{FrameDeallocator, PhysFrame};
+
+/// Bumped for every batch of freed frames
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NEVER: AtomicU64 = AtomicU64::new(0);
+
+/// Last generation each CPU has flushed its TLB for
+static FLUSHED: [AtomicU64; MAX_CPU_COUNT] = [NEVER; MAX_CPU_COUNT];
+
+/// Frames to free, with the generation they were unmapped in
+static PENDING: Mutex<Vec<(u64, PhysFrame)>> = Mutex::new(Vec::new());
+
+/// Free `frames` whose pages have been unmapped
+///
+/// Must not be called with the frame allocator locked.
+pub fn free_frames(frames: Vec<PhysFrame>) {
+    if frames.is_empty() {
+        return;
+    }
+
+    // any CPU flushing from now on drops the old entries
+    let generation = GENERATION.load(Ordering::SeqCst) + 1;
+    PENDING.lock().extend(frames.into_iter().map(|frame| (generation, frame)));
+
+    let this = processor::current_id();
+    flush();
+
+    for (cpu, _) in processor::online() {
+        if cpu != this {
+            interrupt::send_tlb_shootdown(cpu as u8);
+        }
+    }
+}
+
+/// Flush the TLB of this CPU and free what every CPU has flushed for
+pub fn flush() {
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    x86_64::instructions::tlb::flush_all();
+    FLUSHED[processor::current_id()].fetch_max(generation, Ordering::SeqCst);
+    release();
+}
+
+fn release() {
+    let done = processor::online()
+        .map(|(cpu, _)| FLUSHED[cpu].load(Ordering::SeqCst))
+        .min()
+        .unwrap_or(u64::MAX);
+
+    let mut pending = PENDING.lock();
+    if pending.iter().all(|&(generation, _)| generation > done) {
+        return;
+    }
+
+    let mut frame_alloc = get_frame_alloc_for_sure();
+    pending.retain(|&(generation, frame)| {
+        if generation > done {
+            return true;
+        }
+        unsafe { frame_alloc.deallocate_frame(frame) };
+        false
+    });
+}
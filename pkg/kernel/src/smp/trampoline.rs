@@ -0,0 +1,110 @@
+//! Real mode entry of the application processors
+//!
+//! Copied to a page below 1 MiB, a SIPI starts the AP there with
+//! `cs = page << 8, ip = 0`. It goes from real mode straight to long mode
+//! on the kernel page table and calls `ap_entry` on `ap_stack`. The code
+//! only uses offsets from `ap_trampoline`, the BSP fills in the fields
+//! after the code in the copy.
+
+use core::arch::global_asm;
+
+global_asm!(
+    r#"
+.pushsection .text.ap_trampoline, "ax"
+.balign 16
+.code16
+.global ap_trampoline
+ap_trampoline:
+    cli
+    cld
+    mov ax, cs
+    mov ds, ax
+    // lgdt [ap_gdt_ptr], offsets are spelled out as intel syntax
+    // doesn't take the difference of two labels in a memory operand
+    .byte 0x0F, 0x01, 0x16
+    .word ap_gdt_ptr - ap_trampoline
+
+    // PAE
+    mov eax, cr4
+    or eax, 0x20
+    mov cr4, eax
+
+    // mov eax, dword ptr [ap_cr3]
+    .byte 0x66, 0xA1
+    .word ap_cr3 - ap_trampoline
+    mov cr3, eax
+
+    // EFER: LME | NXE
+    mov ecx, 0xC0000080
+    rdmsr
+    or eax, 0x900
+    wrmsr
+
+    // PE | WP | PG, long mode is active after the far jump
+    mov eax, cr0
+    or eax, 0x80010001
+    mov cr0, eax
+
+    // jmp far dword ptr [ap_long_ptr]
+    .byte 0x66, 0xFF, 0x2E
+    .word ap_long_ptr - ap_trampoline
+
+.code64
+.global ap_long_mode
+ap_long_mode:
+    mov ax, 0x10
+    mov ds, ax
+    mov es, ax
+    mov ss, ax
+    xor ax, ax
+    mov fs, ax
+    mov gs, ax
+
+    mov rsp, qword ptr [rip + ap_stack]
+    mov rax, qword ptr [rip + ap_entry]
+    call rax
+2:
+    hlt
+    jmp 2b
+
+.balign 8
+.global ap_gdt
+ap_gdt:
+    .quad 0
+    .quad 0x00AF9A000000FFFF
+    .quad 0x00CF92000000FFFF
+.global ap_gdt_ptr
+ap_gdt_ptr:
+    .word 23
+    .quad 0
+.global ap_long_ptr
+ap_long_ptr:
+    .long 0
+    .word 0x08
+.balign 8
+.global ap_cr3
+ap_cr3:
+    .quad 0
+.global ap_stack
+ap_stack:
+    .quad 0
+.global ap_entry
+ap_entry:
+    .quad 0
+.global ap_trampoline_end
+ap_trampoline_end:
+.popsection
+"#
+);
+
+extern "C" {
+    pub static ap_trampoline: u8;
+    pub static ap_trampoline_end: u8;
+    pub static ap_long_mode: u8;
+    pub static ap_gdt: u8;
+    pub static ap_gdt_ptr: u8;
+    pub static ap_long_ptr: u8;
+    pub static ap_cr3: u8;
+    pub static ap_stack: u8;
+    pub static ap_entry: u8;
+}
@@ -1,25 +1,222 @@
-use super::uefi;
+//! Kernel clocks
+//!
+//! - ticks: timer interrupts at `hz`, what the scheduler counts in
+//! - monotonic: nanoseconds since boot, from the invariant TSC, the HPET
+//!   or the ticks if there is neither
+//! - realtime: the CMOS RTC read once at boot plus the monotonic clock
+//!
+//! None of them needs the UEFI runtime.
+
+use crate::drivers::{hpet, pit, rtc};
 use boot::BootInfo;
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use chrono::{Duration, NaiveDateTime};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Tick rate if the boot config doesn't give one
+pub const DEFAULT_HZ: u64 = 100;
+
+pub const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+static HZ: AtomicU64 = AtomicU64::new(DEFAULT_HZ);
+
+/// Timer interrupts on `TICK_CPU` since its timer started
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// The CPU whose timer drives `TICKS`, the BSP
+static TICK_CPU: AtomicUsize = AtomicUsize::new(0);
+
+static SOURCE: spin::Once<ClockSource> = spin::Once::new();
+
+/// Largest monotonic time handed out, TSCs of different CPUs may be a
+/// little apart and the clock must not go back
+static LAST_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Wall clock time when the monotonic clock was 0, moves when the time
+/// is set
+static BOOT_TIME: spin::RwLock<Option<NaiveDateTime>> = spin::RwLock::new(None);
+
+enum ClockSource {
+    Tsc { start: u64, frequency: u64 },
+    Hpet { hpet: &'static hpet::Hpet, start: u64, frequency: u64 },
+    Ticks,
+}
+
+impl ClockSource {
+    fn nanos(&self) -> u64 {
+        match *self {
+            Self::Tsc { start, frequency } => scale(rdtsc().wrapping_sub(start), frequency),
+            Self::Hpet {
+                hpet,
+                start,
+                frequency,
+            } => scale(hpet.counter().wrapping_sub(start), frequency),
+            Self::Ticks => scale(TICKS.load(Ordering::Relaxed), hz()),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Tsc { .. } => "TSC",
+            Self::Hpet { .. } => "HPET",
+            Self::Ticks => "timer ticks",
+        }
+    }
+}
+
+/// `count` ticks of a `frequency` Hz clock in nanoseconds
+fn scale(count: u64, frequency: u64) -> u64 {
+    (count as u128 * NANOS_PER_SEC as u128 / frequency as u128) as u64
+}
+
+#[inline]
+fn rdtsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+fn has_invariant_tsc() -> bool {
+    x86::cpuid::CpuId::new()
+        .get_advanced_power_mgmt_info()
+        .is_some_and(|info| info.has_invariant_tsc())
+}
+
+/// TSC ticks per second, measured against the PIT
+fn tsc_frequency() -> u64 {
+    const CALIBRATE_US: u64 = 10_000;
+    let start = rdtsc();
+    pit::udelay(CALIBRATE_US);
+    (rdtsc() - start) * (1_000_000 / CALIBRATE_US)
+}
+
+/// Pick the clock source and read the RTC, on the BSP before the timer
+/// starts
+pub fn init(boot_info: &'static BootInfo) {
+    let hz = match boot_info.hz {
+        0 => DEFAULT_HZ,
+        hz => hz.min(10_000),
+    };
+    HZ.store(hz, Ordering::Relaxed);
+    TICK_CPU.store(crate::proc::processor::current_id(), Ordering::Relaxed);
+
+    let source = SOURCE.call_once(|| {
+        if has_invariant_tsc() {
+            let frequency = tsc_frequency();
+            return ClockSource::Tsc {
+                start: rdtsc(),
+                frequency,
+            };
+        }
+        if let Some(hpet) = hpet::init() {
+            return ClockSource::Hpet {
+                hpet,
+                start: hpet.counter(),
+                frequency: hpet.frequency(),
+            };
+        }
+        ClockSource::Ticks
+    });
+    info!("Clock Source     : {}", source.name());
+
+    match rtc::now() {
+        Some(t) => {
+            *BOOT_TIME.write() = Some(t - uptime());
+            info!("Boot Time        : {}", t);
+        }
+        None => warn!("Failed to read the RTC."),
+    }
+}
+
+/// Timer interrupts per second
+#[inline]
+pub fn hz() -> u64 {
+    HZ.load(Ordering::Relaxed)
+}
+
+/// Called by the timer interrupt of every CPU, only one of them counts
+#[inline]
+pub fn tick() {
+    if crate::proc::processor::current_id() == TICK_CPU.load(Ordering::Relaxed) {
+        TICKS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Ticks since the timer started, never goes back
+///
+/// Counted from the monotonic clock when the tick can stop on idle CPUs.
+pub fn ticks() -> u64 {
+    if tickless() {
+        monotonic_ns() * hz() / NANOS_PER_SEC
+    } else {
+        TICKS.load(Ordering::Relaxed)
+    }
+}
+
+/// Length of one tick in nanoseconds
+#[inline]
+pub fn tick_ns() -> u64 {
+    NANOS_PER_SEC / hz()
+}
+
+/// If time goes on without timer interrupts, so idle CPUs may stop their
+/// tick
+pub fn tickless() -> bool {
+    matches!(
+        SOURCE.get(),
+        Some(ClockSource::Tsc { .. } | ClockSource::Hpet { .. })
+    )
+}
+
+/// TSC value at monotonic time `ns`, `None` unless the clock runs on
+/// the TSC
+pub fn tsc_at(ns: u64) -> Option<u64> {
+    match SOURCE.get()? {
+        ClockSource::Tsc { start, frequency } => {
+            Some(start + (ns as u128 * *frequency as u128 / NANOS_PER_SEC as u128) as u64)
+        }
+        _ => None,
+    }
+}
 
+/// Nanoseconds since the clock source was set up, never goes back
+pub fn monotonic_ns() -> u64 {
+    let Some(source) = SOURCE.get() else {
+        return 0;
+    };
+    let nanos = source.nanos();
+    LAST_NANOS.fetch_max(nanos, Ordering::Relaxed).max(nanos)
+}
+
+/// Milliseconds since boot
+pub fn uptime_ms() -> u64 {
+    monotonic_ns() / 1_000_000
+}
+
+/// Time since boot
+pub fn uptime() -> Duration {
+    Duration::nanoseconds(monotonic_ns() as i64)
+}
+
+/// Wall clock time of boot, from the RTC
+pub fn boot_time() -> Option<NaiveDateTime> {
+    *BOOT_TIME.read()
+}
+
+/// Wall clock time, UTC
 pub fn now() -> Option<NaiveDateTime> {
-    let uefi_time = uefi::UEFI_RUNTIME.lock()
-        .as_ref()
-        .expect("UEFI Runtime not initialized")
-        .get_time();
-
-    let naive_date = NaiveDate::from_ymd_opt(
-        uefi_time.year() as i32, 
-        uefi_time.month() as u32, 
-        uefi_time.day() as u32, 
-    )?;
-
-    let naive_time = NaiveTime::from_hms_milli_opt(
-        uefi_time.hour() as u32, 
-        uefi_time.minute() as u32, 
-        uefi_time.second() as u32, 
-        (uefi_time.nanosecond() / 1_000_000) as u32,
-    )?;
-
-    Some(naive_date.and_time(naive_time))
+    Some(boot_time()? + uptime())
+}
+
+/// Set the wall clock and the RTC to `time`, false if the RTC can't
+/// hold it
+pub fn set_now(time: NaiveDateTime) -> bool {
+    if !rtc::set(time) {
+        return false;
+    }
+    *BOOT_TIME.write() = Some(time - uptime());
+    true
+}
+
+/// Wall clock time in nanoseconds since the Unix epoch
+pub fn realtime_ns() -> Option<u64> {
+    let boot = boot_time()?.and_utc().timestamp_nanos_opt()?;
+    Some(boot as u64 + monotonic_ns())
 }
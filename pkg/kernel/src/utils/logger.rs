@@ -1,5 +1,25 @@
+use crate::fs::{self, OpenFlags};
+use crate::proc::{self, ProcessId};
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
 use log::{Metadata, Record};
+use spin::{Mutex, Once};
+use x86_64::instructions::interrupts;
 
+/// Bytes of log kept for `/dev/kmsg`
+const KMSG_SIZE: usize = 16 * 1024;
+
+/// Where `klogd` saves the kernel log, rewritten on every boot
+const KMSG_LOG: &str = "/kmsg.log";
+
+/// How often `klogd` writes out new log lines
+const KLOGD_INTERVAL_NS: u64 = 1_000_000_000;
+
+static KLOGD: Once<ProcessId> = Once::new();
+static KLOGD_STOP: AtomicBool = AtomicBool::new(false);
+
+// the logger starts before the heap, so the buffer is static
+static KMSG: Mutex<KmsgBuffer> = Mutex::new(KmsgBuffer::new());
 
 pub fn init() {
     static LOGGER: Logger = Logger;
@@ -21,7 +41,118 @@ impl log::Log for Logger {
     fn log(&self, record: &Record) {
         // FIXME: Implement the logger with serial output
         println!("{}",record.args());
+
+        // never wait for the buffer, the holder may be who we interrupted
+        if let Some(mut kmsg) = KMSG.try_lock() {
+            let _ = writeln!(kmsg, "[{}] {}", record.level(), record.args());
+        }
     }
 
     fn flush(&self) {}
 }
+
+/// Ring buffer of the latest log output
+struct KmsgBuffer {
+    buf: [u8; KMSG_SIZE],
+    /// Bytes written since boot
+    written: u64,
+}
+
+impl KmsgBuffer {
+    const fn new() -> Self {
+        Self {
+            buf: [0; KMSG_SIZE],
+            written: 0,
+        }
+    }
+
+    /// Returns the bytes read and the offset after them
+    fn read(&self, offset: u64, out: &mut [u8]) -> (usize, u64) {
+        // whatever was overwritten is skipped
+        let oldest = self.written.saturating_sub(KMSG_SIZE as u64);
+        let mut pos = offset.max(oldest);
+        let mut count = 0;
+
+        while pos < self.written && count < out.len() {
+            out[count] = self.buf[pos as usize % KMSG_SIZE];
+            pos += 1;
+            count += 1;
+        }
+        (count, pos)
+    }
+}
+
+impl Write for KmsgBuffer {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &byte in s.as_bytes() {
+            self.buf[self.written as usize % KMSG_SIZE] = byte;
+            self.written += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Read the kernel log starting at byte `offset` since boot, returns the
+/// bytes read and the offset to continue at
+pub fn read_kmsg(offset: u64, buf: &mut [u8]) -> (usize, u64) {
+    x86_64::instructions::interrupts::without_interrupts(|| KMSG.lock().read(offset, buf))
+}
+
+/// Start `klogd`, which copies `/dev/kmsg` to `KMSG_LOG`, needs the root
+/// filesystem
+pub fn start_klogd() {
+    KLOGD.call_once(|| proc::spawn_kernel_thread("klogd", klogd, 0));
+}
+
+/// Let `klogd` write out the last lines and wait for it
+pub fn stop_klogd() {
+    if let Some(&pid) = KLOGD.get() {
+        KLOGD_STOP.store(true, Ordering::Release);
+        proc::join_kernel_thread(pid);
+    }
+}
+
+fn klogd(_arg: usize) -> isize {
+    // files are used with interrupts off, like in a syscall
+    let flags = OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE;
+    let mut file = match interrupts::without_interrupts(|| fs::open("/", KMSG_LOG, flags)) {
+        Ok(file) => file,
+        Err(e) => {
+            warn!("klogd: failed to open {}: {:?}", KMSG_LOG, e);
+            return -1;
+        }
+    };
+
+    let mut offset = 0;
+    let mut buf = [0u8; 512];
+    let ret = loop {
+        let stop = KLOGD_STOP.load(Ordering::Acquire);
+
+        let written = loop {
+            let (count, next) = read_kmsg(offset, &mut buf);
+            if count == 0 {
+                break Ok(());
+            }
+            offset = next;
+            if let Err(e) = interrupts::without_interrupts(|| file.write(&buf[..count])) {
+                break Err(e);
+            }
+        };
+        if let Err(e) = written {
+            warn!("klogd: failed to write {}: {:?}", KMSG_LOG, e);
+            break -1;
+        }
+
+        if stop {
+            break 0;
+        }
+
+        let until = crate::clock::monotonic_ns() + KLOGD_INTERVAL_NS;
+        while crate::clock::monotonic_ns() < until && !KLOGD_STOP.load(Ordering::Acquire) {
+            x86_64::instructions::hlt();
+        }
+    };
+
+    interrupts::without_interrupts(|| drop(file));
+    ret
+}
@@ -1,7 +1,10 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
 use alloc::string::String;
 use alloc::collections::BTreeMap;
 use spin::mutex::Mutex;
 use crate::input;
+use crate::fs::{File, FsError, FsResult, PollEvents};
 
 #[derive(Debug, Clone)]
 pub enum StdIO {
@@ -12,7 +15,7 @@ pub enum StdIO {
 
 #[derive(Debug)]
 pub struct ResourceSet {
-    pub handles: BTreeMap<u8, Mutex<Resource>>,
+    pub handles: BTreeMap<u8, Arc<Mutex<Resource>>>,
 }
 
 impl Default for ResourceSet {
@@ -30,47 +33,52 @@ impl Default for ResourceSet {
 }
 
 impl ResourceSet {
-    pub fn open(&mut self, res: Resource) -> u8 {
-        let fd = self.handles.len() as u8;
-        self.handles.insert(fd, Mutex::new(res));
-        fd
+    /// Insert the resource at the lowest free fd
+    pub fn open(&mut self, res: Resource) -> Option<u8> {
+        let fd = (0..=u8::MAX).find(|fd| !self.handles.contains_key(fd))?;
+        self.handles.insert(fd, Arc::new(Mutex::new(res)));
+        Some(fd)
     }
 
     pub fn close(&mut self, fd: u8) -> bool {
         self.handles.remove(&fd).is_some()
     }
 
-    pub fn read(&self, fd: u8, buf: &mut [u8]) -> isize {
-        if let Some(count) = self.handles.get(&fd).and_then(|h| h.lock().read(buf)) {
-            count as isize
-        } else {
-            -1
-        }
+    /// The resource behind `fd`, usable after the set is unlocked
+    pub fn get(&self, fd: u8) -> Option<Arc<Mutex<Resource>>> {
+        self.handles.get(&fd).cloned()
     }
 
-    pub fn write(&self, fd: u8, buf: &[u8]) -> isize {
-        if let Some(count) = self.handles.get(&fd).and_then(|h| h.lock().write(buf)) {
-            count as isize
-        } else {
-            -1
-        }
+    /// Which of `events` are ready on `fd`, errors are always reported
+    ///
+    /// Also called from the timer interrupt, so a busy resource counts
+    /// as not ready instead of waiting for it.
+    pub fn poll(&self, fd: u8, events: PollEvents) -> PollEvents {
+        let Some(res) = self.handles.get(&fd) else {
+            return PollEvents::NVAL;
+        };
+        let ready = res.try_lock().map_or(PollEvents::empty(), |res| res.poll());
+        ready & (events | PollEvents::ERR | PollEvents::HUP)
     }
 }
 
 #[derive(Debug)]
 pub enum Resource {
     Console(StdIO),
+    File(Box<dyn File>),
+    /// A message queue opened with `MsgCtl(IPC_OPEN)`
+    MsgQueue(u32),
     Null,
 }
 
 impl Resource {
-    pub fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
+    pub fn read(&mut self, buf: &mut [u8]) -> FsResult<usize> {
         match self {
             Resource::Console(stdio) => match stdio {
                 StdIO::Stdin => {
                     // FIXME: just read from kernel input buffer
                     if buf.is_empty(){
-                        return Some(0)
+                        return Ok(0)
                     }
                     else{
                         // if let mut key = input::get_line(){
@@ -80,28 +88,72 @@ impl Resource {
                         let ch = input::get_char_as_u8();
                         buf[0] = ch;
                     }
-                    Some(buf.len())
+                    Ok(buf.len())
                 }
-                _ => None,
+                _ => Err(FsError::BadDescriptor),
             },
-            Resource::Null => Some(0),
+            Resource::File(file) => file.read(buf),
+            // the queue was removed under us
+            Resource::MsgQueue(id) => {
+                crate::proc::msg_read(*id, buf).ok_or(FsError::BadDescriptor)
+            }
+            Resource::Null => Ok(0),
         }
     }
 
-    pub fn write(&mut self, buf: &[u8]) -> Option<usize> {
+    pub fn write(&mut self, buf: &[u8]) -> FsResult<usize> {
         match self {
             Resource::Console(stdio) => match *stdio {
-                StdIO::Stdin => None,
+                StdIO::Stdin => Err(FsError::BadDescriptor),
                 StdIO::Stdout => {
                     print!("{}", String::from_utf8_lossy(buf));
-                    Some(buf.len())
+                    Ok(buf.len())
                 }
                 StdIO::Stderr => {
                     warn!("{}", String::from_utf8_lossy(buf));
-                    Some(buf.len())
+                    Ok(buf.len())
+                }
+            },
+            Resource::File(file) => file.write(buf),
+            Resource::MsgQueue(id) => {
+                crate::proc::msg_write(*id, buf).ok_or(FsError::BadDescriptor)
+            }
+            Resource::Null => Ok(buf.len()),
+        }
+    }
+
+    /// What `read` and `write` can do right now without waiting
+    pub fn poll(&self) -> PollEvents {
+        match self {
+            Resource::Console(StdIO::Stdin) => {
+                if input::has_key() {
+                    PollEvents::IN
+                } else {
+                    PollEvents::empty()
                 }
+            }
+            Resource::Console(_) => PollEvents::OUT,
+            Resource::File(file) => file.poll(),
+            Resource::MsgQueue(id) => crate::proc::msg::MSG_QUEUES
+                .try_lock()
+                .map_or(PollEvents::empty(), |queues| queues.poll(*id)),
+            Resource::Null => PollEvents::IN | PollEvents::OUT,
+        }
+    }
+}
+
+impl core::fmt::Display for Resource {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Resource::Console(StdIO::Stdin) => write!(f, "stdin"),
+            Resource::Console(StdIO::Stdout) => write!(f, "stdout"),
+            Resource::Console(StdIO::Stderr) => write!(f, "stderr"),
+            Resource::File(file) => match file.path() {
+                Some(path) => write!(f, "{}", path),
+                None => write!(f, "{:?}", file),
             },
-            Resource::Null => Some(buf.len()),
+            Resource::MsgQueue(id) => write!(f, "msg:[{}]", id),
+            Resource::Null => write!(f, "null"),
         }
     }
 }
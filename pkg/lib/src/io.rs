@@ -22,6 +22,12 @@ impl Stdin {
         None
     }
 
+    /// Sleep until there is input, instead of spinning on `read`
+    fn wait_input(&self) {
+        let mut fds = [PollFd::new(0, PollEvents::IN)];
+        sys_poll(&mut fds, -1);
+    }
+
     pub fn read_line(&self) -> String {
         // FIXME: allocate string
         let mut line = String::new();
@@ -31,19 +37,21 @@ impl Stdin {
         // FIXME: handle backspace / enter...
         let mut buf = [0; 4];
         loop{
-            if let Some(char) = self.read_char_with_buf(&mut buf){     
-                match char{
-                    '\0' => continue,
-                    '\x0D' =>{
-                        break;
-                    }
-                    '\x7F' => {  // 退格
-                        line.pop();
-                    }
-                    _ => {
-                        self::print!("{}",char);
-                        line.push(char);
-                    }
+            let Some(char) = self.read_char_with_buf(&mut buf) else {
+                self.wait_input();
+                continue;
+            };
+            match char{
+                '\0' => continue,
+                '\x0D' =>{
+                    break;
+                }
+                '\x7F' => {  // 退格
+                    line.pop();
+                }
+                _ => {
+                    self::print!("{}",char);
+                    line.push(char);
                 }
             }
         }
@@ -0,0 +1,139 @@
+//! Message queues and shared memory on top of the System V style syscalls
+
+use crate::*;
+
+/// A handle to a kernel message queue, it stays alive until removed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageQueue {
+    id: u32,
+}
+
+impl MessageQueue {
+    /// Open the queue of `key`, `None` if it does not exist
+    pub fn get(key: u32) -> Option<Self> {
+        sys_msg_get(key, IpcFlags::empty()).map(|id| Self { id })
+    }
+
+    /// Open the queue of `key`, create it if needed
+    pub fn create(key: u32) -> Option<Self> {
+        sys_msg_get(key, IpcFlags::CREATE).map(|id| Self { id })
+    }
+
+    /// A new queue that can only be shared by passing it on, e.g. by fork
+    pub fn private() -> Option<Self> {
+        sys_msg_get(IPC_PRIVATE, IpcFlags::CREATE).map(|id| Self { id })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Send a message of `mtype` (> 0), blocks while the queue is full
+    pub fn send(&self, mtype: i64, data: &[u8]) -> bool {
+        sys_msg_send(self.id, mtype, data, IpcFlags::empty())
+    }
+
+    /// Send without blocking, false if the queue is full
+    pub fn try_send(&self, mtype: i64, data: &[u8]) -> bool {
+        sys_msg_send(self.id, mtype, data, IpcFlags::NOWAIT)
+    }
+
+    /// Receive a message, blocks until there is one
+    ///
+    /// `mtype` 0 takes any message, a positive value only that type and a
+    /// negative value the lowest type up to `-mtype`. Returns the type and
+    /// length of the message.
+    pub fn recv(&self, mtype: i64, buf: &mut [u8]) -> Option<(i64, usize)> {
+        sys_msg_receive(self.id, mtype, buf, IpcFlags::empty())
+    }
+
+    /// Receive without blocking, `None` if there is no such message
+    pub fn try_recv(&self, mtype: i64, buf: &mut [u8]) -> Option<(i64, usize)> {
+        sys_msg_receive(self.id, mtype, buf, IpcFlags::NOWAIT)
+    }
+
+    pub fn stat(&self) -> Option<MsqStat> {
+        sys_msg_stat(self.id)
+    }
+
+    /// A file descriptor for polling the queue, reading it takes the
+    /// first message and writing it sends one of type 1
+    pub fn open_fd(&self) -> Option<u8> {
+        sys_msg_open_fd(self.id)
+    }
+
+    pub fn set_capacity(&self, max_bytes: usize) -> bool {
+        sys_msg_set_max_bytes(self.id, max_bytes)
+    }
+
+    /// Destroy the queue, blocked senders and receivers fail
+    pub fn remove(self) -> bool {
+        sys_msg_remove(self.id)
+    }
+}
+
+/// A handle to a kernel shared memory segment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SharedMemory {
+    id: u32,
+    size: usize,
+}
+
+impl SharedMemory {
+    fn from_id(id: u32) -> Option<Self> {
+        let stat = sys_shm_stat(id)?;
+        Some(Self {
+            id,
+            size: stat.size as usize,
+        })
+    }
+
+    /// Open the segment of `key`, `None` if it does not exist
+    pub fn get(key: u32) -> Option<Self> {
+        sys_shm_get(key, 0, IpcFlags::empty()).and_then(Self::from_id)
+    }
+
+    /// Open the segment of `key`, create it with `size` bytes if needed
+    pub fn create(key: u32, size: usize) -> Option<Self> {
+        sys_shm_get(key, size, IpcFlags::CREATE).and_then(Self::from_id)
+    }
+
+    /// A new segment that can only be shared by passing its id on
+    pub fn private(size: usize) -> Option<Self> {
+        sys_shm_get(IPC_PRIVATE, size, IpcFlags::CREATE).and_then(Self::from_id)
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Size in bytes, rounded up to whole pages
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Map the segment where the kernel finds room
+    pub fn attach(&self) -> Option<&'static mut [u8]> {
+        self.attach_at(0)
+    }
+
+    /// Map the segment at the page aligned `addr`
+    pub fn attach_at(&self, addr: usize) -> Option<&'static mut [u8]> {
+        let ptr = sys_shm_attach(self.id, addr)?;
+        Some(unsafe { core::slice::from_raw_parts_mut(ptr, self.size) })
+    }
+
+    /// Unmap a slice returned by `attach`, it must not be used afterwards
+    pub fn detach(&self, mem: &mut [u8]) -> bool {
+        sys_shm_detach(mem.as_ptr())
+    }
+
+    pub fn stat(&self) -> Option<ShmStat> {
+        sys_shm_stat(self.id)
+    }
+
+    /// Free the segment once the last mapping is gone
+    pub fn remove(self) -> bool {
+        sys_shm_remove(self.id)
+    }
+}
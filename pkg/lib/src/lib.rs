@@ -12,7 +12,9 @@ extern crate syscall_def;
 #[macro_use]
 pub mod io;
 pub mod allocator;
+pub mod ipc;
 pub mod sync;
+pub mod thread;
 pub extern crate alloc;
 
 mod syscall;
@@ -5,6 +5,18 @@ use core::{
 
 use crate::*;
 
+mod barrier;
+mod condvar;
+mod mutex;
+mod once;
+mod rwlock;
+
+pub use barrier::{Barrier, BarrierWaitResult};
+pub use condvar::Condvar;
+pub use mutex::{Mutex, MutexGuard};
+pub use once::Once;
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
 pub struct SpinLock {
     bolt: AtomicBool,
 }
@@ -49,8 +61,22 @@ impl Semaphore {
 
     /* FIXME: other functions with syscall... */
 
+    /// Use a semaphore another process has created with `init`
+    #[inline(always)]
+    pub fn open(&self) -> bool {
+        sys_open_sem(self.key)
+    }
+
+    #[inline(always)]
+    pub fn close(&self) -> bool {
+        sys_close_sem(self.key)
+    }
+
+    /// Remove the name and close it, processes that still have it open
+    /// can go on using it
     pub fn remove(&self) {
         sys_remove_sem(self.key);
+        sys_close_sem(self.key);
     }
 
     pub fn signal(&self){
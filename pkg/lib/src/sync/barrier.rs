@@ -0,0 +1,56 @@
+//! Let a fixed number of threads meet before any of them goes on
+
+use super::{Condvar, Mutex};
+
+pub struct Barrier {
+    lock: Mutex<BarrierState>,
+    cvar: Condvar,
+    num_threads: usize,
+}
+
+struct BarrierState {
+    count: usize,
+    /// Bumped every time the barrier opens, so it can be reused
+    generation: usize,
+}
+
+/// Returned by [`Barrier::wait`], exactly one thread is the leader
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+impl Barrier {
+    pub const fn new(n: usize) -> Self {
+        Self {
+            lock: Mutex::new(BarrierState {
+                count: 0,
+                generation: 0,
+            }),
+            cvar: Condvar::new(),
+            num_threads: n,
+        }
+    }
+
+    /// Block until `n` threads have called `wait`
+    pub fn wait(&self) -> BarrierWaitResult {
+        let mut state = self.lock.lock();
+        let generation = state.generation;
+        state.count += 1;
+
+        if state.count < self.num_threads {
+            while generation == state.generation {
+                state = self.cvar.wait(state);
+            }
+            BarrierWaitResult(false)
+        } else {
+            state.count = 0;
+            state.generation = state.generation.wrapping_add(1);
+            self.cvar.notify_all();
+            BarrierWaitResult(true)
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Condition variable for `Mutex`
+//!
+//! Waiters sleep on a sequence number that every notify bumps, so a
+//! notify between unlocking the mutex and going to sleep isn't lost.
+
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use super::{mutex, MutexGuard};
+use crate::*;
+
+pub struct Condvar {
+    seq: AtomicU32,
+    /// Lock word of the mutex used with this condvar, 0 if none yet
+    mutex: AtomicUsize,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+            mutex: AtomicUsize::new(0),
+        }
+    }
+
+    /// Unlock the mutex, sleep until notified and lock it again
+    ///
+    /// Wakeups may be spurious, check the condition in a loop (or use
+    /// [`Condvar::wait_while`]).
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let mutex = guard.mutex();
+        self.mutex
+            .store(mutex.state() as *const AtomicU32 as usize, Ordering::Relaxed);
+
+        let seq = self.seq.load(Ordering::Relaxed);
+        drop(guard);
+        sys_futex_wait(&self.seq, seq);
+
+        mutex.lock_contended();
+        unsafe { MutexGuard::from_locked(mutex) }
+    }
+
+    pub fn wait_while<'a, T>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: impl FnMut(&mut T) -> bool,
+    ) -> MutexGuard<'a, T> {
+        while condition(&mut *guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        sys_futex_wake(&self.seq, 1);
+    }
+
+    /// Wake up everyone, one at a time: all but one waiter are moved to
+    /// the mutex instead of racing for it
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+
+        let mutex = self.mutex.load(Ordering::Relaxed) as *const AtomicU32;
+        match unsafe { mutex.as_ref() } {
+            Some(mutex) => {
+                sys_futex_requeue(&self.seq, mutex);
+                mutex::wake_requeued(mutex);
+            }
+            None => {
+                sys_futex_wake(&self.seq, usize::MAX);
+            }
+        }
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,55 @@
+//! Run an initializer exactly once, others sleep until it is done
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::*;
+
+const INCOMPLETE: u32 = 0;
+const RUNNING: u32 = 1;
+const COMPLETE: u32 = 2;
+
+pub struct Once {
+    state: AtomicU32,
+}
+
+impl Once {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(INCOMPLETE),
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+
+    /// Run `f` if no one has yet, otherwise wait until the one who does
+    /// is finished
+    pub fn call_once(&self, f: impl FnOnce()) {
+        loop {
+            match self.state.compare_exchange(
+                INCOMPLETE,
+                RUNNING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    f();
+                    self.state.store(COMPLETE, Ordering::Release);
+                    sys_futex_wake(&self.state, usize::MAX);
+                    return;
+                }
+                Err(COMPLETE) => return,
+                Err(state) => {
+                    sys_futex_wait(&self.state, state);
+                }
+            }
+        }
+    }
+}
+
+impl Default for Once {
+    fn default() -> Self {
+        Self::new()
+    }
+}
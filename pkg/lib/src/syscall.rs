@@ -1,6 +1,20 @@
 use syscall_def::Syscall;
 use chrono::{DateTime,Utc};
 
+pub use syscall_def::fs::{DirEntry, FileStat, FileType, OpenFlags, PollEvents, PollFd, Whence};
+pub use syscall_def::proc::{
+    AppRecord, ProcessRecord, ProcessStatus, ARCH_GET_FS, ARCH_SET_FS, FUTEX_REQUEUE, FUTEX_WAIT,
+    FUTEX_WAKE, SEM_CLOSE, SEM_CREATE, SEM_EXISTS, SEM_NOT_FOUND, SEM_OK, SEM_OPEN, SEM_SIGNAL,
+    SEM_UNLINK, SEM_WAIT, THREAD_JOIN_ERROR,
+};
+pub use syscall_def::ipc::{
+    IpcFlags, MsgBuf, MsqStat, ShmStat, IPC_OPEN, IPC_PRIVATE, IPC_RMID, IPC_SET, IPC_STAT,
+};
+pub use syscall_def::time::{
+    TimeSpec, CLOCK_MONOTONIC, CLOCK_PROCESS_CPUTIME_ID, CLOCK_REALTIME,
+};
+use core::sync::atomic::AtomicU32;
+
 #[inline(always)]
 pub fn sys_write(fd: u8, buf: &[u8]) -> Option<usize> {
     let ret = syscall!(
@@ -31,6 +45,93 @@ pub fn sys_read(fd: u8, buf: &mut [u8]) -> Option<usize> {
     }
 }
 
+#[inline(always)]
+pub fn sys_open(path: &str, flags: OpenFlags) -> Option<u8> {
+    let ret = syscall!(
+        Syscall::Open,
+        path.as_ptr() as u64,
+        path.len() as u64,
+        flags.bits()
+    ) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some(ret as u8)
+    }
+}
+
+#[inline(always)]
+pub fn sys_close(fd: u8) -> bool {
+    syscall!(Syscall::Close, fd as u64) == 0
+}
+
+#[inline(always)]
+pub fn sys_path_stat(path: &str) -> Option<FileStat> {
+    let mut stat = FileStat::default();
+    let ret = syscall!(
+        Syscall::PathStat,
+        path.as_ptr() as u64,
+        path.len() as u64,
+        &mut stat as *mut FileStat
+    ) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some(stat)
+    }
+}
+
+#[inline(always)]
+pub fn sys_fstat(fd: u8) -> Option<FileStat> {
+    let mut stat = FileStat::default();
+    let ret = syscall!(Syscall::FStat, fd as u64, &mut stat as *mut FileStat) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some(stat)
+    }
+}
+
+#[inline(always)]
+pub fn sys_seek(fd: u8, offset: i64, whence: Whence) -> Option<usize> {
+    let ret = syscall!(Syscall::Seek, fd as u64, offset, whence as usize) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some(ret as usize)
+    }
+}
+
+#[inline(always)]
+pub fn sys_getdents(fd: u8, buf: &mut [DirEntry]) -> Option<usize> {
+    let ret = syscall!(
+        Syscall::Getdents,
+        fd as u64,
+        buf.as_mut_ptr() as u64,
+        buf.len() as u64
+    ) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some(ret as usize)
+    }
+}
+
+#[inline(always)]
+pub fn sys_getcwd(buf: &mut [u8]) -> Option<&str> {
+    let ret = syscall!(Syscall::Getcwd, buf.as_mut_ptr() as u64, buf.len() as u64) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        core::str::from_utf8(&buf[..ret as usize]).ok()
+    }
+}
+
+#[inline(always)]
+pub fn sys_chdir(path: &str) -> bool {
+    syscall!(Syscall::Chdir, path.as_ptr() as u64, path.len() as u64) == 0
+}
+
 #[inline(always)]
 pub fn sys_wait_pid(pid: u16) -> usize {
     // FIXME: try to get the return value for process
@@ -53,6 +154,28 @@ pub fn sys_stat() {
     syscall!(Syscall::Stat);
 }
 
+/// Fill `buf` with records of the preloaded apps, returns the total
+/// number of apps
+#[inline(always)]
+pub fn sys_list_app_info(buf: &mut [AppRecord]) -> usize {
+    syscall!(
+        Syscall::ListAppInfo,
+        buf.as_mut_ptr() as u64,
+        buf.len() as u64
+    )
+}
+
+/// Fill `buf` with process records, returns the total number of
+/// processes, which is more than `buf.len()` if the buffer was too small
+#[inline(always)]
+pub fn sys_list_process(buf: &mut [ProcessRecord]) -> usize {
+    syscall!(
+        Syscall::ListProcess,
+        buf.as_mut_ptr() as u64,
+        buf.len() as u64
+    )
+}
+
 #[inline(always)]
 pub fn sys_allocate(layout: &core::alloc::Layout) -> *mut u8 {
     syscall!(Syscall::Allocate, layout as *const _) as *mut u8
@@ -80,6 +203,13 @@ pub fn sys_exit(pid: isize) /*-> !*/ {
     unreachable!("This process should be terminated by now.")
 }
 
+/// Reset the machine, never returns
+#[inline(always)]
+pub fn sys_reboot() -> ! {
+    syscall!(Syscall::Reboot);
+    unreachable!("The machine should be resetting by now.")
+}
+
 #[inline(always)]
 pub fn sys_time() -> DateTime<Utc> {
     let time = syscall!(Syscall::Time) as i64;
@@ -87,27 +217,252 @@ pub fn sys_time() -> DateTime<Utc> {
     DateTime::from_timestamp(time / BILLION, (time % BILLION) as u32).unwrap_or_default()
 }
 
+/// Time of `clock`, one of the `CLOCK_*` ids in `syscall_def::time`
+#[inline(always)]
+pub fn sys_clock_gettime(clock: usize) -> Option<TimeSpec> {
+    let mut ts = TimeSpec::default();
+    match syscall!(Syscall::ClockGettime, clock, &mut ts as *mut TimeSpec as usize) {
+        0 => Some(ts),
+        _ => None,
+    }
+}
+
 #[inline(always)]
 pub fn sys_fork() -> u16 {
     syscall!(Syscall::Fork) as u16
 }
 
+/// Start a thread at `entry(arg)`, returns its tid or 0 on failure
+#[inline(always)]
+pub fn sys_thread_create(entry: extern "C" fn(usize) -> !, arg: usize) -> u16 {
+    syscall!(Syscall::ThreadCreate, entry as usize, arg) as u16
+}
+
+/// Wait for a thread of this process to exit and get its exit code
+#[inline(always)]
+pub fn sys_thread_join(tid: u16) -> Option<isize> {
+    let ret = syscall!(Syscall::ThreadJoin, tid as u64) as isize;
+    if ret == THREAD_JOIN_ERROR {
+        None
+    } else {
+        Some(ret)
+    }
+}
+
+#[inline(always)]
+pub fn sys_thread_exit(ret: isize) -> ! {
+    syscall!(Syscall::ThreadExit, ret as u64);
+    unreachable!("This thread should be terminated by now.")
+}
+
+/// Point `FS_BASE` of the calling thread at `addr`
+#[inline(always)]
+pub fn sys_set_fs_base(addr: usize) -> bool {
+    syscall!(Syscall::ArchPrctl, ARCH_SET_FS, addr) == 0
+}
+
+#[inline(always)]
+pub fn sys_get_fs_base() -> Option<usize> {
+    let mut addr = 0usize;
+    let ret = syscall!(Syscall::ArchPrctl, ARCH_GET_FS, &mut addr as *mut usize);
+    if ret == 0 {
+        Some(addr)
+    } else {
+        None
+    }
+}
+
+/// Sleep while `*futex == val`, false if it had changed already
+#[inline(always)]
+pub fn sys_futex_wait(futex: &AtomicU32, val: u32) -> bool {
+    syscall!(Syscall::Futex, futex as *const AtomicU32, FUTEX_WAIT, val) == 0
+}
+
+/// Wake up at most `count` threads sleeping on `futex`, returns how many
+#[inline(always)]
+pub fn sys_futex_wake(futex: &AtomicU32, count: usize) -> usize {
+    syscall!(Syscall::Futex, futex as *const AtomicU32, FUTEX_WAKE, count)
+}
+
+/// Wake up one thread sleeping on `futex` and move the others to `target`
+#[inline(always)]
+pub fn sys_futex_requeue(futex: &AtomicU32, target: &AtomicU32) -> usize {
+    syscall!(
+        Syscall::Futex,
+        futex as *const AtomicU32,
+        FUTEX_REQUEUE,
+        target as *const AtomicU32
+    )
+}
+
+/// Let `pid`, 0 for the caller, run only on the CPUs in `mask`
+#[inline(always)]
+pub fn sys_set_affinity(pid: u16, mask: u64) -> bool {
+    syscall!(Syscall::SchedSetAffinity, pid as usize, mask as usize) == 0
+}
+
+/// Mask of the online CPUs `pid`, 0 for the caller, may run on
+#[inline(always)]
+pub fn sys_get_affinity(pid: u16) -> Option<u64> {
+    match syscall!(Syscall::SchedGetAffinity, pid as usize) {
+        usize::MAX => None,
+        mask => Some(mask as u64),
+    }
+}
+
+/// Create the semaphore `key`, false if it exists already
 #[inline(always)]
 pub fn sys_new_sem(key: u32, value: usize) -> bool {
-    syscall!(Syscall::Sem, 0, key as usize, value) == 0
+    syscall!(Syscall::Sem, SEM_CREATE, key as usize, value) == SEM_OK
+}
+
+/// Open the semaphore `key` created by another process
+#[inline(always)]
+pub fn sys_open_sem(key: u32) -> bool {
+    syscall!(Syscall::Sem, SEM_OPEN, key as usize) == SEM_OK
+}
+
+#[inline(always)]
+pub fn sys_close_sem(key: u32) -> bool {
+    syscall!(Syscall::Sem, SEM_CLOSE, key as usize) == SEM_OK
 }
 
+/// Remove the name `key`, it can't be opened any more
 #[inline(always)]
 pub fn sys_remove_sem(key: u32) -> bool {
-    syscall!(Syscall::Sem, 1, key as usize) == 0
+    syscall!(Syscall::Sem, SEM_UNLINK, key as usize) == SEM_OK
 }
 
 #[inline(always)]
 pub fn sys_sem_signal(key: u32) -> bool {
-    syscall!(Syscall::Sem, 2, key as usize) == 0
+    syscall!(Syscall::Sem, SEM_SIGNAL, key as usize) == SEM_OK
 }
 
 #[inline(always)]
 pub fn sys_sem_wait(key: u32) -> bool {
-    syscall!(Syscall::Sem, 3, key as usize) == 0
-}
\ No newline at end of file
+    syscall!(Syscall::Sem, SEM_WAIT, key as usize) == SEM_OK
+}
+
+/// Wait until one of `fds` is ready, at most `timeout` milliseconds or
+/// forever if it is negative; returns how many entries have `revents`
+#[inline(always)]
+pub fn sys_poll(fds: &mut [PollFd], timeout: isize) -> Option<usize> {
+    let ret = syscall!(Syscall::Poll, fds.as_mut_ptr(), fds.len(), timeout) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some(ret as usize)
+    }
+}
+
+/// Find or create the message queue of `key`, returns its id
+#[inline(always)]
+pub fn sys_msg_get(key: u32, flags: IpcFlags) -> Option<u32> {
+    let ret = syscall!(Syscall::MsgGet, key as usize, flags.bits()) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some(ret as u32)
+    }
+}
+
+#[inline(always)]
+pub fn sys_msg_send(id: u32, mtype: i64, data: &[u8], flags: IpcFlags) -> bool {
+    let msg = MsgBuf::new(mtype, data);
+    syscall!(
+        Syscall::MsgSnd,
+        id as usize,
+        &msg as *const MsgBuf,
+        flags.bits()
+    ) == 0
+}
+
+/// Receive a message selected by `mtype`, returns its type and length
+#[inline(always)]
+pub fn sys_msg_receive(id: u32, mtype: i64, buf: &mut [u8], flags: IpcFlags) -> Option<(i64, usize)> {
+    let mut msg = MsgBuf::new_mut(mtype, buf);
+    let ret = syscall!(
+        Syscall::MsgRcv,
+        id as usize,
+        &mut msg as *mut MsgBuf,
+        flags.bits()
+    ) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some((msg.mtype, ret as usize))
+    }
+}
+
+#[inline(always)]
+pub fn sys_msg_stat(id: u32) -> Option<MsqStat> {
+    let mut stat = MsqStat::default();
+    let ret = syscall!(Syscall::MsgCtl, id as usize, IPC_STAT, &mut stat as *mut MsqStat);
+    (ret == 0).then_some(stat)
+}
+
+/// Change the capacity of a queue in bytes
+#[inline(always)]
+pub fn sys_msg_set_max_bytes(id: u32, max_bytes: usize) -> bool {
+    let stat = MsqStat {
+        max_bytes: max_bytes as u64,
+        ..Default::default()
+    };
+    syscall!(Syscall::MsgCtl, id as usize, IPC_SET, &stat as *const MsqStat) == 0
+}
+
+/// Open a message queue as a file descriptor for `sys_poll`
+#[inline(always)]
+pub fn sys_msg_open_fd(id: u32) -> Option<u8> {
+    let ret = syscall!(Syscall::MsgCtl, id as usize, IPC_OPEN, 0usize) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some(ret as u8)
+    }
+}
+
+#[inline(always)]
+pub fn sys_msg_remove(id: u32) -> bool {
+    syscall!(Syscall::MsgCtl, id as usize, IPC_RMID, 0usize) == 0
+}
+
+/// Find or create the shared memory segment of `key`, returns its id
+#[inline(always)]
+pub fn sys_shm_get(key: u32, size: usize, flags: IpcFlags) -> Option<u32> {
+    let ret = syscall!(Syscall::ShmGet, key as usize, size, flags.bits()) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some(ret as u32)
+    }
+}
+
+/// Map a segment at `addr`, or where the kernel likes if it is 0
+#[inline(always)]
+pub fn sys_shm_attach(id: u32, addr: usize) -> Option<*mut u8> {
+    let ret = syscall!(Syscall::ShmAt, id as usize, addr) as isize;
+    if ret.is_negative() {
+        None
+    } else {
+        Some(ret as *mut u8)
+    }
+}
+
+#[inline(always)]
+pub fn sys_shm_detach(addr: *const u8) -> bool {
+    syscall!(Syscall::ShmDt, addr) == 0
+}
+
+#[inline(always)]
+pub fn sys_shm_stat(id: u32) -> Option<ShmStat> {
+    let mut stat = ShmStat::default();
+    let ret = syscall!(Syscall::ShmCtl, id as usize, IPC_STAT, &mut stat as *mut ShmStat);
+    (ret == 0).then_some(stat)
+}
+
+/// Free the segment once nothing maps it any more
+#[inline(always)]
+pub fn sys_shm_remove(id: u32) -> bool {
+    syscall!(Syscall::ShmCtl, id as usize, IPC_RMID, 0usize) == 0
+}
@@ -0,0 +1,89 @@
+//! Threads sharing the address space of the process
+//!
+//! ```ignore
+//! let handle = thread::spawn(|| 6 * 7);
+//! assert_eq!(handle.join(), Some(42));
+//! ```
+//!
+//! All threads see the same memory, heap, fds and semaphores; each one
+//! has a stack of its own. When the process exits its threads go too.
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+
+use crate::*;
+
+type ThreadMain = Box<dyn FnOnce()>;
+
+/// Where the thread leaves its return value for `join`
+struct Packet<T>(UnsafeCell<Option<T>>);
+
+// written once by the thread, read by the joiner after it has exited
+unsafe impl<T: Send> Sync for Packet<T> {}
+
+/// Owned permission to join a thread
+///
+/// Dropping the handle detaches the thread, it keeps running.
+pub struct JoinHandle<T> {
+    tid: u16,
+    packet: Arc<Packet<T>>,
+}
+
+impl<T> JoinHandle<T> {
+    pub fn tid(&self) -> u16 {
+        self.tid
+    }
+
+    /// Wait for the thread to finish and take its return value
+    ///
+    /// `None` if the thread was killed before returning.
+    pub fn join(self) -> Option<T> {
+        sys_thread_join(self.tid)?;
+        unsafe { (*self.packet.0.get()).take() }
+    }
+}
+
+/// Run `f` in a new thread
+///
+/// Panics if the kernel can't create the thread.
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let packet = Arc::new(Packet(UnsafeCell::new(None)));
+    let their_packet = packet.clone();
+
+    let main: ThreadMain = Box::new(move || {
+        let ret = f();
+        unsafe { *their_packet.0.get() = Some(ret) };
+    });
+    // a thin pointer fits in the single argument register
+    let arg = Box::into_raw(Box::new(main));
+
+    let tid = sys_thread_create(thread_start, arg as usize);
+    if tid == 0 {
+        drop(unsafe { Box::from_raw(arg) });
+        panic!("failed to spawn thread");
+    }
+
+    JoinHandle { tid, packet }
+}
+
+/// Tid of the calling thread, the pid for the main thread
+#[inline]
+pub fn current() -> u16 {
+    sys_get_pid()
+}
+
+/// Exit the calling thread, `exit` of the main thread ends the process
+pub fn exit(ret: isize) -> ! {
+    sys_thread_exit(ret)
+}
+
+extern "C" fn thread_start(arg: usize) -> ! {
+    let main = unsafe { Box::from_raw(arg as *mut ThreadMain) };
+    main();
+    sys_thread_exit(0)
+}
@@ -1,12 +1,6 @@
-use chrono::TimeDelta;
-
 use crate::*;
 
+/// Sleep in the kernel: a poll on no fds that only times out
 pub fn sleep(millisecs: i64) {
-    let start = sys_time();
-    let dur: TimeDelta = TimeDelta::try_milliseconds(millisecs).unwrap();
-    let mut current = start;
-    while current - start < dur {
-        current = sys_time();
-    }
-}
\ No newline at end of file
+    sys_poll(&mut [], millisecs.max(0) as isize);
+}
@@ -0,0 +1,146 @@
+//! Data structures shared by the kernel and user space for file syscalls.
+
+use bitflags::bitflags;
+use num_enum::FromPrimitive;
+
+/// Max length of a file name returned by `Getdents`
+pub const DIRENT_NAME_MAX: usize = 64;
+
+bitflags! {
+    /// Flags for `Open`
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct OpenFlags: usize {
+        const READ      = 1 << 0;
+        const WRITE     = 1 << 1;
+        /// Create the file if it does not exist
+        const CREATE    = 1 << 2;
+        /// Truncate the file to zero length on open
+        const TRUNCATE  = 1 << 3;
+        /// Every write goes to the end of the file
+        const APPEND    = 1 << 4;
+        /// Fail unless the path is a directory
+        const DIRECTORY = 1 << 5;
+    }
+}
+
+/// `whence` argument of `Seek`
+#[repr(usize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
+pub enum Whence {
+    #[num_enum(default)]
+    Set = 0,
+    Current = 1,
+    End = 2,
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
+pub enum FileType {
+    #[num_enum(default)]
+    File = 0,
+    Directory = 1,
+    CharDevice = 2,
+    BlockDevice = 3,
+}
+
+/// Result of `FStat`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct FileStat {
+    pub ino: u64,
+    pub size: u64,
+    /// Last modification time in seconds since the unix epoch, 0 if unknown
+    pub modified: i64,
+    pub kind: FileType,
+}
+
+impl Default for FileStat {
+    fn default() -> Self {
+        Self {
+            ino: 0,
+            size: 0,
+            modified: 0,
+            kind: FileType::File,
+        }
+    }
+}
+
+/// One record written by `Getdents`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DirEntry {
+    pub size: u64,
+    pub kind: FileType,
+    pub name_len: u8,
+    pub name: [u8; DIRENT_NAME_MAX],
+}
+
+impl DirEntry {
+    pub fn new(name: &str, kind: FileType, size: u64) -> Self {
+        let mut entry = Self {
+            size,
+            kind,
+            ..Default::default()
+        };
+        entry.name_len = crate::copy_name(&mut entry.name, name);
+        entry
+    }
+
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len as usize]).unwrap_or("?")
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileType::Directory
+    }
+}
+
+impl Default for DirEntry {
+    fn default() -> Self {
+        Self {
+            size: 0,
+            kind: FileType::File,
+            name_len: 0,
+            name: [0; DIRENT_NAME_MAX],
+        }
+    }
+}
+
+bitflags! {
+    /// Events of a [`PollFd`], same values as Linux
+    #[repr(transparent)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct PollEvents: u16 {
+        /// There is data to read
+        const IN   = 0x001;
+        /// Writing would not block
+        const OUT  = 0x004;
+        /// Only in `revents`: the object is broken
+        const ERR  = 0x008;
+        /// Only in `revents`: the other end is gone
+        const HUP  = 0x010;
+        /// Only in `revents`: the fd is not open
+        const NVAL = 0x020;
+    }
+}
+
+/// One entry of the array passed to `Poll`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PollFd {
+    /// Negative entries are skipped
+    pub fd: i32,
+    pub events: PollEvents,
+    /// Written by the kernel
+    pub revents: PollEvents,
+}
+
+impl PollFd {
+    pub fn new(fd: u8, events: PollEvents) -> Self {
+        Self {
+            fd: fd as i32,
+            events,
+            revents: PollEvents::empty(),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Data structures shared by the kernel and user space for IPC syscalls.
+
+use bitflags::bitflags;
+
+/// Key that always gets a new object, never found by `MsgGet`
+pub const IPC_PRIVATE: u32 = 0;
+
+bitflags! {
+    /// Flags for `MsgGet`, `MsgSnd` and `MsgRcv`, same values as Linux
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct IpcFlags: usize {
+        /// Create the object if the key is not in use
+        const CREATE   = 0o1000;
+        /// With `CREATE`: fail if the key is in use
+        const EXCL     = 0o2000;
+        /// Fail instead of blocking
+        const NOWAIT   = 0o4000;
+        /// `MsgRcv`: truncate messages that don't fit the buffer
+        const NOERROR  = 0o10000;
+    }
+}
+
+/// `MsgCtl` commands
+pub const IPC_RMID: usize = 0;
+pub const IPC_SET: usize = 1;
+pub const IPC_STAT: usize = 2;
+/// Open the queue as a file descriptor that can be polled, reads take
+/// any message and writes send messages of type 1
+pub const IPC_OPEN: usize = 3;
+
+/// Largest message accepted by `MsgSnd`
+pub const MSG_MAX: usize = 8192;
+/// Default capacity of a queue in bytes
+pub const MSG_QUEUE_BYTES: usize = 16384;
+
+/// A message for `MsgSnd`, or the buffer for `MsgRcv`
+///
+/// For `MsgRcv`, `mtype` selects the message: 0 takes the first one,
+/// a positive value the first one of that type, a negative value the
+/// first one with the lowest type not above `-mtype`. The type of the
+/// received message is written back.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct MsgBuf {
+    pub mtype: i64,
+    pub ptr: u64,
+    pub len: u64,
+}
+
+impl MsgBuf {
+    pub fn new(mtype: i64, data: &[u8]) -> Self {
+        Self {
+            mtype,
+            ptr: data.as_ptr() as u64,
+            len: data.len() as u64,
+        }
+    }
+
+    pub fn new_mut(mtype: i64, buf: &mut [u8]) -> Self {
+        Self {
+            mtype,
+            ptr: buf.as_mut_ptr() as u64,
+            len: buf.len() as u64,
+        }
+    }
+}
+
+/// Written by `MsgCtl(IPC_STAT)`, `IPC_SET` reads `max_bytes`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MsqStat {
+    pub key: u32,
+    pub id: u32,
+    /// Messages in the queue
+    pub messages: u64,
+    /// Bytes in the queue
+    pub bytes: u64,
+    /// Capacity in bytes
+    pub max_bytes: u64,
+    /// Messages sent and received since the queue was created
+    pub sent: u64,
+    pub received: u64,
+    /// 0 if nothing has been sent / received yet
+    pub last_send_pid: u16,
+    pub last_recv_pid: u16,
+    /// Processes blocked on the queue
+    pub waiting: u16,
+}
+
+/// Largest shared memory segment accepted by `ShmGet`
+pub const SHM_MAX: usize = 16 * 1024 * 1024;
+
+/// Written by `ShmCtl(IPC_STAT)`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShmStat {
+    pub key: u32,
+    pub id: u32,
+    /// Size in bytes, a multiple of the page size
+    pub size: u64,
+    /// Address spaces the segment is mapped into
+    pub attached: u64,
+    /// Removed by `IPC_RMID`, freed when the last mapping goes away
+    pub removed: bool,
+}
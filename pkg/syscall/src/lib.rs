@@ -2,25 +2,73 @@
 
 use num_enum::FromPrimitive;
 
+pub mod fs;
+pub mod ipc;
 pub mod macros;
+pub mod proc;
+pub mod time;
+
+/// Copy as much of `name` as fits in `buf`, cut on a char boundary so
+/// the copy stays valid utf-8, returns the copied length
+pub(crate) fn copy_name(buf: &mut [u8], name: &str) -> u8 {
+    let mut len = name.len().min(buf.len());
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+    buf[..len].copy_from_slice(&name.as_bytes()[..len]);
+    len as u8
+}
 
 #[repr(usize)]
 #[derive(Clone, Debug, FromPrimitive)]
 pub enum Syscall {
     Read = 0,
     Write = 1,
+    Open = 2,
+    Close = 3,
+    PathStat = 4,
+    FStat = 5,
+    Poll = 7,
+    Seek = 8,
+
+    ShmGet = 29,
+    ShmAt = 30,
+    ShmCtl = 31,
 
     GetPid = 39,
-    
+
+    ThreadCreate = 56,
     Sem = 57, // 自定义
     Fork = 58,
     Spawn = 59,
     Exit = 60,
     WaitPid = 61,
 
+    ShmDt = 67,
+
+    MsgGet = 68,
+    MsgSnd = 69,
+    MsgRcv = 70,
+    MsgCtl = 71,
+
+    Getdents = 78,
+    Getcwd = 79,
+    Chdir = 80,
+
+    ArchPrctl = 158,
+    Reboot = 169,
+    Futex = 202,
+    SchedSetAffinity = 203,
+    SchedGetAffinity = 204,
+    ClockGettime = 228,
+
     // 
     Time = 1145,
 
+    ThreadExit = 65527,
+    ThreadJoin = 65528,
+    ListAppInfo = 65529,
+    ListProcess = 65530,
     ListApp = 65531,
     Stat = 65532,
     Allocate = 65533,
@@ -0,0 +1,135 @@
+//! Data structures shared by the kernel and user space for process syscalls.
+
+use num_enum::FromPrimitive;
+
+/// Max length of a process name in a [`ProcessRecord`]
+pub const PROC_NAME_MAX: usize = 32;
+
+/// Max length of an app name in an [`AppRecord`], same as the boot `AppList`
+pub const APP_NAME_MAX: usize = 16;
+
+/// Returned by `ThreadJoin` when the thread can't be joined
+pub const THREAD_JOIN_ERROR: isize = isize::MIN;
+
+/// `ArchPrctl` codes, same values as Linux
+pub const ARCH_SET_FS: usize = 0x1002;
+pub const ARCH_GET_FS: usize = 0x1003;
+
+/// `Futex` operations
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
+pub const FUTEX_REQUEUE: usize = 3;
+
+/// `Sem` operations, semaphores are named by a `u32` key shared by a
+/// program and the processes it forks
+pub const SEM_CREATE: usize = 0;
+pub const SEM_UNLINK: usize = 1;
+pub const SEM_SIGNAL: usize = 2;
+pub const SEM_WAIT: usize = 3;
+pub const SEM_OPEN: usize = 4;
+pub const SEM_CLOSE: usize = 5;
+
+/// `Sem` return codes
+pub const SEM_OK: usize = 0;
+pub const SEM_NOT_FOUND: usize = 1;
+pub const SEM_EXISTS: usize = 2;
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
+pub enum ProcessStatus {
+    Running = 0,
+    #[num_enum(default)]
+    Ready = 1,
+    Blocked = 2,
+    Dead = 3,
+}
+
+/// One record written by `ListProcess`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ProcessRecord {
+    pub pid: u16,
+    /// 0 if the process has no parent
+    pub ppid: u16,
+    pub status: ProcessStatus,
+    pub name_len: u8,
+    pub name: [u8; PROC_NAME_MAX],
+    pub ticks: u64,
+    /// Memory usage in bytes
+    pub memory: u64,
+}
+
+impl ProcessRecord {
+    pub fn new(pid: u16, ppid: u16, name: &str, status: ProcessStatus) -> Self {
+        let mut record = Self {
+            pid,
+            ppid,
+            status,
+            ..Default::default()
+        };
+        record.name_len = crate::copy_name(&mut record.name, name);
+        record
+    }
+
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len as usize]).unwrap_or("?")
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.status != ProcessStatus::Dead
+    }
+}
+
+impl Default for ProcessRecord {
+    fn default() -> Self {
+        Self {
+            pid: 0,
+            ppid: 0,
+            status: ProcessStatus::Ready,
+            name_len: 0,
+            name: [0; PROC_NAME_MAX],
+            ticks: 0,
+            memory: 0,
+        }
+    }
+}
+
+/// One record written by `ListAppInfo`, describing a preloaded ELF
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct AppRecord {
+    pub name_len: u8,
+    pub name: [u8; APP_NAME_MAX],
+    /// Size of the ELF file in bytes
+    pub size: u64,
+    pub entry: u64,
+    /// Number of `LOAD` segments
+    pub segments: u16,
+    /// Bytes of memory the `LOAD` segments occupy once mapped
+    pub memory: u64,
+}
+
+impl AppRecord {
+    pub fn new(name: &str) -> Self {
+        let mut record = Self::default();
+        record.name_len = crate::copy_name(&mut record.name, name);
+        record
+    }
+
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len as usize]).unwrap_or("?")
+    }
+}
+
+impl Default for AppRecord {
+    fn default() -> Self {
+        Self {
+            name_len: 0,
+            name: [0; APP_NAME_MAX],
+            size: 0,
+            entry: 0,
+            segments: 0,
+            memory: 0,
+        }
+    }
+}
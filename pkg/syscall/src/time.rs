@@ -0,0 +1,30 @@
+//! Data structures shared by the kernel and user space for clock syscalls.
+
+/// `ClockGettime` clock ids, same values as Linux
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+pub const CLOCK_PROCESS_CPUTIME_ID: usize = 2;
+
+pub const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeSpec {
+    pub sec: i64,
+    /// Always in `0..NANOS_PER_SEC`
+    pub nsec: i64,
+}
+
+impl TimeSpec {
+    pub fn from_nanos(nanos: u64) -> Self {
+        let nanos = nanos as i64;
+        Self {
+            sec: nanos / NANOS_PER_SEC,
+            nsec: nanos % NANOS_PER_SEC,
+        }
+    }
+
+    pub fn as_nanos(&self) -> i64 {
+        self.sec * NANOS_PER_SEC + self.nsec
+    }
+}
@@ -13,6 +13,8 @@ parser.add_argument('-i', '--intdbg', action='store_true',
                     help='Enable interrupt output for qemu')
 parser.add_argument('-m', '--memory', default='96M',
                     help='Set memory size for qemu, default is 96M')
+parser.add_argument('--smp', default='4',
+                    help='Set number of CPUs for qemu, default is 4')
 parser.add_argument('-o', '--output', default='-nographic',
                     help='Set output for qemu, default is -nographic')
 parser.add_argument('-p', '--profile', type=str, choices=['release', 'debug'],
@@ -83,7 +85,7 @@ def qemu(output: str = '-nographic', memory: str = '96M', debug: bool = False, i
         raise Exception('qemu-system-x86_64 not found in PATH')
 
     qemu_args = [qemu_exe, '-bios', args.bios, '-net', 'none', *output.split(),
-                 '-m', memory, '-drive', 'format=raw,file=fat:esp', '-snapshot']
+                 '-m', memory, '-smp', args.smp, '-drive', 'format=raw,file=fat:esp', '-snapshot']
 
     if debug:
         qemu_args += ['-s', '-S']